@@ -0,0 +1,73 @@
+/* C interface to the catcaller crate. build with `cargo build --release -p catcaller-ffi`, which
+ * gives libcatcaller_ffi.so / .dylib / .dll and a static library next to it.
+ * every call blocks until the headset is done with it. don't call them from the state callback. */
+
+#ifndef CATCALLER_H
+#define CATCALLER_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum {
+    CATCALLER_OK = 0,
+    CATCALLER_ERR_NULL_POINTER,
+    CATCALLER_ERR_INVALID_ARGUMENT,
+    CATCALLER_ERR_NO_ADAPTER,
+    CATCALLER_ERR_NOT_FOUND,
+    CATCALLER_ERR_NOT_CONNECTED,
+    CATCALLER_ERR_MISSING_CHARACTERISTIC,
+    CATCALLER_ERR_BLUETOOTH,
+    CATCALLER_ERR_NO_PRESET,
+} CatcallerError;
+
+/* -1 (0 for mode) for anything that isn't known yet */
+typedef struct {
+    bool connected;
+    int battery;        /* percent */
+    int mode;           /* 1-8 */
+    bool has_color;
+    uint8_t r, g, b;
+    int brightness;     /* 0-63 */
+    int speed;          /* 0-63 */
+    int audio_profile;  /* 0-3 */
+} CatcallerState;
+
+typedef struct CatcallerHeadset CatcallerHeadset;
+
+/* called from a thread of the library's, `state` is only valid during the call */
+typedef void (*CatcallerStateCallback)(const CatcallerState *state, void *user_data);
+
+/* scans for up to timeout_ms and connects to the first headset found */
+CatcallerError catcaller_connect(uint32_t timeout_ms, CatcallerHeadset **out);
+
+/* a headset that only exists in memory, NULL on failure */
+CatcallerHeadset *catcaller_simulated(void);
+
+CatcallerError catcaller_set_color(CatcallerHeadset *headset, uint8_t r, uint8_t g, uint8_t b);
+CatcallerError catcaller_set_mode(CatcallerHeadset *headset, uint8_t mode);                    /* 1-8 */
+CatcallerError catcaller_set_settings(CatcallerHeadset *headset, uint8_t brightness, uint8_t speed); /* 0-63, 0 = unchanged */
+CatcallerError catcaller_set_audio_profile(CatcallerHeadset *headset, uint8_t profile);        /* 0-3 */
+
+/* a [[presets]] entry from ~/.config/catcaller/config.toml, case-insensitive */
+CatcallerError catcaller_apply_preset(CatcallerHeadset *headset, const char *name);
+
+CatcallerError catcaller_battery(CatcallerHeadset *headset, int *out); /* -1 if not reported */
+CatcallerError catcaller_state(CatcallerHeadset *headset, CatcallerState *out);
+
+/* replaces the previous callback, NULL removes it */
+CatcallerError catcaller_on_state_change(CatcallerHeadset *headset, CatcallerStateCallback callback, void *user_data);
+
+/* disconnects and frees the handle */
+CatcallerError catcaller_disconnect(CatcallerHeadset *headset);
+
+const char *catcaller_error_message(CatcallerError error);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,209 @@
+"""Control Yowu headsets from Python, through the catcaller C library.
+
+    import catcaller
+
+    with catcaller.connect() as headset:
+        headset.set_color(255, 0, 0)
+        headset.apply_preset("on air")
+
+The library is looked up in $CATCALLER_LIB, next to this file, and in the cargo target directory
+(build it with `cargo build --release -p catcaller-ffi`). catcaller.simulated() gives a headset
+that only exists in memory, for trying scripts out.
+"""
+
+import ctypes
+import os
+import sys
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Callable, Optional
+
+__all__ = ["CatcallerError", "Headset", "State", "MODES", "connect", "simulated"]
+
+# in the order the headset numbers them, 1-8
+MODES = ["Default", "Flash", "Breath", "Rhythm", "Yowu", "Lights off", "Lights on", "?"]
+
+
+class _State(ctypes.Structure):
+    _fields_ = [
+        ("connected", ctypes.c_bool),
+        ("battery", ctypes.c_int),
+        ("mode", ctypes.c_int),
+        ("has_color", ctypes.c_bool),
+        ("r", ctypes.c_uint8),
+        ("g", ctypes.c_uint8),
+        ("b", ctypes.c_uint8),
+        ("brightness", ctypes.c_int),
+        ("speed", ctypes.c_int),
+        ("audio_profile", ctypes.c_int),
+    ]
+
+
+_CALLBACK = ctypes.CFUNCTYPE(None, ctypes.POINTER(_State), ctypes.c_void_p)
+
+
+def _library_names():
+    if sys.platform == "win32":
+        return ["catcaller_ffi.dll"]
+    if sys.platform == "darwin":
+        return ["libcatcaller_ffi.dylib"]
+    return ["libcatcaller_ffi.so"]
+
+
+def _load():
+    if "CATCALLER_LIB" in os.environ:
+        return ctypes.CDLL(os.environ["CATCALLER_LIB"])
+
+    here = Path(__file__).resolve().parent
+    target = here.parent.parent / "target"
+
+    for directory in [here, target / "release", target / "debug"]:
+        for name in _library_names():
+            if (directory / name).exists():
+                return ctypes.CDLL(str(directory / name))
+
+    return ctypes.CDLL(_library_names()[0])  # the system's library path
+
+
+_lib = _load()
+
+_handle = ctypes.c_void_p
+_lib.catcaller_connect.argtypes = [ctypes.c_uint32, ctypes.POINTER(_handle)]
+_lib.catcaller_simulated.restype = _handle
+_lib.catcaller_set_color.argtypes = [_handle, ctypes.c_uint8, ctypes.c_uint8, ctypes.c_uint8]
+_lib.catcaller_set_mode.argtypes = [_handle, ctypes.c_uint8]
+_lib.catcaller_set_settings.argtypes = [_handle, ctypes.c_uint8, ctypes.c_uint8]
+_lib.catcaller_set_audio_profile.argtypes = [_handle, ctypes.c_uint8]
+_lib.catcaller_apply_preset.argtypes = [_handle, ctypes.c_char_p]
+_lib.catcaller_battery.argtypes = [_handle, ctypes.POINTER(ctypes.c_int)]
+_lib.catcaller_state.argtypes = [_handle, ctypes.POINTER(_State)]
+_lib.catcaller_on_state_change.argtypes = [_handle, _CALLBACK, ctypes.c_void_p]
+_lib.catcaller_disconnect.argtypes = [_handle]
+_lib.catcaller_error_message.argtypes = [ctypes.c_int]
+_lib.catcaller_error_message.restype = ctypes.c_char_p
+
+
+class CatcallerError(Exception):
+    """A non-zero CatcallerError code from the library."""
+
+    def __init__(self, code: int):
+        self.code = code
+        super().__init__(_lib.catcaller_error_message(code).decode())
+
+
+def _check(code: int):
+    if code != 0:
+        raise CatcallerError(code)
+
+
+def _optional(value: int, unknown: int = -1) -> Optional[int]:
+    return None if value == unknown else value
+
+
+@dataclass
+class State:
+    """None for anything that isn't known yet."""
+
+    connected: bool
+    battery: Optional[int]
+    mode: Optional[int]
+    color: Optional[tuple]
+    brightness: Optional[int]
+    speed: Optional[int]
+    audio_profile: Optional[int]
+
+    @classmethod
+    def _from_c(cls, state: _State) -> "State":
+        return cls(
+            connected=state.connected,
+            battery=_optional(state.battery),
+            mode=_optional(state.mode, 0),
+            color=(state.r, state.g, state.b) if state.has_color else None,
+            brightness=_optional(state.brightness),
+            speed=_optional(state.speed),
+            audio_profile=_optional(state.audio_profile),
+        )
+
+
+class Headset:
+    """A connected headset, from connect() or simulated(). Every call blocks until it's done."""
+
+    def __init__(self, handle: int):
+        self._handle = handle
+        self._callback = None  # keeps the ctypes callback alive
+
+    def set_color(self, r: int, g: int, b: int):
+        _check(_lib.catcaller_set_color(self._handle, r, g, b))
+
+    def set_mode(self, mode):
+        """1-8, or a name from MODES."""
+        if isinstance(mode, str):
+            names = [name.lower() for name in MODES]
+            if mode.lower() not in names:
+                raise ValueError(f"no mode named {mode!r}")
+            mode = names.index(mode.lower()) + 1
+
+        _check(_lib.catcaller_set_mode(self._handle, mode))
+
+    def set_settings(self, brightness: int, speed: int):
+        """0-63 each, 0 leaves it as it is. bpm and duration in rhythm mode."""
+        _check(_lib.catcaller_set_settings(self._handle, brightness, speed))
+
+    def set_audio_profile(self, profile: int):
+        _check(_lib.catcaller_set_audio_profile(self._handle, profile))
+
+    def apply_preset(self, name: str):
+        """A [[presets]] entry from the GUI's config file."""
+        _check(_lib.catcaller_apply_preset(self._handle, name.encode()))
+
+    def battery(self) -> Optional[int]:
+        battery = ctypes.c_int()
+        _check(_lib.catcaller_battery(self._handle, ctypes.byref(battery)))
+        return _optional(battery.value)
+
+    def state(self) -> State:
+        state = _State()
+        _check(_lib.catcaller_state(self._handle, ctypes.byref(state)))
+        return State._from_c(state)
+
+    def on_state_change(self, callback: Optional[Callable[[State], None]]):
+        """Called from a library thread with every change. None removes it."""
+        if callback is None:
+            self._callback = None
+            _check(_lib.catcaller_on_state_change(self._handle, ctypes.cast(None, _CALLBACK), None))
+            return
+
+        self._callback = _CALLBACK(lambda state, _: callback(State._from_c(state.contents)))
+        _check(_lib.catcaller_on_state_change(self._handle, self._callback, None))
+
+    def disconnect(self):
+        if self._handle:
+            handle, self._handle = self._handle, None
+            _check(_lib.catcaller_disconnect(handle))
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *_):
+        self.disconnect()
+
+    def __del__(self):
+        try:
+            self.disconnect()
+        except CatcallerError:
+            pass
+
+
+def connect(timeout: float = 15.0) -> Headset:
+    """Scans for up to `timeout` seconds and connects to the first headset found."""
+    handle = _handle()
+    _check(_lib.catcaller_connect(int(timeout * 1000), ctypes.byref(handle)))
+    return Headset(handle.value)
+
+
+def simulated() -> Headset:
+    """A headset that only exists in memory."""
+    handle = _lib.catcaller_simulated()
+    if not handle:
+        raise RuntimeError("couldn't start the catcaller runtime")
+    return Headset(handle)
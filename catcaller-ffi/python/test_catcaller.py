@@ -0,0 +1,123 @@
+"""The bindings against the simulated headset. Needs the library built first:
+
+    cargo build -p catcaller-ffi
+    python3 -m unittest discover catcaller-ffi/python
+"""
+
+import os
+import tempfile
+import threading
+import time
+import unittest
+from pathlib import Path
+
+import catcaller
+
+
+class SimulatedTest(unittest.TestCase):
+    def setUp(self):
+        self.headset = catcaller.simulated()
+
+    def tearDown(self):
+        self.headset.disconnect()
+
+    def test_commands_show_up_in_the_state(self):
+        state = self.headset.state()
+        self.assertTrue(state.connected)
+        self.assertIsNone(state.mode)
+        self.assertIsNone(state.color)
+
+        self.headset.set_mode("breath")
+        self.headset.set_color(0, 128, 255)
+        self.headset.set_settings(40, 0)
+        self.headset.set_audio_profile(2)
+
+        state = self.headset.state()
+        self.assertEqual(state.mode, 3)
+        self.assertEqual(state.color, (0, 128, 255))
+        self.assertEqual(state.brightness, 40)
+        self.assertIsNone(state.speed)  # 0 leaves it as it is
+        self.assertEqual(state.audio_profile, 2)
+        self.assertIsNone(self.headset.battery())
+
+    def test_bad_arguments(self):
+        for call in [
+            lambda: self.headset.set_mode(9),
+            lambda: self.headset.set_settings(64, 0),
+            lambda: self.headset.set_audio_profile(4),
+        ]:
+            with self.assertRaises(catcaller.CatcallerError) as raised:
+                call()
+            self.assertEqual(str(raised.exception), "argument out of range")
+
+        with self.assertRaises(ValueError):
+            self.headset.set_mode("disco")
+
+        self.assertIsNone(self.headset.state().mode)
+
+    def test_state_callback(self):
+        seen = []
+        changed = threading.Event()
+
+        def on_change(state):
+            seen.append(state.mode)
+            changed.set()
+
+        self.headset.on_state_change(on_change)
+        self.headset.set_mode("yowu")
+        self.assertTrue(changed.wait(2))
+        self.assertEqual(seen[-1], 5)
+
+        self.headset.on_state_change(None)
+        calls = len(seen)
+        self.headset.set_mode(1)
+        time.sleep(0.1)
+        self.assertEqual(len(seen), calls)
+
+    def test_presets(self):
+        with tempfile.TemporaryDirectory() as home:
+            (Path(home) / "catcaller").mkdir()
+            (Path(home) / "catcaller" / "config.toml").write_text(
+                '[[presets]]\nname = "On Air"\nmode = 7\nrgb = [255, 0, 0]\nsettings = [63, 0]\n'
+            )
+
+            previous = os.environ.get("XDG_CONFIG_HOME")
+            os.environ["XDG_CONFIG_HOME"] = home
+            try:
+                self.headset.apply_preset("on air")
+                with self.assertRaises(catcaller.CatcallerError):
+                    self.headset.apply_preset("off air")
+            finally:
+                if previous is None:
+                    del os.environ["XDG_CONFIG_HOME"]
+                else:
+                    os.environ["XDG_CONFIG_HOME"] = previous
+
+        state = self.headset.state()
+        self.assertEqual((state.mode, state.color, state.brightness), (7, (255, 0, 0), 63))
+
+    def test_strobe_is_limited(self):
+        # a loop doing its worst: 40 switches between lights off and on, 25 ms apart
+        changes = []
+        self.headset.on_state_change(lambda state: changes.append((time.monotonic(), state.mode)))
+
+        for i in range(40):
+            self.headset.set_mode("lights off" if i % 2 == 0 else "lights on")
+            time.sleep(0.025)
+
+        time.sleep(1.2)
+        self.headset.on_state_change(None)
+
+        times, shown = [], None
+        for t, mode in changes:
+            if mode != shown:
+                times.append(t)
+                shown = mode
+
+        most = max(sum(1 for u in times if t <= u < t + 1) for t in times)
+        self.assertLessEqual(most, 6)
+        self.assertEqual(self.headset.state().mode, 7)  # still ends up where it was told to
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,563 @@
+// C ABI over the catcaller crate, declared in include/catcaller.h. every call blocks until the
+// headset is done with it. a handle owns a small tokio runtime that runs the bluetooth work and
+// the state callback, so the callback is called from that runtime's thread, not the caller's.
+
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::time::Duration;
+
+use catcaller::presets::{self, Preset};
+use catcaller::{sim, AudioProfile, Headset, Level, Lighting, Mode, Model, Rgb, Scanner, State};
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatcallerError {
+    Ok = 0,
+    NullPointer,
+    InvalidArgument,
+    NoAdapter,
+    NotFound,
+    NotConnected,
+    MissingCharacteristic,
+    Bluetooth,
+    NoPreset,
+}
+
+impl From<catcaller::Error> for CatcallerError {
+    fn from(e: catcaller::Error) -> Self {
+        match e {
+            catcaller::Error::NoAdapter => CatcallerError::NoAdapter,
+            catcaller::Error::NotFound => CatcallerError::NotFound,
+            catcaller::Error::NotConnected => CatcallerError::NotConnected,
+            catcaller::Error::MissingCharacteristic => CatcallerError::MissingCharacteristic,
+            _ => CatcallerError::Bluetooth,
+        }
+    }
+}
+
+// -1 / 0 for anything that isn't known
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CatcallerState {
+    pub connected: bool,
+    pub battery: c_int,
+    pub mode: c_int,
+    pub has_color: bool,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub brightness: c_int,
+    pub speed: c_int,
+    pub audio_profile: c_int,
+}
+
+impl From<&State> for CatcallerState {
+    fn from(state: &State) -> Self {
+        let lighting = state.lighting.unwrap_or_default();
+        let color = lighting.color.unwrap_or_default();
+        let level = |level: Option<Level>| level.map_or(-1, |level| level.get() as c_int);
+
+        Self {
+            connected: state.connected,
+            battery: state.battery.map_or(-1, c_int::from),
+            mode: lighting.mode.map_or(0, |mode| mode.number() as c_int),
+            has_color: lighting.color.is_some(),
+            r: color.r,
+            g: color.g,
+            b: color.b,
+            brightness: level(lighting.brightness),
+            speed: level(lighting.speed),
+            audio_profile: state.audio_profile.map_or(-1, |profile| profile.get() as c_int),
+        }
+    }
+}
+
+pub type CatcallerStateCallback = Option<unsafe extern "C" fn(state: *const CatcallerState, user_data: *mut c_void)>;
+
+// the callback's user data is the caller's business, we only hand it back
+struct UserData(*mut c_void);
+unsafe impl Send for UserData {}
+
+pub struct CatcallerHeadset {
+    runtime: Runtime,
+    headset: Headset,
+    callback: Option<JoinHandle<()>>,
+}
+
+impl CatcallerHeadset {
+    fn new(runtime: Runtime, headset: Headset) -> *mut Self {
+        Box::into_raw(Box::new(Self { runtime, headset, callback: None }))
+    }
+
+    fn set_lighting(&self, lighting: Lighting) -> CatcallerError {
+        result(self.runtime.block_on(self.headset.set_lighting(lighting)))
+    }
+}
+
+fn runtime() -> Option<Runtime> {
+    tokio::runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build().ok()
+}
+
+fn result(result: Result<(), catcaller::Error>) -> CatcallerError {
+    match result {
+        Ok(_) => CatcallerError::Ok,
+        Err(e) => e.into(),
+    }
+}
+
+// turns a handle pointer into a reference or returns NullPointer
+macro_rules! handle {
+    ($headset:expr) => {
+        match unsafe { $headset.as_ref() } {
+            Some(headset) => headset,
+            None => return CatcallerError::NullPointer,
+        }
+    };
+}
+
+/// Scans for up to `timeout_ms` and connects to the first headset found.
+///
+/// # Safety
+/// `out` must be valid for writing a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_connect(timeout_ms: u32, out: *mut *mut CatcallerHeadset) -> CatcallerError {
+    if out.is_null() {
+        return CatcallerError::NullPointer;
+    }
+
+    let Some(runtime) = runtime() else {
+        return CatcallerError::Bluetooth;
+    };
+
+    let headset = runtime.block_on(async {
+        let discovered = Scanner::new().await?.find(Duration::from_millis(timeout_ms as u64)).await?;
+        discovered.connect().await
+    });
+
+    match headset {
+        Ok(headset) => {
+            *out = CatcallerHeadset::new(runtime, headset);
+            CatcallerError::Ok
+        }
+
+        Err(e) => e.into(),
+    }
+}
+
+/// A headset that only exists in memory, for trying out scripts. NULL if no runtime could be started.
+#[no_mangle]
+pub extern "C" fn catcaller_simulated() -> *mut CatcallerHeadset {
+    match runtime() {
+        Some(runtime) => CatcallerHeadset::new(runtime, sim::simulated(Model::YowuSelkirk4).0),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `headset` must come from `catcaller_connect` or `catcaller_simulated` and not be disconnected yet.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_set_color(headset: *mut CatcallerHeadset, r: u8, g: u8, b: u8) -> CatcallerError {
+    handle!(headset).set_lighting(Lighting::new().color(Rgb::new(r, g, b)))
+}
+
+/// `mode` is 1-8.
+///
+/// # Safety
+/// `headset` must come from `catcaller_connect` or `catcaller_simulated` and not be disconnected yet.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_set_mode(headset: *mut CatcallerHeadset, mode: u8) -> CatcallerError {
+    let headset = handle!(headset);
+
+    match Mode::from_number(mode) {
+        Some(mode) => headset.set_lighting(Lighting::new().mode(mode)),
+        None => CatcallerError::InvalidArgument,
+    }
+}
+
+/// Brightness and speed (bpm and duration in rhythm mode), 0-63 each. 0 leaves it as it is.
+///
+/// # Safety
+/// `headset` must come from `catcaller_connect` or `catcaller_simulated` and not be disconnected yet.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_set_settings(headset: *mut CatcallerHeadset, brightness: u8, speed: u8) -> CatcallerError {
+    let headset = handle!(headset);
+
+    if Level::new(brightness).is_none() || Level::new(speed).is_none() {
+        return CatcallerError::InvalidArgument;
+    }
+
+    headset.set_lighting(Lighting::from_numbers(0, [0, 0, 0], [brightness, speed]))
+}
+
+/// `profile` is 0-3.
+///
+/// # Safety
+/// `headset` must come from `catcaller_connect` or `catcaller_simulated` and not be disconnected yet.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_set_audio_profile(headset: *mut CatcallerHeadset, profile: u8) -> CatcallerError {
+    let headset = handle!(headset);
+
+    match AudioProfile::new(profile) {
+        Some(profile) => result(headset.runtime.block_on(headset.headset.set_audio_profile(profile))),
+        None => CatcallerError::InvalidArgument,
+    }
+}
+
+/// Applies a preset from the GUI's config file, matched case-insensitively.
+///
+/// # Safety
+/// `headset` must come from `catcaller_connect` or `catcaller_simulated` and not be disconnected yet,
+/// `name` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_apply_preset(headset: *mut CatcallerHeadset, name: *const c_char) -> CatcallerError {
+    let headset = handle!(headset);
+
+    if name.is_null() {
+        return CatcallerError::NullPointer;
+    }
+
+    let presets = presets::load();
+
+    match CStr::from_ptr(name).to_str().ok().and_then(|name| Preset::find(&presets, name)) {
+        Some(preset) => headset.set_lighting(preset.lighting()),
+        None => CatcallerError::NoPreset,
+    }
+}
+
+/// Reads the battery level into `out`, -1 if the headset doesn't report one.
+///
+/// # Safety
+/// `headset` must come from `catcaller_connect` or `catcaller_simulated` and not be disconnected yet,
+/// `out` must be valid for writing.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_battery(headset: *mut CatcallerHeadset, out: *mut c_int) -> CatcallerError {
+    let headset = handle!(headset);
+
+    if out.is_null() {
+        return CatcallerError::NullPointer;
+    }
+
+    match headset.runtime.block_on(headset.headset.battery()) {
+        Ok(battery) => {
+            *out = battery.map_or(-1, c_int::from);
+            CatcallerError::Ok
+        }
+
+        Err(e) => e.into(),
+    }
+}
+
+/// # Safety
+/// `headset` must come from `catcaller_connect` or `catcaller_simulated` and not be disconnected yet,
+/// `out` must be valid for writing.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_state(headset: *mut CatcallerHeadset, out: *mut CatcallerState) -> CatcallerError {
+    let headset = handle!(headset);
+
+    if out.is_null() {
+        return CatcallerError::NullPointer;
+    }
+
+    *out = CatcallerState::from(&headset.headset.state());
+    CatcallerError::Ok
+}
+
+/// Calls `callback` with every state change, from another thread. The state pointer is only valid
+/// during the call. Replaces the previous callback, NULL removes it.
+///
+/// # Safety
+/// `headset` must come from `catcaller_connect` or `catcaller_simulated` and not be disconnected yet,
+/// `callback` must be safe to call from another thread with `user_data` until it's replaced or
+/// the headset is disconnected.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_on_state_change(headset: *mut CatcallerHeadset, callback: CatcallerStateCallback, user_data: *mut c_void) -> CatcallerError {
+    let Some(headset) = headset.as_mut() else {
+        return CatcallerError::NullPointer;
+    };
+
+    if let Some(previous) = headset.callback.take() {
+        previous.abort();
+    }
+
+    if let Some(callback) = callback {
+        let mut states = headset.headset.states();
+        let user_data = UserData(user_data);
+
+        headset.callback = Some(headset.runtime.spawn(async move {
+            let user_data = user_data;
+
+            while let Some(state) = states.next().await {
+                callback(&CatcallerState::from(&state), user_data.0);
+            }
+        }));
+    }
+
+    CatcallerError::Ok
+}
+
+/// Disconnects and frees the handle. NULL is ignored.
+///
+/// # Safety
+/// `headset` must come from `catcaller_connect` or `catcaller_simulated` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn catcaller_disconnect(headset: *mut CatcallerHeadset) -> CatcallerError {
+    if headset.is_null() {
+        return CatcallerError::Ok;
+    }
+
+    let CatcallerHeadset { runtime, headset, callback } = *Box::from_raw(headset);
+
+    if let Some(callback) = callback {
+        callback.abort();
+    }
+
+    result(runtime.block_on(headset.disconnect()))
+}
+
+/// A static description of an error code.
+#[no_mangle]
+pub extern "C" fn catcaller_error_message(error: CatcallerError) -> *const c_char {
+    let message: &CStr = match error {
+        CatcallerError::Ok => c"ok",
+        CatcallerError::NullPointer => c"null pointer",
+        CatcallerError::InvalidArgument => c"argument out of range",
+        CatcallerError::NoAdapter => c"no bluetooth adapter",
+        CatcallerError::NotFound => c"no headset found",
+        CatcallerError::NotConnected => c"headset not connected",
+        CatcallerError::MissingCharacteristic => c"headset has no command characteristic",
+        CatcallerError::Bluetooth => c"bluetooth error",
+        CatcallerError::NoPreset => c"no preset with that name",
+    };
+
+    message.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use catcaller::{protocol, BoxFuture, Transport};
+
+    use super::*;
+
+    // a simulated headset that notes when each lighting frame reached it
+    #[derive(Clone, Default)]
+    struct Timed {
+        simulator: sim::Simulator,
+        lighting: Arc<Mutex<Vec<(Instant, Lighting)>>>,
+    }
+
+    impl Transport for Timed {
+        fn write<'a>(&'a self, frame: &'a [u8]) -> BoxFuture<'a, Result<(), catcaller::Error>> {
+            if let Some(lighting) = protocol::parse_lighting(frame) {
+                self.lighting.lock().unwrap().push((Instant::now(), lighting));
+            }
+
+            self.simulator.write(frame)
+        }
+
+        fn battery(&self) -> BoxFuture<'_, Result<Option<u8>, catcaller::Error>> {
+            self.simulator.battery()
+        }
+
+        fn is_connected(&self) -> BoxFuture<'_, Result<bool, catcaller::Error>> {
+            self.simulator.is_connected()
+        }
+
+        fn disconnect(&self) -> BoxFuture<'_, Result<(), catcaller::Error>> {
+            self.simulator.disconnect()
+        }
+    }
+
+    fn simulated() -> (*mut CatcallerHeadset, Timed) {
+        let timed = Timed::default();
+        let headset = Headset::with_transport(Model::YowuSelkirk4, Arc::new(timed.clone()));
+
+        (CatcallerHeadset::new(runtime().unwrap(), headset), timed)
+    }
+
+    // the most times the mode or color changed within any second
+    fn max_changes_per_second(frames: &[(Instant, Lighting)]) -> usize {
+        let mut shown = (Some(Mode::LightsOn), None);
+        let mut changes = Vec::new();
+
+        for &(t, lighting) in frames {
+            let next = (lighting.mode.or(shown.0), lighting.color.or(shown.1));
+
+            if std::mem::replace(&mut shown, next) != next {
+                changes.push(t);
+            }
+        }
+
+        changes.iter()
+            .map(|&t| changes.iter().filter(|&&u| u >= t && u < t + Duration::from_secs(1)).count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    // what a python loop doing its worst looks like from here
+    fn strobe(headset: *mut CatcallerHeadset, mut send: impl FnMut(*mut CatcallerHeadset, u32) -> CatcallerError) {
+        for i in 0 .. 40 {
+            assert_eq!(send(headset, i), CatcallerError::Ok);
+            std::thread::sleep(Duration::from_millis(25));
+        }
+
+        std::thread::sleep(Duration::from_millis(1200));
+    }
+
+    #[test]
+    fn mode_strobe_is_limited() {
+        let (headset, timed) = simulated();
+        strobe(headset, |headset, i| unsafe { catcaller_set_mode(headset, if i.is_multiple_of(2) { 6 } else { 7 }) });
+
+        let frames = timed.lighting.lock().unwrap().clone();
+        assert!(max_changes_per_second(&frames) <= 6);
+        assert_eq!(timed.simulator.lighting().unwrap().mode, Some(Mode::LightsOn));
+
+        let mut state = CatcallerState::from(&State::default());
+        assert_eq!(unsafe { catcaller_state(headset, &mut state) }, CatcallerError::Ok);
+        assert_eq!(state.mode, 7);
+
+        assert_eq!(unsafe { catcaller_disconnect(headset) }, CatcallerError::Ok);
+    }
+
+    #[test]
+    fn color_strobe_is_limited() {
+        let (headset, timed) = simulated();
+        strobe(headset, |headset, i| unsafe {
+            match i % 2 {
+                0 => catcaller_set_color(headset, 255, 0, 0),
+                _ => catcaller_set_color(headset, 0, 0, 255),
+            }
+        });
+
+        let frames = timed.lighting.lock().unwrap().clone();
+        assert!(max_changes_per_second(&frames) <= 6);
+        assert_eq!(timed.simulator.lighting().unwrap().color, Some(Rgb::new(0, 0, 255)));
+
+        assert_eq!(unsafe { catcaller_disconnect(headset) }, CatcallerError::Ok);
+    }
+
+    #[test]
+    fn flash_speed_is_capped() {
+        let (headset, timed) = simulated();
+
+        assert_eq!(unsafe { catcaller_set_mode(headset, 2) }, CatcallerError::Ok);
+        assert_eq!(unsafe { catcaller_set_settings(headset, 63, 63) }, CatcallerError::Ok);
+        assert_eq!(timed.simulator.lighting().unwrap().speed, Some(Level::saturating(1)));
+
+        assert_eq!(unsafe { catcaller_disconnect(headset) }, CatcallerError::Ok);
+    }
+
+    fn state(headset: *mut CatcallerHeadset) -> CatcallerState {
+        let mut state = CatcallerState::from(&State::default());
+        assert_eq!(unsafe { catcaller_state(headset, &mut state) }, CatcallerError::Ok);
+        state
+    }
+
+    #[test]
+    fn simulated_headset() {
+        let headset = catcaller_simulated();
+        assert!(!headset.is_null());
+
+        let initial = state(headset);
+        assert!(initial.connected);
+        assert_eq!((initial.mode, initial.has_color, initial.brightness, initial.audio_profile), (0, false, -1, -1));
+
+        unsafe {
+            assert_eq!(catcaller_set_mode(headset, 3), CatcallerError::Ok);
+            assert_eq!(catcaller_set_color(headset, 0, 128, 255), CatcallerError::Ok);
+            assert_eq!(catcaller_set_settings(headset, 40, 0), CatcallerError::Ok);
+            assert_eq!(catcaller_set_audio_profile(headset, 2), CatcallerError::Ok);
+        }
+
+        let state = state(headset);
+        assert_eq!((state.mode, state.has_color, state.r, state.g, state.b), (3, true, 0, 128, 255));
+        assert_eq!((state.brightness, state.speed, state.audio_profile), (40, -1, 2), "a 0 setting stays unknown");
+
+        let mut battery = 0;
+        assert_eq!(unsafe { catcaller_battery(headset, &mut battery) }, CatcallerError::Ok);
+        assert_eq!(battery, -1);
+
+        assert_eq!(unsafe { catcaller_disconnect(headset) }, CatcallerError::Ok);
+    }
+
+    #[test]
+    fn bad_arguments() {
+        let headset = catcaller_simulated();
+
+        unsafe {
+            assert_eq!(catcaller_set_mode(headset, 0), CatcallerError::InvalidArgument);
+            assert_eq!(catcaller_set_mode(headset, 9), CatcallerError::InvalidArgument);
+            assert_eq!(catcaller_set_settings(headset, 64, 0), CatcallerError::InvalidArgument);
+            assert_eq!(catcaller_set_audio_profile(headset, 4), CatcallerError::InvalidArgument);
+
+            assert_eq!(catcaller_set_color(std::ptr::null_mut(), 0, 0, 0), CatcallerError::NullPointer);
+            assert_eq!(catcaller_battery(headset, std::ptr::null_mut()), CatcallerError::NullPointer);
+            assert_eq!(catcaller_state(headset, std::ptr::null_mut()), CatcallerError::NullPointer);
+            assert_eq!(catcaller_apply_preset(headset, std::ptr::null()), CatcallerError::NullPointer);
+            assert_eq!(catcaller_on_state_change(std::ptr::null_mut(), None, std::ptr::null_mut()), CatcallerError::NullPointer);
+            assert_eq!(catcaller_connect(0, std::ptr::null_mut()), CatcallerError::NullPointer);
+        }
+
+        assert_eq!(state(headset).mode, 0, "nothing was sent");
+        assert_eq!(unsafe { CStr::from_ptr(catcaller_error_message(CatcallerError::InvalidArgument)) }, c"argument out of range");
+
+        assert_eq!(unsafe { catcaller_disconnect(headset) }, CatcallerError::Ok);
+        assert_eq!(unsafe { catcaller_disconnect(std::ptr::null_mut()) }, CatcallerError::Ok);
+    }
+
+    unsafe extern "C" fn count(state: *const CatcallerState, user_data: *mut c_void) {
+        let (calls, mode) = &*(user_data as *const (AtomicUsize, AtomicI32));
+        mode.store((*state).mode, Ordering::SeqCst);
+        calls.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn state_callback() {
+        let headset = catcaller_simulated();
+        let seen = (AtomicUsize::new(0), AtomicI32::new(0));
+        let user_data = &seen as *const _ as *mut c_void;
+
+        assert_eq!(unsafe { catcaller_on_state_change(headset, Some(count), user_data) }, CatcallerError::Ok);
+        assert_eq!(unsafe { catcaller_set_mode(headset, 5) }, CatcallerError::Ok);
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while seen.0.load(Ordering::SeqCst) == 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(seen.1.load(Ordering::SeqCst), 5);
+
+        // removed, so no more calls
+        assert_eq!(unsafe { catcaller_on_state_change(headset, None, std::ptr::null_mut()) }, CatcallerError::Ok);
+        let calls = seen.0.load(Ordering::SeqCst);
+        assert_eq!(unsafe { catcaller_set_mode(headset, 1) }, CatcallerError::Ok);
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(seen.0.load(Ordering::SeqCst), calls);
+
+        assert_eq!(unsafe { catcaller_disconnect(headset) }, CatcallerError::Ok);
+    }
+
+    #[test]
+    fn presets_from_the_config_file() {
+        let home = std::env::temp_dir().join(format!("catcaller-ffi-test-{}", std::process::id()));
+        std::fs::create_dir_all(home.join("catcaller")).unwrap();
+        std::fs::write(home.join("catcaller").join("config.toml"), "dbus = false\n\n[[presets]]\nname = \"On Air\"\nmode = 7\nrgb = [255, 0, 0]\nsettings = [63, 0]\n").unwrap();
+        std::env::set_var("XDG_CONFIG_HOME", &home); //only this test reads the config
+
+        let headset = catcaller_simulated();
+
+        assert_eq!(unsafe { catcaller_apply_preset(headset, c"on air".as_ptr()) }, CatcallerError::Ok);
+        assert_eq!(unsafe { catcaller_apply_preset(headset, c"off air".as_ptr()) }, CatcallerError::NoPreset);
+
+        let state = state(headset);
+        assert_eq!((state.mode, state.r, state.g, state.b, state.brightness, state.speed), (7, 255, 0, 0, 63, -1));
+
+        assert_eq!(unsafe { catcaller_disconnect(headset) }, CatcallerError::Ok);
+        let _ = std::fs::remove_dir_all(home);
+    }
+}
@@ -0,0 +1,22 @@
+// sets the color of the first headset that shows up:
+//   cargo run -p catcaller --example color -- ff0000
+
+use std::time::Duration;
+
+use catcaller::{Lighting, Rgb, Scanner};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let hex = std::env::args().nth(1).unwrap_or_else(|| String::from("ff0000"));
+    let [_, r, g, b] = u32::from_str_radix(hex.trim_start_matches('#'), 16)?.to_be_bytes();
+
+    let scanner = Scanner::new().await?;
+    let discovered = scanner.find(Duration::from_secs(15)).await?;
+    println!("found a {}", discovered.model().name());
+
+    let headset = discovered.connect().await?;
+    headset.set_lighting(Lighting::new().color(Rgb::new(r, g, b))).await?;
+    headset.disconnect().await?;
+
+    Ok(())
+}
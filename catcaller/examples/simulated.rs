@@ -0,0 +1,31 @@
+// drives a simulated headset and prints the frames it receives:
+//   cargo run -p catcaller --example simulated
+
+use catcaller::{sim, AudioProfile, Level, Lighting, Mode, Model, Rgb};
+
+#[tokio::main]
+async fn main() -> Result<(), catcaller::Error> {
+    let (headset, simulator) = sim::simulated(Model::YowuSelkirk4);
+    let mut states = headset.states();
+
+    tokio::spawn(async move {
+        while let Some(state) = states.next().await {
+            println!("state: {state:?}");
+        }
+    });
+
+    headset.set_lighting(Lighting::new().mode(Mode::Breath).color(Rgb::new(255, 0, 128)).brightness(Level::MAX)).await?;
+    headset.set_audio_profile(AudioProfile::new(2).unwrap()).await?;
+
+    simulator.set_battery(Some(80));
+    println!("battery: {:?}", headset.battery().await?);
+
+    for frame in simulator.frames() {
+        println!("frame: {}", frame.iter().map(|byte| format!("{byte:02X}")).collect::<Vec<_>>().join(" "));
+    }
+
+    simulator.drop_connection();
+    println!("after the connection dropped: {:?}", headset.set_lighting(Lighting::new().mode(Mode::LightsOff)).await);
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use btleplug::api::bleuuid::uuid_from_u16;
+use btleplug::api::{Central, Characteristic, Manager as _, Peripheral as _, ScanFilter, WriteType};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use tokio::time::{sleep, Instant};
+
+use crate::headset::BoxFuture;
+use crate::{Error, Headset, Model, Transport};
+
+const SCAN_TIME: Duration = Duration::from_millis(850);
+
+// not a From impl, that would make btleplug's error type part of the public api
+fn bluetooth(e: btleplug::Error) -> Error {
+    Error::Bluetooth(Box::new(e))
+}
+
+/// Finds headsets on every bluetooth adapter.
+pub struct Scanner {
+    adapters: Vec<Adapter>,
+}
+
+impl Scanner {
+    /// [`Error::NoAdapter`] if there's no bluetooth adapter (yet).
+    pub async fn new() -> Result<Self, Error> {
+        let adapters = Manager::new().await.map_err(bluetooth)?.adapters().await.map_err(bluetooth)?;
+
+        match adapters.is_empty() {
+            true => Err(Error::NoAdapter),
+            false => Ok(Self { adapters }),
+        }
+    }
+
+    /// Scans for a moment and returns the headsets that showed up.
+    pub async fn scan(&self) -> Result<Vec<Discovered>, Error> {
+        for adapter in &self.adapters {
+            adapter.start_scan(ScanFilter::default()).await.map_err(bluetooth)?;
+        }
+
+        sleep(SCAN_TIME).await;
+
+        let mut found = Vec::new();
+
+        for adapter in &self.adapters {
+            for peripheral in adapter.peripherals().await.map_err(bluetooth)? {
+                let name = peripheral.properties().await.map_err(bluetooth)?.and_then(|properties| properties.local_name);
+
+                if let Some(model) = name.as_deref().and_then(Model::from_local_name) {
+                    found.push(Discovered { peripheral, model });
+                }
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Keeps scanning until a headset shows up, [`Error::NotFound`] after `timeout`.
+    pub async fn find(&self, timeout: Duration) -> Result<Discovered, Error> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(discovered) = self.scan().await?.into_iter().next() {
+                return Ok(discovered);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::NotFound);
+            }
+        }
+    }
+}
+
+/// A headset found by a [`Scanner`], not connected yet.
+pub struct Discovered {
+    peripheral: Peripheral,
+    model: Model,
+}
+
+impl Discovered {
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Connects and looks up the characteristics. Can be retried after an error.
+    pub async fn connect(&self) -> Result<Headset, Error> {
+        if !self.peripheral.is_connected().await.map_err(bluetooth)? {
+            self.peripheral.connect().await.map_err(bluetooth)?;
+        }
+
+        self.peripheral.discover_services().await.map_err(bluetooth)?;
+
+        let characteristics = self.peripheral.characteristics();
+        let command = characteristics.iter().find(|c| c.uuid == uuid_from_u16(0x2A06)).ok_or(Error::MissingCharacteristic)?;
+        let battery = characteristics.iter().find(|c| c.uuid == uuid_from_u16(0x2A19)); //standard battery level, if it has one
+
+        let transport = Ble { peripheral: self.peripheral.clone(), command: command.clone(), battery: battery.cloned() };
+
+        Ok(Headset::with_transport(self.model, Arc::new(transport)))
+    }
+}
+
+struct Ble {
+    peripheral: Peripheral,
+    command: Characteristic,
+    battery: Option<Characteristic>,
+}
+
+impl Transport for Ble {
+    fn write<'a>(&'a self, frame: &'a [u8]) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            self.peripheral.write(&self.command, frame, WriteType::WithoutResponse).await.map_err(bluetooth)?;
+            Ok(())
+        })
+    }
+
+    fn battery(&self) -> BoxFuture<'_, Result<Option<u8>, Error>> {
+        Box::pin(async move {
+            match &self.battery {
+                Some(battery) => Ok(self.peripheral.read(battery).await.map_err(bluetooth)?.first().copied()),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn is_connected(&self) -> BoxFuture<'_, Result<bool, Error>> {
+        Box::pin(async move { self.peripheral.is_connected().await.map_err(bluetooth) })
+    }
+
+    fn disconnect(&self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move { self.peripheral.disconnect().await.map_err(bluetooth) })
+    }
+}
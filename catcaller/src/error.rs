@@ -0,0 +1,38 @@
+use std::fmt;
+
+/// Everything that can go wrong talking to a headset.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// No bluetooth adapter was found.
+    NoAdapter,
+    /// Scanning ran out of time without finding a known headset.
+    NotFound,
+    /// The headset isn't connected (anymore).
+    NotConnected,
+    /// The headset doesn't have the characteristic commands are written to.
+    MissingCharacteristic,
+    /// An error from the bluetooth stack or a [`Transport`](crate::Transport).
+    Bluetooth(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoAdapter => write!(f, "no bluetooth adapter"),
+            Error::NotFound => write!(f, "no headset found"),
+            Error::NotConnected => write!(f, "headset not connected"),
+            Error::MissingCharacteristic => write!(f, "headset has no command characteristic"),
+            Error::Bluetooth(e) => write!(f, "bluetooth: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Bluetooth(e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
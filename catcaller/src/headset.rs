@@ -0,0 +1,188 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::sync::{watch, Mutex};
+use tokio::time::Instant;
+
+use crate::safety::{self, Safety};
+use crate::{protocol, AudioProfile, Error, FlashLimits, Lighting, Model};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// How a [`Headset`] reaches the hardware. Bluetooth LE for headsets from a [`Scanner`](crate::Scanner),
+/// [`sim::simulated`](crate::sim::simulated) for one that only records what it's sent.
+pub trait Transport: Send + Sync {
+    /// Writes one frame to the command characteristic.
+    fn write<'a>(&'a self, frame: &'a [u8]) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// Battery level in percent, `None` if the headset doesn't report it.
+    fn battery(&self) -> BoxFuture<'_, Result<Option<u8>, Error>>;
+
+    fn is_connected(&self) -> BoxFuture<'_, Result<bool, Error>>;
+
+    fn disconnect(&self) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+/// What the headset was last told and reported. Only knows about commands sent through this
+/// [`Headset`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct State {
+    pub connected: bool,
+    pub battery: Option<u8>,
+    pub lighting: Option<Lighting>,
+    pub audio_profile: Option<AudioProfile>,
+}
+
+/// A connected headset.
+pub struct Headset {
+    model: Model,
+    inner: Arc<Inner>,
+}
+
+// shared with the task that sends lighting held back by the flash limits
+struct Inner {
+    transport: Arc<dyn Transport>,
+    state: watch::Sender<State>,
+    safety: Mutex<Safety>, //held while writing lighting, so frames go out in order
+}
+
+impl Headset {
+    pub fn with_transport(model: Model, transport: Arc<dyn Transport>) -> Self {
+        let (state, _) = watch::channel(State { connected: true, ..Default::default() });
+        let safety = Mutex::new(Safety::new(FlashLimits::default()));
+
+        Self { model, inner: Arc::new(Inner { transport, state, safety }) }
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Sends `lighting`, within the [`FlashLimits`]. Lighting that would make the lights flash
+    /// too often is held back, merged with what comes after it and sent from a background task
+    /// once it's safe, so this also returns `Ok` for lighting that hasn't been sent yet.
+    /// [`State::lighting`] only changes once it has been. Needs a tokio runtime.
+    pub async fn set_lighting(&self, lighting: Lighting) -> Result<(), Error> {
+        let mut safety = self.inner.safety.lock().await;
+
+        match safety.filter(lighting, Instant::now()) {
+            Some(lighting) => self.inner.write_lighting(lighting).await,
+            None => {
+                if !std::mem::replace(&mut safety.flushing, true) {
+                    tokio::spawn(flush(self.inner.clone()));
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn flash_limits(&self) -> FlashLimits {
+        self.inner.safety.lock().await.limits
+    }
+
+    /// Applies from the next [`set_lighting`](Self::set_lighting) on.
+    pub async fn set_flash_limits(&self, limits: FlashLimits) {
+        self.inner.safety.lock().await.limits = limits;
+    }
+
+    pub async fn set_audio_profile(&self, profile: AudioProfile) -> Result<(), Error> {
+        self.inner.write(&protocol::audio_profile_frame(profile)).await?;
+        self.inner.state.send_modify(|state| state.audio_profile = Some(profile));
+
+        Ok(())
+    }
+
+    /// Reads the battery level, which also updates [`State::battery`].
+    pub async fn battery(&self) -> Result<Option<u8>, Error> {
+        let battery = self.inner.transport.battery().await?;
+        self.inner.state.send_if_modified(|state| std::mem::replace(&mut state.battery, battery) != battery);
+
+        Ok(battery)
+    }
+
+    pub async fn is_connected(&self) -> Result<bool, Error> {
+        self.inner.is_connected().await
+    }
+
+    pub fn state(&self) -> State {
+        self.inner.state.borrow().clone()
+    }
+
+    /// Every change to the [`State`] from here on.
+    pub fn states(&self) -> States {
+        States(self.inner.state.subscribe())
+    }
+
+    pub async fn disconnect(self) -> Result<(), Error> {
+        self.inner.transport.disconnect().await?;
+        self.inner.state.send_modify(|state| state.connected = false);
+
+        Ok(())
+    }
+}
+
+impl Inner {
+    async fn write_lighting(&self, lighting: Lighting) -> Result<(), Error> {
+        self.write(&protocol::lighting_frame(&lighting)).await?;
+        //unset fields are left as they were on the headset too
+        self.state.send_modify(|state| state.lighting = Some(safety::merge(state.lighting.unwrap_or_default(), lighting)));
+
+        Ok(())
+    }
+
+    async fn is_connected(&self) -> Result<bool, Error> {
+        let connected = self.transport.is_connected().await?;
+        self.state.send_if_modified(|state| std::mem::replace(&mut state.connected, connected) != connected);
+
+        Ok(connected)
+    }
+
+    async fn write(&self, frame: &[u8]) -> Result<(), Error> {
+        if !self.is_connected().await? {
+            return Err(Error::NotConnected);
+        }
+
+        self.transport.write(frame).await
+    }
+}
+
+// sends what the flash limits held back, once they allow it
+async fn flush(inner: Arc<Inner>) {
+    loop {
+        let due = {
+            let mut safety = inner.safety.lock().await;
+
+            match safety.next_due() {
+                Some(due) => due,
+                None => {
+                    safety.flushing = false;
+                    return;
+                }
+            }
+        };
+
+        tokio::time::sleep_until(due).await;
+
+        let mut safety = inner.safety.lock().await;
+
+        if let Some(lighting) = safety.due(Instant::now()) {
+            // nobody left to tell if this fails, State::lighting just stays as it was
+            let _ = inner.write_lighting(lighting).await;
+        }
+    }
+}
+
+/// From [`Headset::states`].
+pub struct States(watch::Receiver<State>);
+
+impl States {
+    /// Waits for the next change, `None` once the [`Headset`] is gone. Changes that happen
+    /// while nobody is waiting are merged into one.
+    pub async fn next(&mut self) -> Option<State> {
+        self.0.changed().await.ok()?;
+        Some(self.0.borrow_and_update().clone())
+    }
+}
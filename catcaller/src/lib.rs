@@ -0,0 +1,39 @@
+//! Control Yowu headsets over bluetooth LE: the lights (mode, color, brightness and speed) and the
+//! audio profile.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use catcaller::{Lighting, Mode, Rgb, Scanner};
+//!
+//! # async fn run() -> Result<(), catcaller::Error> {
+//! let scanner = Scanner::new().await?;
+//! let headset = scanner.find(Duration::from_secs(10)).await?.connect().await?;
+//!
+//! headset.set_lighting(Lighting::new().mode(Mode::Breath).color(Rgb::new(255, 0, 0))).await?;
+//! headset.disconnect().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Lighting goes through photosensitivity [`FlashLimits`], on unless turned off with
+//! [`Headset::set_flash_limits`].
+//!
+//! [`sim::simulated`] gives a [`Headset`] without any hardware, for tests and examples.
+//! The `serde` feature derives `Serialize` and `Deserialize` for the data types, `presets` adds
+//! [`presets`] for reading the presets in catcaller's config file.
+
+mod ble;
+mod error;
+mod headset;
+#[cfg(feature = "presets")]
+pub mod presets;
+pub mod protocol;
+mod safety;
+pub mod sim;
+mod types;
+
+pub use ble::{Discovered, Scanner};
+pub use error::Error;
+pub use headset::{BoxFuture, Headset, State, States, Transport};
+pub use safety::FlashLimits;
+pub use types::{AudioProfile, Level, Lighting, Mode, Model, Rgb};
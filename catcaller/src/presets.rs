@@ -0,0 +1,58 @@
+//! `[[presets]]` from catcaller's config file, the same ones the app and its integrations use.
+//!
+//! ```toml
+//! [[presets]]
+//! name = "on air"
+//! mode = 7          # 1-8, 0 or left out keeps the current mode
+//! rgb = [255, 0, 0]
+//! settings = [63, 0] # brightness and speed
+//! ```
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::Lighting;
+
+/// A named lighting command. Like in a lighting frame, 0 leaves something as it is.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    #[serde(default)]
+    pub mode: u8,
+    #[serde(default)]
+    pub rgb: [u8; 3],
+    #[serde(default)]
+    pub settings: [u8; 2],
+}
+
+impl Preset {
+    pub fn lighting(&self) -> Lighting {
+        Lighting::from_numbers(self.mode, self.rgb, self.settings)
+    }
+
+    /// Matches names case-insensitively.
+    pub fn find<'a>(presets: &'a [Preset], name: &str) -> Option<&'a Preset> {
+        presets.iter().find(|preset| preset.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// `~/.config/catcaller` on Linux.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("catcaller"))
+}
+
+/// The presets in `config.toml`, empty if there's no config file or it can't be read.
+pub fn load() -> Vec<Preset> {
+    #[derive(Deserialize)]
+    struct Config {
+        #[serde(default)]
+        presets: Vec<Preset>,
+    }
+
+    let Some(text) = config_dir().and_then(|dir| std::fs::read_to_string(dir.join("config.toml")).ok()) else {
+        return Vec::new();
+    };
+
+    toml::from_str::<Config>(&text).map(|config| config.presets).unwrap_or_default()
+}
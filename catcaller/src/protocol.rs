@@ -0,0 +1,60 @@
+//! The frames written to the headset's command characteristic (0x2A06). Every frame ends with a
+//! checksum that makes the sum of all its bytes 0.
+
+use crate::{AudioProfile, Level, Lighting, Rgb};
+
+const LIGHTING_HEADER: [u8; 4] = [0xFC, 0x04, 0x01, 0x06];
+const AUDIO_PROFILE_HEADER: [u8; 5] = [0xFC, 0x05, 0x02, 0x02, 0x92];
+
+/// `FC 04 01 06 mode r g b brightness speed checksum`, 0 for everything that's left unchanged.
+pub fn lighting_frame(lighting: &Lighting) -> [u8; 11] {
+    let Rgb { r, g, b } = lighting.color.unwrap_or_default();
+    let level = |level: Option<Level>| level.map_or(0, Level::get);
+
+    let [h0, h1, h2, h3] = LIGHTING_HEADER;
+    let mode = lighting.mode.map_or(0, |mode| mode.number());
+
+    with_checksum([h0, h1, h2, h3, mode, r, g, b, level(lighting.brightness), level(lighting.speed), 0x00])
+}
+
+/// `FC 05 02 02 92 profile checksum`
+pub fn audio_profile_frame(profile: AudioProfile) -> [u8; 7] {
+    let [h0, h1, h2, h3, h4] = AUDIO_PROFILE_HEADER;
+
+    with_checksum([h0, h1, h2, h3, h4, profile.get(), 0x00])
+}
+
+/// The other way around, `None` if it isn't a valid lighting frame.
+pub fn parse_lighting(frame: &[u8]) -> Option<Lighting> {
+    let &[h0, h1, h2, h3, mode, r, g, b, brightness, speed, _] = frame else {
+        return None;
+    };
+
+    if [h0, h1, h2, h3] != LIGHTING_HEADER || !checksum_ok(frame) {
+        return None;
+    }
+
+    Some(Lighting::from_numbers(mode, [r, g, b], [brightness, speed]))
+}
+
+/// `None` if it isn't a valid audio profile frame.
+pub fn parse_audio_profile(frame: &[u8]) -> Option<AudioProfile> {
+    let &[h0, h1, h2, h3, h4, profile, _] = frame else {
+        return None;
+    };
+
+    if [h0, h1, h2, h3, h4] != AUDIO_PROFILE_HEADER || !checksum_ok(frame) {
+        return None;
+    }
+
+    AudioProfile::new(profile)
+}
+
+fn with_checksum<const N: usize>(mut frame: [u8; N]) -> [u8; N] {
+    frame[N - 1] = frame.iter().fold(0, |checksum: u8, x| checksum.wrapping_sub(*x));
+    frame
+}
+
+fn checksum_ok(frame: &[u8]) -> bool {
+    frame.iter().fold(0, |sum: u8, x| sum.wrapping_add(*x)) == 0
+}
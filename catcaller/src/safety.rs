@@ -0,0 +1,305 @@
+// photosensitivity limits on everything a Headset is told to show. follows WCAG 2.3.1: no more
+// than 3 flashes in any second, a flash being a pair of opposing changes in relative luminance of
+// 10% or more where the darker side is below 0.8, or a change to or from saturated red. commands
+// that would go over that are held back, merged with whatever comes after them, and sent once
+// it's safe, so the headset still ends up where it was told to go. the headset's own flash mode
+// gets its speed capped on top of that.
+
+use std::collections::VecDeque;
+
+use tokio::time::{Duration, Instant};
+
+use crate::{Level, Lighting, Mode};
+
+const WINDOW: Duration = Duration::from_secs(1);
+const MAX_TRANSITIONS: usize = 6; //per WINDOW, two per flash
+
+/// Photosensitivity limits for the lights, on by default for every [`Headset`](crate::Headset).
+///
+/// Following WCAG's three flashes guideline, the lights don't flash more than 3 times a second.
+/// Big brightness changes and changes to or from saturated red count as flashes. Commands that
+/// would go over the limit are held back, merged with the ones after them and sent when it's safe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FlashLimits {
+    /// `false` turns every limit off, see [`FlashLimits::unsafe_off`].
+    pub enabled: bool,
+    /// What the speed of [`Mode::Flash`] is capped to. How fast the headset flashes at each step
+    /// hasn't been measured yet, so by default it's held at the slowest speed, 1. Raise it only
+    /// after checking that speed stays under 3 flashes a second on the headset itself.
+    pub max_flash_speed: Level,
+}
+
+impl Default for FlashLimits {
+    fn default() -> Self {
+        Self { enabled: true, max_flash_speed: Level::saturating(1) }
+    }
+}
+
+impl FlashLimits {
+    /// No limits at all. Only for places where nobody who can see the headset is sensitive to
+    /// flashing lights.
+    pub fn unsafe_off() -> Self {
+        Self { enabled: false, ..Self::default() }
+    }
+
+    pub fn max_flash_speed(self, max_flash_speed: Level) -> Self {
+        Self { max_flash_speed, ..self }
+    }
+}
+
+// relative luminance of the lights, assuming the worst (full white) for what isn't known
+fn luminance(shown: &Lighting) -> f32 {
+    if shown.mode == Some(Mode::LightsOff) {
+        return 0.0;
+    }
+
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+
+    let color = match shown.color {
+        None => 1.0,
+        Some(rgb) => 0.2126 * linear(rgb.r) + 0.7152 * linear(rgb.g) + 0.0722 * linear(rgb.b),
+    };
+
+    let brightness = match shown.brightness {
+        None => 1.0,
+        Some(level) => level.get() as f32 / 63.0,
+    };
+
+    color * brightness
+}
+
+// WCAG's saturated red: red is at least 80% of r + g + b
+fn saturated_red(shown: &Lighting) -> bool {
+    let Some(rgb) = shown.color else {
+        return false;
+    };
+
+    let (r, g, b) = (rgb.r as f32, rgb.g as f32, rgb.b as f32);
+    shown.mode != Some(Mode::LightsOff) && r > 0.0 && r / (r + g + b) >= 0.8
+}
+
+fn is_transition(from: &Lighting, to: &Lighting) -> bool {
+    let (a, b) = (luminance(from), luminance(to));
+    let (darker, lighter) = (a.min(b), a.max(b));
+
+    (lighter - darker >= 0.1 && darker < 0.8) || saturated_red(from) != saturated_red(to)
+}
+
+// newer fields win, unset ones are left as they were
+pub(crate) fn merge(older: Lighting, newer: Lighting) -> Lighting {
+    Lighting {
+        mode: newer.mode.or(older.mode),
+        color: newer.color.or(older.color),
+        brightness: newer.brightness.or(older.brightness),
+        speed: newer.speed.or(older.speed),
+    }
+}
+
+pub(crate) struct Safety {
+    pub limits: FlashLimits,
+    shown: Lighting, //everything sent so far, merged
+    transitions: VecDeque<Instant>, //within the last WINDOW
+    held: Option<Lighting>,
+    pub flushing: bool, //a task is waiting to send what's held
+}
+
+impl Safety {
+    pub fn new(limits: FlashLimits) -> Self {
+        Self { limits, shown: Lighting::default(), transitions: VecDeque::new(), held: None, flushing: false }
+    }
+
+    // what to send to the headset now, None if there's nothing to send yet
+    pub fn filter(&mut self, lighting: Lighting, now: Instant) -> Option<Lighting> {
+        let mut lighting = match self.held.take() {
+            Some(held) => merge(held, lighting),
+            None => lighting,
+        };
+
+        if !self.limits.enabled {
+            self.shown = merge(self.shown, lighting);
+            return Some(lighting);
+        }
+
+        // flash mode, at a speed it was asked for or one it still has from before
+        let cap = self.limits.max_flash_speed.max(Level::saturating(1));
+        let next = merge(self.shown, lighting);
+
+        if next.mode == Some(Mode::Flash) && next.speed.is_none_or(|speed| speed > cap) {
+            lighting.speed = Some(cap);
+        }
+
+        let next = merge(self.shown, lighting);
+
+        if is_transition(&self.shown, &next) {
+            while self.transitions.front().is_some_and(|&t| now.duration_since(t) >= WINDOW) {
+                self.transitions.pop_front();
+            }
+
+            if self.transitions.len() >= MAX_TRANSITIONS {
+                self.held = Some(lighting);
+                return None;
+            }
+
+            self.transitions.push_back(now);
+        }
+
+        self.shown = next;
+        Some(lighting)
+    }
+
+    // when what's held back can go out
+    pub fn next_due(&self) -> Option<Instant> {
+        self.held?;
+        Some(self.transitions.front().map_or_else(Instant::now, |&t| t + WINDOW))
+    }
+
+    pub fn due(&mut self, now: Instant) -> Option<Lighting> {
+        let held = self.held.take()?;
+        self.filter(held, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rgb;
+
+    const ON: Lighting = Lighting { mode: Some(Mode::LightsOn), color: Some(Rgb::new(255, 255, 255)), brightness: Some(Level::MAX), speed: None };
+    const OFF: Lighting = Lighting { mode: Some(Mode::LightsOff), color: None, brightness: None, speed: None };
+
+    fn color(r: u8, g: u8, b: u8) -> Lighting {
+        Lighting::new().color(Rgb::new(r, g, b))
+    }
+
+    // feeds (ms, lighting) on a made up clock, returns (ms, lighting) of what went out, held
+    // back commands included
+    fn run(limits: FlashLimits, input: &[(u64, Lighting)], until: u64) -> Vec<(u64, Lighting)> {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut safety = Safety::new(limits);
+        let mut out = Vec::new();
+
+        let flush = |safety: &mut Safety, out: &mut Vec<(u64, Lighting)>, until: u64| {
+            while let Some(due) = safety.next_due().filter(|&due| due <= at(until)) {
+                if let Some(lighting) = safety.due(due) {
+                    out.push((due.duration_since(start).as_millis() as u64, lighting));
+                }
+            }
+        };
+
+        for &(ms, lighting) in input {
+            flush(&mut safety, &mut out, ms);
+
+            if let Some(lighting) = safety.filter(lighting, at(ms)) {
+                out.push((ms, lighting));
+            }
+        }
+
+        flush(&mut safety, &mut out, until);
+        out
+    }
+
+    fn strobe(period: u64, count: u64) -> Vec<(u64, Lighting)> {
+        (0 .. count).map(|i| (i * period, if i.is_multiple_of(2) { ON } else { OFF })).collect()
+    }
+
+    // the most transitions within any second of what went out
+    fn max_transitions_per_second(out: &[(u64, Lighting)]) -> usize {
+        let mut shown = Lighting::default();
+        let mut times = Vec::new();
+
+        for (ms, lighting) in out {
+            let next = merge(shown, *lighting);
+
+            if is_transition(&shown, &next) {
+                times.push(*ms);
+            }
+
+            shown = next;
+        }
+
+        times.iter().map(|&t| times.iter().filter(|&&u| u >= t && u < t + 1000).count()).max().unwrap_or(0)
+    }
+
+    fn last_state(out: &[(u64, Lighting)]) -> Lighting {
+        out.iter().fold(Lighting::default(), |shown, (_, lighting)| merge(shown, *lighting))
+    }
+
+    #[test]
+    fn strobe_is_limited_and_lands() {
+        let input = strobe(50, 20);
+        let out = run(FlashLimits::default(), &input, 3000);
+
+        assert_eq!(max_transitions_per_second(&out), MAX_TRANSITIONS);
+        assert_eq!(last_state(&out).mode, Some(Mode::LightsOff));
+        assert_eq!(out.last().unwrap().0, 1050, "the last command waits for the window to move on");
+    }
+
+    #[test]
+    fn slow_changes_pass_untouched() {
+        let input = strobe(200, 10);
+        assert_eq!(run(FlashLimits::default(), &input, 3000), input);
+    }
+
+    #[test]
+    fn saturated_red_counts_as_a_flash() {
+        // about the same luminance, so only the red rule catches it
+        let mut input = vec![(0, ON)];
+        input.extend((1 .. 12u64).map(|i| (i * 40, if i.is_multiple_of(2) { color(255, 0, 0) } else { color(0, 90, 255) })));
+
+        let out = run(FlashLimits::default(), &input, 2000);
+        assert!(max_transitions_per_second(&out) <= MAX_TRANSITIONS);
+        assert!(out.len() < input.len());
+        assert_eq!(last_state(&out).color, Some(Rgb::new(0, 90, 255)));
+    }
+
+    #[test]
+    fn small_changes_are_not_flashes() {
+        let input: Vec<_> = (0 .. 12u64).map(|i| (i * 40, if i.is_multiple_of(2) { color(200, 200, 200) } else { color(190, 190, 190) })).collect();
+        assert_eq!(run(FlashLimits::default(), &input, 1000), input);
+    }
+
+    #[test]
+    fn brightness_flicker_is_limited() {
+        let mut input = vec![(0, color(255, 255, 255))];
+        input.extend((1 .. 16u64).map(|i| {
+            let brightness = if i.is_multiple_of(2) { Level::MAX } else { Level::saturating(1) };
+            (i * 30, Lighting::new().brightness(brightness))
+        }));
+
+        let out = run(FlashLimits::default(), &input, 3000);
+        assert_eq!(max_transitions_per_second(&out), MAX_TRANSITIONS);
+        assert_eq!(last_state(&out).brightness, Some(Level::saturating(1)));
+    }
+
+    #[test]
+    fn flash_speed_is_capped() {
+        let speed = |level: u8| Lighting::new().speed(Level::saturating(level));
+        let input = [
+            (0, Lighting::new().mode(Mode::Flash).speed(Level::MAX)),
+            (500, speed(60)),
+            (1000, speed(8)),
+            (1500, Lighting::new().mode(Mode::Breath).speed(Level::MAX)),
+            (2000, Lighting::new().mode(Mode::Flash)), //still at the breath speed
+        ];
+
+        let speeds: Vec<_> = run(FlashLimits::default(), &input, 3000).iter().map(|(_, lighting)| lighting.speed.map(Level::get)).collect();
+        assert_eq!(speeds, [Some(1), Some(1), Some(1), Some(63), Some(1)]);
+
+        let limits = FlashLimits::default().max_flash_speed(Level::saturating(16));
+        let speeds: Vec<_> = run(limits, &input, 3000).iter().map(|(_, lighting)| lighting.speed.map(Level::get)).collect();
+        assert_eq!(speeds, [Some(16), Some(16), Some(8), Some(63), Some(16)]);
+    }
+
+    #[test]
+    fn unsafe_off_lets_everything_through() {
+        let mut input = strobe(50, 10);
+        input.push((500, Lighting::new().mode(Mode::Flash).speed(Level::MAX)));
+
+        assert_eq!(run(FlashLimits::unsafe_off(), &input, 1000), input);
+    }
+}
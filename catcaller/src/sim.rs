@@ -0,0 +1,92 @@
+//! A headset that only exists in memory: it records every frame it's sent and lets the other
+//! side change its battery level or drop the connection.
+//!
+//! ```
+//! use catcaller::{sim, Lighting, Mode, Model};
+//!
+//! # #[tokio::main] async fn main() -> Result<(), catcaller::Error> {
+//! let (headset, simulator) = sim::simulated(Model::YowuSelkirk4);
+//!
+//! headset.set_lighting(Lighting::new().mode(Mode::Flash)).await?;
+//! assert_eq!(simulator.lighting().unwrap().mode, Some(Mode::Flash));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use crate::headset::BoxFuture;
+use crate::{protocol, AudioProfile, Error, Headset, Lighting, Model, Transport};
+
+#[derive(Default)]
+struct Inner {
+    frames: Vec<Vec<u8>>,
+    battery: Option<u8>,
+    disconnected: bool,
+}
+
+/// The headset's side of [`simulated`].
+#[derive(Clone, Default)]
+pub struct Simulator(Arc<Mutex<Inner>>);
+
+pub fn simulated(model: Model) -> (Headset, Simulator) {
+    let simulator = Simulator::default();
+    (Headset::with_transport(model, Arc::new(simulator.clone())), simulator)
+}
+
+impl Simulator {
+    /// Every frame written so far.
+    pub fn frames(&self) -> Vec<Vec<u8>> {
+        self.0.lock().unwrap().frames.clone()
+    }
+
+    /// The most recent lighting frame, decoded.
+    pub fn lighting(&self) -> Option<Lighting> {
+        self.0.lock().unwrap().frames.iter().rev().find_map(|frame| protocol::parse_lighting(frame))
+    }
+
+    /// The most recent audio profile frame, decoded.
+    pub fn audio_profile(&self) -> Option<AudioProfile> {
+        self.0.lock().unwrap().frames.iter().rev().find_map(|frame| protocol::parse_audio_profile(frame))
+    }
+
+    pub fn set_battery(&self, battery: Option<u8>) {
+        self.0.lock().unwrap().battery = battery;
+    }
+
+    /// Writes fail with [`Error::NotConnected`] from now on.
+    pub fn drop_connection(&self) {
+        self.0.lock().unwrap().disconnected = true;
+    }
+}
+
+impl Transport for Simulator {
+    fn write<'a>(&'a self, frame: &'a [u8]) -> BoxFuture<'a, Result<(), Error>> {
+        let mut inner = self.0.lock().unwrap();
+
+        let result = match inner.disconnected {
+            true => Err(Error::NotConnected),
+            false => {
+                inner.frames.push(frame.to_vec());
+                Ok(())
+            }
+        };
+
+        Box::pin(async move { result })
+    }
+
+    fn battery(&self) -> BoxFuture<'_, Result<Option<u8>, Error>> {
+        let battery = self.0.lock().unwrap().battery;
+        Box::pin(async move { Ok(battery) })
+    }
+
+    fn is_connected(&self) -> BoxFuture<'_, Result<bool, Error>> {
+        let connected = !self.0.lock().unwrap().disconnected;
+        Box::pin(async move { Ok(connected) })
+    }
+
+    fn disconnect(&self) -> BoxFuture<'_, Result<(), Error>> {
+        self.drop_connection();
+        Box::pin(async move { Ok(()) })
+    }
+}
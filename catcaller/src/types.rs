@@ -0,0 +1,215 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Supported headsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum Model {
+    YowuSelkirk4,
+}
+
+impl Model {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::YowuSelkirk4 => "Yowu Selkirk 4",
+        }
+    }
+
+    /// The model that advertises itself as `name`, if it's a supported one.
+    pub fn from_local_name(name: &str) -> Option<Self> {
+        match name {
+            "YOWU-SELKIRK-4" => Some(Model::YowuSelkirk4),
+            _ => None,
+        }
+    }
+}
+
+/// Light modes, in the order the headset numbers them (1-8).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Mode {
+    Default,
+    Flash,
+    Breath,
+    Rhythm,
+    Yowu,
+    LightsOff,
+    LightsOn,
+    /// Mode 8, which doesn't do anything obvious.
+    Unknown,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 8] = [
+        Mode::Default, Mode::Flash, Mode::Breath, Mode::Rhythm,
+        Mode::Yowu, Mode::LightsOff, Mode::LightsOn, Mode::Unknown,
+    ];
+
+    /// 1-8
+    pub fn number(&self) -> u8 {
+        Self::ALL.iter().position(|mode| mode == self).unwrap() as u8 + 1
+    }
+
+    /// `None` outside of 1-8.
+    pub fn from_number(number: u8) -> Option<Self> {
+        Self::ALL.get((number as usize).checked_sub(1)?).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Default => "Default",
+            Mode::Flash => "Flash",
+            Mode::Breath => "Breath",
+            Mode::Rhythm => "Rhythm",
+            Mode::Yowu => "Yowu",
+            Mode::LightsOff => "Lights off",
+            Mode::LightsOn => "Lights on",
+            Mode::Unknown => "?",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// Brightness or speed, 0-63.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(try_from = "u8", into = "u8"))]
+pub struct Level(u8);
+
+impl Level {
+    pub const MAX: Level = Level(63);
+
+    /// `None` above 63.
+    pub const fn new(level: u8) -> Option<Self> {
+        match level <= Self::MAX.0 {
+            true => Some(Level(level)),
+            false => None,
+        }
+    }
+
+    /// Clamps to 63.
+    pub const fn saturating(level: u8) -> Self {
+        match Self::new(level) {
+            Some(level) => level,
+            None => Self::MAX,
+        }
+    }
+
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for Level {
+    type Error = String;
+
+    fn try_from(level: u8) -> Result<Self, Self::Error> {
+        Level::new(level).ok_or_else(|| format!("level {level} is above 63"))
+    }
+}
+
+impl From<Level> for u8 {
+    fn from(level: Level) -> u8 {
+        level.0
+    }
+}
+
+/// Audio profiles 0-3.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(try_from = "u8", into = "u8"))]
+pub struct AudioProfile(u8);
+
+impl AudioProfile {
+    /// `None` above 3.
+    pub const fn new(profile: u8) -> Option<Self> {
+        match profile <= 3 {
+            true => Some(AudioProfile(profile)),
+            false => None,
+        }
+    }
+
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for AudioProfile {
+    type Error = String;
+
+    fn try_from(profile: u8) -> Result<Self, Self::Error> {
+        AudioProfile::new(profile).ok_or_else(|| format!("audio profile {profile} is above 3"))
+    }
+}
+
+impl From<AudioProfile> for u8 {
+    fn from(profile: AudioProfile) -> u8 {
+        profile.0
+    }
+}
+
+/// One lighting command. Whatever is left as `None` stays as it is on the headset.
+///
+/// ```
+/// use catcaller::{Level, Lighting, Mode, Rgb};
+///
+/// let lighting = Lighting::new().mode(Mode::Breath).color(Rgb::new(255, 0, 0)).brightness(Level::MAX);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub struct Lighting {
+    pub mode: Option<Mode>,
+    pub color: Option<Rgb>,
+    pub brightness: Option<Level>,
+    /// Speed, or the duration in rhythm mode.
+    pub speed: Option<Level>,
+}
+
+impl Lighting {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(self, mode: Mode) -> Self {
+        Self { mode: Some(mode), ..self }
+    }
+
+    pub fn color(self, color: Rgb) -> Self {
+        Self { color: Some(color), ..self }
+    }
+
+    pub fn brightness(self, brightness: Level) -> Self {
+        Self { brightness: Some(brightness), ..self }
+    }
+
+    pub fn speed(self, speed: Level) -> Self {
+        Self { speed: Some(speed), ..self }
+    }
+
+    /// From the numbers a lighting frame carries, where 0 leaves something as it is: mode 1-8,
+    /// color, and brightness and speed (clamped to 63).
+    pub fn from_numbers(mode: u8, rgb: [u8; 3], settings: [u8; 2]) -> Self {
+        let [r, g, b] = rgb;
+        let level = |level: u8| (level != 0).then_some(Level::saturating(level));
+
+        Self {
+            mode: Mode::from_number(mode),
+            color: (rgb != [0, 0, 0]).then_some(Rgb::new(r, g, b)),
+            brightness: level(settings[0]),
+            speed: level(settings[1]),
+        }
+    }
+}
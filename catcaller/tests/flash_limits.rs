@@ -0,0 +1,130 @@
+// hostile lighting sequences through a Headset, on tokio's paused clock
+
+use std::sync::{Arc, Mutex};
+
+use catcaller::{protocol, sim, BoxFuture, Error, FlashLimits, Headset, Level, Lighting, Mode, Model, Transport};
+use tokio::time::{sleep, Duration, Instant};
+
+// the simulator, plus when each lighting frame reached it
+#[derive(Clone, Default)]
+struct Timed {
+    simulator: sim::Simulator,
+    lighting: Arc<Mutex<Vec<(Instant, Lighting)>>>,
+}
+
+impl Transport for Timed {
+    fn write<'a>(&'a self, frame: &'a [u8]) -> BoxFuture<'a, Result<(), Error>> {
+        if let Some(lighting) = protocol::parse_lighting(frame) {
+            self.lighting.lock().unwrap().push((Instant::now(), lighting));
+        }
+
+        self.simulator.write(frame)
+    }
+
+    fn battery(&self) -> BoxFuture<'_, Result<Option<u8>, Error>> {
+        self.simulator.battery()
+    }
+
+    fn is_connected(&self) -> BoxFuture<'_, Result<bool, Error>> {
+        self.simulator.is_connected()
+    }
+
+    fn disconnect(&self) -> BoxFuture<'_, Result<(), Error>> {
+        self.simulator.disconnect()
+    }
+}
+
+fn headset() -> (Headset, Timed) {
+    let timed = Timed::default();
+    (Headset::with_transport(Model::YowuSelkirk4, Arc::new(timed.clone())), timed)
+}
+
+// off first, the lights being on is what a new Headset assumes
+fn off_on(i: u32) -> Lighting {
+    Lighting::new().mode(if i.is_multiple_of(2) { Mode::LightsOff } else { Mode::LightsOn })
+}
+
+// the most times the lights went on or off within any second. a held back command that gets
+// undone by the next one still goes out, but changes nothing
+fn max_changes_per_second(frames: &[(Instant, Lighting)]) -> usize {
+    let mut shown = Some(Mode::LightsOn);
+    let mut changes = Vec::new();
+
+    for &(t, lighting) in frames {
+        if std::mem::replace(&mut shown, lighting.mode) != lighting.mode {
+            changes.push(t);
+        }
+    }
+
+    changes.iter()
+        .map(|&t| changes.iter().filter(|&&u| u >= t && u < t + Duration::from_secs(1)).count())
+        .max()
+        .unwrap_or(0)
+}
+
+#[tokio::test(start_paused = true)]
+async fn strobe_is_limited_and_lands() {
+    let (headset, timed) = headset();
+
+    for i in 0 .. 40 {
+        headset.set_lighting(off_on(i)).await.unwrap();
+        sleep(Duration::from_millis(25)).await;
+    }
+
+    sleep(Duration::from_secs(2)).await;
+    let frames = timed.lighting.lock().unwrap().clone();
+
+    // 3 flashes a second at most
+    assert_eq!(max_changes_per_second(&frames), 6);
+    assert!(frames.len() < 40);
+    assert_eq!(timed.simulator.lighting().unwrap().mode, Some(Mode::LightsOn));
+    assert_eq!(headset.state().lighting.unwrap().mode, Some(Mode::LightsOn));
+}
+
+#[tokio::test(start_paused = true)]
+async fn held_back_commands_are_merged() {
+    let (headset, timed) = headset();
+
+    for i in 0 .. 6 {
+        headset.set_lighting(off_on(i)).await.unwrap();
+    }
+
+    // held back together, and sent as one once the second is over
+    headset.set_lighting(off_on(6)).await.unwrap();
+    headset.set_lighting(Lighting::new().brightness(Level::MAX)).await.unwrap();
+    assert_eq!(timed.lighting.lock().unwrap().len(), 6);
+
+    sleep(Duration::from_millis(1100)).await;
+    let last = timed.simulator.lighting().unwrap();
+
+    assert_eq!(timed.lighting.lock().unwrap().len(), 7);
+    assert_eq!((last.mode, last.brightness), (Some(Mode::LightsOff), Some(Level::MAX)));
+}
+
+#[tokio::test(start_paused = true)]
+async fn flash_speed_is_capped() {
+    let (headset, timed) = headset();
+
+    headset.set_lighting(Lighting::new().mode(Mode::Flash).speed(Level::MAX)).await.unwrap();
+    assert_eq!(timed.simulator.lighting().unwrap().speed, Some(Level::saturating(1)));
+
+    headset.set_flash_limits(FlashLimits::default().max_flash_speed(Level::saturating(4))).await;
+    headset.set_lighting(Lighting::new().speed(Level::MAX)).await.unwrap();
+    assert_eq!(timed.simulator.lighting().unwrap().speed, Some(Level::saturating(4)));
+}
+
+#[tokio::test(start_paused = true)]
+async fn unsafe_off_lets_everything_through() {
+    let (headset, timed) = headset();
+    headset.set_flash_limits(FlashLimits::unsafe_off()).await;
+
+    for i in 0 .. 40 {
+        headset.set_lighting(off_on(i)).await.unwrap();
+        sleep(Duration::from_millis(25)).await;
+    }
+
+    headset.set_lighting(Lighting::new().mode(Mode::Flash).speed(Level::MAX)).await.unwrap();
+
+    assert_eq!(timed.lighting.lock().unwrap().len(), 41);
+    assert_eq!(timed.simulator.lighting().unwrap().speed, Some(Level::MAX));
+}
@@ -0,0 +1,111 @@
+// the public api against sim::simulated, the way an application would use it
+
+use std::error::Error as _;
+use std::io;
+use std::sync::Arc;
+
+use catcaller::{sim, AudioProfile, BoxFuture, Error, Headset, Level, Lighting, Mode, Model, Rgb, Transport};
+use tokio::time::{timeout, Duration};
+
+#[tokio::test]
+async fn frames_on_the_wire() {
+    let (headset, simulator) = sim::simulated(Model::YowuSelkirk4);
+
+    let lighting = Lighting::new().mode(Mode::Breath).color(Rgb::new(255, 0, 128)).brightness(Level::MAX).speed(Level::saturating(20));
+    headset.set_lighting(lighting).await.unwrap();
+    headset.set_audio_profile(AudioProfile::new(2).unwrap()).await.unwrap();
+
+    assert_eq!(simulator.frames(), [
+        vec![0xFC, 0x04, 0x01, 0x06, 0x03, 0xFF, 0x00, 0x80, 0x3F, 0x14, 0x24],
+        vec![0xFC, 0x05, 0x02, 0x02, 0x92, 0x02, 0x67],
+    ]);
+
+    assert_eq!(simulator.lighting(), Some(lighting));
+    assert_eq!(simulator.audio_profile(), AudioProfile::new(2));
+}
+
+#[tokio::test]
+async fn state_follows_what_was_sent() {
+    let (headset, simulator) = sim::simulated(Model::YowuSelkirk4);
+    let mut states = headset.states();
+
+    assert_eq!(headset.model(), Model::YowuSelkirk4);
+    assert!(headset.state().connected);
+    assert_eq!(headset.state().lighting, None);
+
+    headset.set_lighting(Lighting::new().mode(Mode::LightsOn).color(Rgb::new(0, 0, 255))).await.unwrap();
+    headset.set_lighting(Lighting::new().brightness(Level::saturating(10))).await.unwrap();
+
+    // the two changes got merged into one for a listener that wasn't waiting
+    let state = states.next().await.unwrap();
+    assert_eq!(state.lighting, Some(Lighting::new().mode(Mode::LightsOn).color(Rgb::new(0, 0, 255)).brightness(Level::saturating(10))));
+
+    headset.set_audio_profile(AudioProfile::new(1).unwrap()).await.unwrap();
+    assert_eq!(states.next().await.unwrap().audio_profile, AudioProfile::new(1));
+
+    simulator.set_battery(Some(55));
+    assert_eq!(headset.battery().await.unwrap(), Some(55));
+    assert_eq!(states.next().await.unwrap().battery, Some(55));
+
+    // reading the same level again isn't a change
+    headset.battery().await.unwrap();
+    assert!(timeout(Duration::from_millis(50), states.next()).await.is_err());
+}
+
+#[tokio::test]
+async fn lost_connection() {
+    let (headset, simulator) = sim::simulated(Model::YowuSelkirk4);
+    let mut states = headset.states();
+
+    simulator.drop_connection();
+
+    assert!(matches!(headset.set_lighting(Lighting::new().mode(Mode::LightsOff)).await, Err(Error::NotConnected)));
+    assert!(matches!(headset.set_audio_profile(AudioProfile::new(0).unwrap()).await, Err(Error::NotConnected)));
+    assert!(!states.next().await.unwrap().connected);
+    assert!(!headset.is_connected().await.unwrap());
+    assert!(simulator.frames().is_empty());
+}
+
+#[tokio::test]
+async fn disconnect() {
+    let (headset, simulator) = sim::simulated(Model::YowuSelkirk4);
+    let mut states = headset.states();
+
+    headset.disconnect().await.unwrap();
+
+    assert_eq!(states.next().await.map(|state| state.connected), Some(false));
+    assert!(states.next().await.is_none(), "the headset is gone");
+    assert!(simulator.frames().is_empty());
+}
+
+// what a transport of its own reports comes back as Error::Bluetooth, with the cause attached
+struct Broken;
+
+impl Transport for Broken {
+    fn write<'a>(&'a self, _: &'a [u8]) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async { Err(Error::Bluetooth(Box::new(io::Error::new(io::ErrorKind::BrokenPipe, "radio fell off")))) })
+    }
+
+    fn battery(&self) -> BoxFuture<'_, Result<Option<u8>, Error>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn is_connected(&self) -> BoxFuture<'_, Result<bool, Error>> {
+        Box::pin(async { Ok(true) })
+    }
+
+    fn disconnect(&self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn transport_errors() {
+    let headset = Headset::with_transport(Model::YowuSelkirk4, Arc::new(Broken));
+
+    let e = headset.set_lighting(Lighting::new().mode(Mode::Yowu)).await.unwrap_err();
+    assert_eq!(e.to_string(), "bluetooth: radio fell off");
+    assert_eq!(e.source().and_then(|source| source.downcast_ref::<io::Error>()).map(io::Error::kind), Some(io::ErrorKind::BrokenPipe));
+
+    assert_eq!(headset.state().lighting, None, "nothing was shown");
+}
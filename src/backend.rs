@@ -0,0 +1,70 @@
+// bt_stuff plus every integration that feeds it. runs inside the gui when there's no daemon
+// around, otherwise inside the daemon
+
+use tokio::sync::{mpsc, watch};
+
+use crate::bt::{bt_stuff, BtCommands, BtToGui, HeadsetState};
+use crate::config::Config;
+use crate::{dmx, openrgb, osc, scripting, web};
+
+pub struct Backend {
+    pub tx: mpsc::Sender<BtCommands>,
+    pub state_rx: watch::Receiver<HeadsetState>,
+    pub events: Option<mpsc::Sender<String>>, //for on_event in scripts, None when they run in the daemon
+}
+
+pub fn spawn(config: &Config, gui_tx: mpsc::Sender<BtToGui>) -> Backend {
+    let (tx, mut rx) = mpsc::channel(4);
+    let (state_tx, state_rx) = watch::channel(HeadsetState::default());
+
+    if let Some(openrgb_config) = config.openrgb.clone() {
+        tokio::spawn(openrgb::mirror(openrgb_config, tx.clone(), state_rx.clone()));
+    }
+
+    if let Some(dmx_config) = config.dmx.clone() {
+        tokio::spawn(dmx::listen(dmx_config, tx.clone()));
+    }
+
+    if let Some(osc_config) = config.osc.clone() {
+        tokio::spawn(osc::serve(osc_config, config.presets.clone(), tx.clone(), state_rx.clone()));
+    }
+
+    if let Some(web_config) = config.web.clone() {
+        tokio::spawn(web::serve(web_config, config.presets.clone(), tx.clone(), state_rx.clone()));
+    }
+
+    let events = scripting::spawn(config.scripts.clone(), config.presets.clone(), tx.clone(), state_rx.clone());
+
+    #[cfg(target_os = "linux")]
+    if config.dbus {
+        tokio::spawn(crate::dbus_service::serve(config.presets.clone(), tx.clone(), state_rx.clone()));
+    }
+
+    if config.safety.allow_unsafe_flashing {
+        println!("warning: flash limits are off (allow_unsafe_flashing in [safety])");
+    }
+
+    let limits = config.safety.limits();
+
+    tokio::spawn(async move {
+        match bt_stuff(&mut rx, &gui_tx, &state_tx, limits).await {
+            Ok(_) => (),
+            Err(e) => println!("error! {e}"),
+        };
+    });
+
+    Backend { tx, state_rx, events: Some(events) }
+}
+
+// what the gui uses: the daemon if one is running, otherwise its own backend
+pub async fn start(config: &Config, gui_tx: mpsc::Sender<BtToGui>) -> Backend {
+    #[cfg(unix)]
+    if let Ok(conn) = crate::ipc::Connection::connect().await {
+        let (tx, rx) = mpsc::channel(4);
+        let (state_tx, state_rx) = watch::channel(HeadsetState::default());
+        tokio::spawn(crate::ipc::bridge(conn, crate::ipc::Connection::connect, rx, gui_tx, state_tx));
+        return Backend { tx, state_rx, events: None };
+    }
+
+    spawn(config, gui_tx)
+}
@@ -1,112 +1,201 @@
 use std::error::Error;
+use std::sync::{Arc, Weak};
 
-use btleplug::api::bleuuid::uuid_from_u16;
-use btleplug::api::{Central, Manager as _, Peripheral, ScanFilter, BDAddr, WriteType};
-use btleplug::platform::Manager;
-
-use tokio::sync::mpsc;
+use catcaller::{sim, AudioProfile, FlashLimits, Headset, Level, Lighting, Model, Scanner};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch};
 use tokio::time::{sleep, Duration};
 
+use crate::config::Preset;
+
+// modes 1-8, in the order the headset numbers them
+pub const MODES: [&str; 8] = [
+    "Default",   "Flash",
+    "Breath",    "Rhythm",
+    "Yowu",      "Lights off",
+    "Lights on", "?",
+];
+
+#[derive(Debug)]
 pub enum BtCommands {
     SetMode(CmdData),
+    SetAudioProfile(u8),
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CmdData {
     pub mode: u8,
     pub rgb: [u8; 3],
     pub settings: [u8; 2], //brightness + speed / bpm + duration
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub enum BtToGui {
     #[default] Init,
     AdapterConnected,
-    Found(HeadsetType),
+    Found(Model),
     Connected,
     Ready,
 }
 
-#[derive(Debug)]
-pub struct BtInfo {
-    pub name: String,
-    pub is_connected: bool,
-    pub address: BDAddr,
+// what everything that isn't the gui gets to look at
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HeadsetState {
+    pub status: BtToGui,
+    pub headset: Option<Model>,
+    pub lighting: Option<CmdData>, //what the headset shows, everything sent so far merged, 0 = don't know
+    pub audio_profile: Option<u8>,
+    pub battery: Option<u8>, //percent
 }
 
-#[derive(Debug)]
-pub enum HeadsetType {
-    YowuSelkirk4,
-}
-
-impl HeadsetType {
-    pub fn name(&self) -> String {
+impl BtToGui {
+    pub fn name(&self) -> &'static str {
         match self {
-            HeadsetType::YowuSelkirk4 => "Yowu Selkirk 4".to_string(),
+            BtToGui::Init => "init",
+            BtToGui::AdapterConnected => "adapter connected",
+            BtToGui::Found(_) => "found",
+            BtToGui::Connected => "connected",
+            BtToGui::Ready => "ready",
         }
     }
 }
 
-pub async fn bt_stuff(rx: &mut mpsc::Receiver<BtCommands>, tx: &mpsc::Sender<BtToGui>) -> Result<(), Box<dyn Error>> {
-    let manager = Manager::new().await?;
-    let mut adapter_list = Vec::new();
+// zeroes are the headset's "leave as it is"
+impl From<CmdData> for Lighting {
+    fn from(data: CmdData) -> Self {
+        Lighting::from_numbers(data.mode, data.rgb, data.settings)
+    }
+}
+
+impl CmdData {
+    // newer fields win, zeroes leave what was there. sending the result is the same as sending both
+    pub fn merge(self, newer: CmdData) -> CmdData {
+        let pick = |older: u8, newer: u8| if newer != 0 { newer } else { older };
 
-    while adapter_list.is_empty() { //find BT adapter
-        adapter_list = manager.adapters().await?;
-        sleep(Duration::from_millis(750)).await;
+        CmdData {
+            mode: pick(self.mode, newer.mode),
+            rgb: if newer.rgb != [0, 0, 0] { newer.rgb } else { self.rgb },
+            settings: [pick(self.settings[0], newer.settings[0]), pick(self.settings[1], newer.settings[1])],
+        }
     }
+}
 
-    tx.send(BtToGui::AdapterConnected).await?;
+// for whatever sends faster than the headset takes it (faders, sliders). what hasn't gone out yet
+// gets merged with what comes after it, so values in between are skipped but nothing that was set
+// gets lost, a mode or a preset included
+#[derive(Clone)]
+pub struct Coalescer {
+    pending: Arc<watch::Sender<Option<CmdData>>>,
+    tx: mpsc::Sender<BtCommands>,
+}
 
-    let headset;
+impl Coalescer {
+    pub fn new(tx: mpsc::Sender<BtCommands>) -> Self {
+        let (pending, pending_rx) = watch::channel(None);
+        let pending = Arc::new(pending);
+        tokio::spawn(forward(Arc::downgrade(&pending), pending_rx, tx.clone()));
 
-    'outer: loop { //find headset
-        for adapter in adapter_list.iter() {
-            adapter
-                .start_scan(ScanFilter::default())
-                .await
-                .expect("Can't scan BLE adapter for connected devices...");
+        Self { pending, tx }
+    }
 
-            sleep(Duration::from_millis(850)).await;
+    // false once the backend is gone
+    pub fn send(&self, data: CmdData) -> bool {
+        self.pending.send_modify(|pending| *pending = Some(pending.map_or(data, |older| older.merge(data))));
+        !self.tx.is_closed()
+    }
+}
 
-            for peripheral in adapter.peripherals().await? {
-                let properties = peripheral.properties().await?;
-                let local_name = properties.unwrap().local_name.unwrap_or(String::from("name unknown"));
+async fn forward(pending: Weak<watch::Sender<Option<CmdData>>>, mut pending_rx: watch::Receiver<Option<CmdData>>, tx: mpsc::Sender<BtCommands>) {
+    while pending_rx.changed().await.is_ok() {
+        // taken without telling anyone, what comes in while this waits for room starts over
+        let data = match pending.upgrade() {
+            Some(pending) => {
+                let mut data = None;
+                pending.send_if_modified(|pending| { data = pending.take(); false });
+                data
+            }
 
-                if local_name == "YOWU-SELKIRK-4" {
-                    headset = peripheral;
-                    tx.send(BtToGui::Found(HeadsetType::YowuSelkirk4)).await?;
-                    break 'outer;
-                }
+            None => *pending_rx.borrow(), //the last one before it was dropped
+        };
+
+        if let Some(data) = data {
+            if tx.send(BtCommands::SetMode(data)).await.is_err() {
+                return;
             }
         }
     }
+}
 
-    while !headset.is_connected().await? { //connect
-        if let Err(err) = headset.connect().await {
-            println!("Error connecting to peripheral: {}", err);
-        }
+impl From<&Preset> for CmdData {
+    fn from(preset: &Preset) -> Self {
+        CmdData { mode: preset.mode, rgb: preset.rgb, settings: preset.settings }
+    }
+}
 
-        sleep(Duration::from_millis(500)).await;
+impl From<Lighting> for CmdData {
+    fn from(lighting: Lighting) -> Self {
+        let level = |level: Option<Level>| level.map_or(0, Level::get);
+
+        CmdData {
+            mode: lighting.mode.map_or(0, |mode| mode.number()),
+            rgb: lighting.color.map_or([0, 0, 0], |rgb| [rgb.r, rgb.g, rgb.b]),
+            settings: [level(lighting.brightness), level(lighting.speed)],
+        }
     }
+}
+
+pub async fn bt_stuff(rx: &mut mpsc::Receiver<BtCommands>, tx: &mpsc::Sender<BtToGui>, state: &watch::Sender<HeadsetState>, limits: FlashLimits) -> Result<(), Box<dyn Error>> {
+    // CATCALLER_SIMULATE=1 runs against a headset that only exists in memory
+    let headset = match std::env::var("CATCALLER_SIMULATE").is_ok_and(|s| s == "1") {
+        true => {
+            report(tx, state, BtToGui::AdapterConnected).await?;
+            found(tx, state, Model::YowuSelkirk4).await?;
+            sim::simulated(Model::YowuSelkirk4).0
+        }
 
-    tx.send(BtToGui::Connected).await?;
+        false => connect(tx, state).await?,
+    };
 
-    headset.discover_services().await?;
+    headset.set_flash_limits(limits).await;
+    report(tx, state, BtToGui::Connected).await?;
+    report(tx, state, BtToGui::Ready).await?;
 
-    sleep(Duration::from_millis(100)).await;
+    let mut battery_timer = tokio::time::interval(Duration::from_secs(60));
+    let mut headset_states = headset.states(); //lighting held back by the flash limits lands later
 
-    let chara = uuid_from_u16(0x2A06);
-    let chars = headset.characteristics();
-    let cmd_char = chars.iter().find(|c| c.uuid == chara).expect("Unable to find characterics");
+    loop {
+        tokio::select! {
+            commands = rx.recv() => {
+                let Some(commands) = commands else { break };
 
-    tx.send(BtToGui::Ready).await?;
+                let result = match commands {
+                    BtCommands::SetMode(data) => headset.set_lighting(data.into()).await,
 
-    while let Some(commands) = rx.recv().await {
-        match commands {
-            BtCommands::SetMode(data) => {
-                if headset.is_connected().await? {
-                    headset.write(cmd_char, &command(data), WriteType::WithoutResponse).await?;
+                    BtCommands::SetAudioProfile(profile) => match AudioProfile::new(profile) {
+                        Some(audio_profile) => headset.set_audio_profile(audio_profile).await
+                            .map(|_| state.send_modify(|state| state.audio_profile = Some(profile))),
+
+                        None => {
+                            println!("there's no audio profile {profile}");
+                            Ok(())
+                        }
+                    },
+                };
+
+                match result {
+                    Ok(_) | Err(catcaller::Error::NotConnected) => (),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            Some(headset_state) = headset_states.next() => {
+                let lighting = headset_state.lighting.map(CmdData::from);
+                state.send_if_modified(|state| std::mem::replace(&mut state.lighting, lighting) != lighting);
+            }
+
+            _ = battery_timer.tick() => {
+                if let Ok(battery) = headset.battery().await {
+                    state.send_if_modified(|state| std::mem::replace(&mut state.battery, battery) != battery);
                 }
             }
         }
@@ -115,15 +204,43 @@ pub async fn bt_stuff(rx: &mut mpsc::Receiver<BtCommands>, tx: &mpsc::Sender<BtT
     Ok(())
 }
 
-fn command(d: CmdData) -> [u8; 11] {
-    // 0xFC, 0x04, 0x01, 0x06 = header / command
-    let mut cmd = [0xFC, 0x04, 0x01, 0x06, d.mode, d.rgb[0], d.rgb[1], d.rgb[2], d.settings[0], d.settings[1], 0x00];
-    cmd[10] = cmd.iter().fold(0, |checksum, x| checksum.wrapping_sub(*x));
+async fn connect(tx: &mpsc::Sender<BtToGui>, state: &watch::Sender<HeadsetState>) -> Result<Headset, Box<dyn Error>> {
+    let scanner = loop { //find BT adapter
+        match Scanner::new().await {
+            Ok(scanner) => break scanner,
+            Err(catcaller::Error::NoAdapter) => sleep(Duration::from_millis(750)).await,
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    report(tx, state, BtToGui::AdapterConnected).await?;
 
-    cmd
+    let discovered = loop { //find headset
+        if let Some(discovered) = scanner.scan().await?.into_iter().next() {
+            break discovered;
+        }
+    };
+
+    found(tx, state, discovered.model()).await?;
+
+    loop {
+        match discovered.connect().await {
+            Ok(headset) => return Ok(headset),
+            Err(err) => println!("Error connecting to peripheral: {}", err),
+        }
+
+        sleep(Duration::from_millis(500)).await;
+    }
+}
+
+async fn found(tx: &mpsc::Sender<BtToGui>, state: &watch::Sender<HeadsetState>, model: Model) -> Result<(), Box<dyn Error>> {
+    state.send_modify(|state| state.headset = Some(model));
+    report(tx, state, BtToGui::Found(model)).await
 }
 
-//audio profile
-// FC 05 02 02 92 xx cc
-// xx = audio profile 0-3
-// cc = checksum
+async fn report(tx: &mpsc::Sender<BtToGui>, state: &watch::Sender<HeadsetState>, status: BtToGui) -> Result<(), Box<dyn Error>> {
+    state.send_modify(|state| state.status = status.clone());
+    tx.send(status).await?;
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+// while the tui is up the backend runs in the same process, and everything it (or a library) prints
+// would land in the middle of the screen. so stdout and stderr get pointed at a pipe: every line
+// goes into a log file and to the tui's status line, and the tui draws to the terminal behind them
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tokio::sync::watch;
+
+// the terminal's stdout and stderr while captured, for release and the panic hook
+static SAVED: Mutex<Option<[OwnedFd; 2]>> = Mutex::new(None);
+
+pub fn log_path() -> Option<PathBuf> {
+    Some(dirs::state_dir().or_else(dirs::cache_dir)?.join("catcaller").join("tui.log"))
+}
+
+// returns the terminal to draw to
+pub fn start(lines: watch::Sender<String>) -> io::Result<File> {
+    let path = log_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state or cache directory"))?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let mut log = File::create(&path)?;
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    let saved = [io::stdout().as_fd().try_clone_to_owned()?, io::stderr().as_fd().try_clone_to_owned()?];
+    let terminal = File::from(saved[0].try_clone()?);
+
+    let _ = io::stdout().flush();
+    redirect(&[write.as_raw_fd(); 2])?;
+    *SAVED.lock().unwrap() = Some(saved);
+
+    std::thread::spawn(move || {
+        for line in BufReader::new(File::from(read)).lines().map_while(Result::ok) {
+            let _ = writeln!(log, "{line}");
+            lines.send_replace(line); //the status line only shows the newest one
+        }
+    });
+
+    Ok(terminal)
+}
+
+// back to the terminal. the pipe closes along with the last end pointing at it, which ends the thread
+pub fn release() {
+    if let Some(saved) = SAVED.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        let _ = io::stdout().flush();
+        let _ = redirect(&saved.each_ref().map(|fd| fd.as_raw_fd()));
+    }
+}
+
+fn redirect(to: &[RawFd; 2]) -> io::Result<()> {
+    for (fd, to) in [libc::STDOUT_FILENO, libc::STDERR_FILENO].into_iter().zip(to) {
+        if unsafe { libc::dup2(*to, fd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
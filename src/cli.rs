@@ -0,0 +1,186 @@
+// command line client for the daemon, e.g. `blatand color ff0000` or `blatand preset "on air"`
+
+use crate::bt::{CmdData, MODES};
+use crate::ipc::{read_message, write_message, Connection, Reply, Request};
+
+const USAGE: &str = "\
+usage: blatand [command]
+
+without a command the gui is started, --minimized starts it hidden in the tray
+
+commands:
+    daemon                  run in the background and own the bluetooth connection
+    tui                     the gui's controls in the terminal
+    doctor                  check the bluetooth setup when the headset isn't found
+    status                  show connection status, battery and the last applied settings
+    color <r> <g> <b>       set the color, 0-255 each. also takes a hex color like ff8800
+    mode <1-8 | name>       set the mode
+    settings <a> <b>        brightness + speed / bpm + duration, 0-63 each
+    audio-profile <0-3>     set the audio profile
+    preset <name>           apply a preset from the config file
+    event <name>            call on_event(name) in scripts
+    autostart <on | off>    start the gui minimized when logging in";
+
+// returns the exit code
+pub async fn run(args: &[String]) -> i32 {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let request = match args.as_slice() {
+        ["status"] => None,
+        ["help" | "-h" | "--help"] => {
+            println!("{USAGE}");
+            return 0;
+        }
+
+        ["autostart", on @ ("on" | "off")] => return autostart(*on == "on"),
+
+        _ => match parse(&args) {
+            Some(request) => Some(request),
+            None => {
+                println!("{USAGE}");
+                return 2;
+            }
+        },
+    };
+
+    let mut conn = match Connection::connect().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            println!("can't reach the daemon ({e}), start it with `blatand daemon`");
+            return 1;
+        }
+    };
+
+    let Ok(Some(Reply::State(state))) = read_message(&mut conn.reader).await else {
+        println!("daemon didn't send its state");
+        return 1;
+    };
+
+    let Some(request) = request else {
+        println!("status: {}", state.status.name());
+
+        if let Some(headset) = &state.headset {
+            println!("headset: {}", headset.name());
+        }
+
+        if let Some(battery) = state.battery {
+            println!("battery: {battery}%");
+        }
+
+        if let Some(data) = state.lighting {
+            let [r, g, b] = data.rgb;
+            println!("lighting: mode {}, color {r} {g} {b}, settings {} {}", data.mode, data.settings[0], data.settings[1]);
+        }
+
+        if let Some(profile) = state.audio_profile {
+            println!("audio profile: {profile}");
+        }
+
+        return 0;
+    };
+
+    if let Err(e) = write_message(&mut conn.writer, &request).await {
+        println!("can't talk to the daemon: {e}");
+        return 1;
+    }
+
+    loop {
+        match read_message(&mut conn.reader).await {
+            Ok(Some(Reply::Done)) => return 0,
+            Ok(Some(Reply::Error { message })) => {
+                println!("{message}");
+                return 1;
+            }
+
+            Ok(Some(_)) => (), //state updates
+            Ok(None) => {
+                println!("daemon closed the connection");
+                return 1;
+            }
+
+            Err(e) => {
+                println!("can't talk to the daemon: {e}");
+                return 1;
+            }
+        }
+    }
+}
+
+fn parse(args: &[&str]) -> Option<Request> {
+    Some(match args {
+        ["color", hex] => {
+            let hex = hex.trim_start_matches('#');
+            let value = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+            let [_, r, g, b] = value.to_be_bytes();
+            Request::SetMode(CmdData { rgb: [r, g, b], ..Default::default() })
+        }
+
+        ["color", r, g, b] => Request::SetMode(CmdData { rgb: [r.parse().ok()?, g.parse().ok()?, b.parse().ok()?], ..Default::default() }),
+
+        ["mode", mode] => {
+            let mode = match mode.parse::<u8>() {
+                Ok(n) if (1 ..= 8).contains(&n) => n,
+                Ok(_) => return None,
+                Err(_) => MODES.iter().position(|name| name.eq_ignore_ascii_case(mode))? as u8 + 1,
+            };
+
+            Request::SetMode(CmdData { mode, ..Default::default() })
+        }
+
+        ["settings", first, second] => {
+            let settings = [first.parse().ok().filter(|&v| v <= 63)?, second.parse().ok().filter(|&v| v <= 63)?];
+            Request::SetMode(CmdData { settings, ..Default::default() })
+        }
+
+        ["audio-profile", profile] => Request::SetAudioProfile { profile: profile.parse().ok().filter(|&p| p <= 3)? },
+        ["preset", name @ ..] if !name.is_empty() => Request::ApplyPreset { name: name.join(" ") },
+        ["event", name @ ..] if !name.is_empty() => Request::Event { name: name.join(" ") },
+        _ => return None,
+    })
+}
+
+// xdg autostart entry, picked up by pretty much every desktop
+fn autostart(enable: bool) -> i32 {
+    let Some(path) = dirs::config_dir().map(|dir| dir.join("autostart").join("catcaller.desktop")) else {
+        println!("can't find the config directory");
+        return 1;
+    };
+
+    let result = match enable {
+        true => std::env::current_exe().and_then(|exe| {
+            std::fs::create_dir_all(path.parent().unwrap())?;
+
+            // desktop entry quoting, then the string escapes on top of that
+            let exe = exe.to_string_lossy().chars().flat_map(|c| match c {
+                '"' | '`' | '$' | '\\' => vec!['\\', c],
+                c => vec![c],
+            }).collect::<String>().replace('\\', "\\\\");
+
+            std::fs::write(&path, format!("\
+[Desktop Entry]
+Type=Application
+Name=Yowu CatCaller
+Exec=\"{exe}\" --minimized
+Icon=audio-headphones
+Terminal=false
+X-GNOME-Autostart-enabled=true
+"))
+        }),
+
+        false => match std::fs::remove_file(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        },
+    };
+
+    match result {
+        Ok(_) if enable => println!("wrote {}", path.display()),
+        Ok(_) => println!("autostart disabled"),
+        Err(e) => {
+            println!("can't update {}: {e}", path.display());
+            return 1;
+        }
+    }
+
+    0
+}
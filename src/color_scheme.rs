@@ -0,0 +1,61 @@
+// the desktop's light/dark preference from the settings portal, for theme = "auto"
+
+use futures_util::StreamExt;
+use winit::event_loop::EventLoopProxy;
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{dbus_proxy, Connection};
+
+use crate::ui::GuiEvent;
+
+const NAMESPACE: &str = "org.freedesktop.appearance";
+const KEY: &str = "color-scheme";
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.Settings",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Settings {
+    fn read(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+
+    #[dbus_proxy(signal)]
+    fn setting_changed(&self, namespace: &str, key: &str, value: Value<'_>) -> zbus::Result<()>;
+}
+
+pub async fn follow(proxy: EventLoopProxy<GuiEvent>) {
+    if let Err(e) = run(proxy).await {
+        println!("color scheme: {e}");
+    }
+}
+
+async fn run(proxy: EventLoopProxy<GuiEvent>) -> zbus::Result<()> {
+    let conn = Connection::session().await?;
+    let settings = SettingsProxy::new(&conn).await?;
+
+    let mut changes = settings.receive_setting_changed().await?;
+
+    let dark = settings.read(NAMESPACE, KEY).await.is_ok_and(|scheme| is_dark(&scheme)); //older portals don't have it
+
+    if proxy.send_event(GuiEvent::SystemDark(dark)).is_err() {
+        return Ok(());
+    }
+
+    while let Some(signal) = changes.next().await {
+        let args = signal.args()?;
+
+        if args.namespace == NAMESPACE && args.key == KEY && proxy.send_event(GuiEvent::SystemDark(is_dark(&args.value))).is_err() {
+            break; //event loop is gone
+        }
+    }
+
+    Ok(())
+}
+
+// 0 no preference, 1 dark, 2 light. Read wraps it in one more variant
+fn is_dark(value: &Value) -> bool {
+    match value {
+        Value::Value(inner) => is_dark(inner),
+        Value::U32(scheme) => *scheme == 1,
+        _ => false,
+    }
+}
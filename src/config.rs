@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+// shared with the python bindings
+pub use catcaller::presets::{config_dir, Preset};
+
+use crate::dmx::DmxConfig;
+use crate::openrgb::OpenRgbConfig;
+use crate::osc::OscConfig;
+use crate::safety::SafetyConfig;
+use crate::scripting::ScriptConfig;
+use crate::web::WebConfig;
+use crate::ui::GuiConfig;
+
+// ~/.config/catcaller/config.toml on linux
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub openrgb: Option<OpenRgbConfig>,
+    pub dmx: Option<DmxConfig>,
+    pub osc: Option<OscConfig>,
+    pub web: Option<WebConfig>,
+    pub dbus: bool, //linux only
+    pub presets: Vec<Preset>,
+    pub gui: GuiConfig,
+    pub keys: HashMap<String, String>, //see keys.rs
+    pub scripts: ScriptConfig, //see scripting.rs
+    pub safety: SafetyConfig, //see safety.rs
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            openrgb: None,
+            dmx: None,
+            osc: None,
+            web: None,
+            dbus: true,
+            presets: Vec::new(),
+            gui: GuiConfig::default(),
+            keys: HashMap::new(),
+            scripts: ScriptConfig::default(),
+            safety: SafetyConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let Some(path) = config_dir().map(|dir| dir.join("config.toml")) else {
+            return Self::default();
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(text) => match toml::from_str(&text) {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("error in {}: {e}", path.display());
+                    Self::default()
+                }
+            },
+
+            Err(_) => Self::default(), //no config file, that's fine
+        }
+    }
+}
@@ -0,0 +1,265 @@
+// `blatand daemon`: owns the bluetooth connection so it survives the gui being closed.
+// the gui and cli talk to it over the socket in ipc.rs
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::UnixListener;
+use tokio::sync::{mpsc, watch};
+
+use crate::backend;
+use crate::bt::{BtCommands, BtToGui, CmdData, HeadsetState};
+use crate::config::{Config, Preset};
+use crate::ipc::{self, read_message, write_message, Connection, Reply, Request};
+
+pub async fn run(config: Config) {
+    if Connection::connect().await.is_ok() {
+        return println!("daemon is already running");
+    }
+
+    let path = match ipc::socket_path() {
+        Ok(path) => path,
+        Err(e) => return println!("no place for the socket: {e}"),
+    };
+
+    let _ = std::fs::remove_file(&path); //left behind by a daemon that didn't exit cleanly
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => return println!("can't listen on {}: {e}", path.display()),
+    };
+
+    // nobody's looking at the gui channel here, clients get the same info from the state channel
+    let (gui_tx, mut gui_rx) = mpsc::channel(4);
+    tokio::spawn(async move { while gui_rx.recv().await.is_some() {} });
+
+    let backend = backend::spawn(&config, gui_tx);
+    println!("listening on {}", path.display());
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let conn = Connection::new(stream);
+                tokio::spawn(serve_client(conn, backend.tx.clone(), backend.state_rx.clone(), backend.events.clone(), config.presets.clone()));
+            }
+
+            Err(e) => println!("accept failed: {e}"),
+        }
+    }
+}
+
+pub async fn serve_client<S>(conn: Connection<S>, tx: mpsc::Sender<BtCommands>, state_rx: watch::Receiver<HeadsetState>, events: Option<mpsc::Sender<String>>, presets: Vec<Preset>)
+where
+    S: AsyncRead + AsyncWrite,
+{
+    if let Err(e) = handle_client(conn, tx, state_rx, events, presets).await {
+        println!("client: {e}");
+    }
+}
+
+async fn handle_client<S>(conn: Connection<S>, tx: mpsc::Sender<BtCommands>, mut state_rx: watch::Receiver<HeadsetState>, events: Option<mpsc::Sender<String>>, presets: Vec<Preset>) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let Connection { mut reader, mut writer } = conn;
+
+    let reply = match read_message(&mut reader).await? {
+        Some(Request::Hello { version }) if version == ipc::VERSION => Reply::Hello { version: ipc::VERSION },
+        Some(Request::Hello { version }) => Reply::Error {
+            message: format!("protocol version {version} not supported, the daemon speaks version {}", ipc::VERSION),
+        },
+        _ => Reply::Error { message: String::from("expected hello") },
+    };
+
+    let accepted = matches!(reply, Reply::Hello { .. });
+    write_message(&mut writer, &reply).await?;
+
+    if !accepted {
+        return Ok(());
+    }
+
+    let state = state_rx.borrow_and_update().clone();
+    write_message(&mut writer, &Reply::State(state)).await?;
+
+    let mut bt_alive = true;
+
+    loop {
+        tokio::select! {
+            request = read_message(&mut reader) => {
+                let Some(request) = request? else { return Ok(()) };
+                let reply = command(request, &tx, &state_rx, events.as_ref(), &presets).await;
+                write_message(&mut writer, &reply).await?;
+            }
+
+            changed = state_rx.changed(), if bt_alive => {
+                if changed.is_err() {
+                    bt_alive = false; //bt task is gone, commands will get an error from now on
+                    continue;
+                }
+
+                let state = state_rx.borrow_and_update().clone();
+                write_message(&mut writer, &Reply::State(state)).await?;
+            }
+        }
+    }
+}
+
+async fn command(request: Request, tx: &mpsc::Sender<BtCommands>, state_rx: &watch::Receiver<HeadsetState>, events: Option<&mpsc::Sender<String>>, presets: &[Preset]) -> Reply {
+    let command = match request {
+        Request::Hello { .. } => return Reply::Error { message: String::from("already said hello") },
+        Request::SetMode(data) => BtCommands::SetMode(data),
+        Request::SetAudioProfile { profile } => BtCommands::SetAudioProfile(profile),
+        Request::ApplyPreset { name } => match Preset::find(presets, &name) {
+            Some(preset) => BtCommands::SetMode(CmdData::from(preset)),
+            None => return Reply::Error { message: format!("no preset named \"{name}\"") },
+        },
+
+        // doesn't need the headset, scripts decide what to do with it
+        Request::Event { name } => return match events.map(|events| events.try_send(name)) {
+            Some(Ok(_)) => Reply::Done,
+            Some(Err(_)) => Reply::Error { message: String::from("queue full!") },
+            None => Reply::Error { message: String::from("scripts aren't running") },
+        },
+    };
+
+    if !matches!(state_rx.borrow().status, BtToGui::Ready) {
+        return Reply::Error { message: String::from("headset not connected") };
+    }
+
+    match tx.send(command).await {
+        Ok(_) => Reply::Done,
+        Err(_) => Reply::Error { message: String::from("bluetooth task is gone") },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use catcaller::Model;
+    use tokio::io::{duplex, DuplexStream};
+
+    use super::*;
+
+    struct Daemon {
+        state_tx: watch::Sender<HeadsetState>,
+        rx: mpsc::Receiver<BtCommands>,
+        events: mpsc::Receiver<String>,
+        conn: Connection<DuplexStream>,
+    }
+
+    fn ready() -> HeadsetState {
+        HeadsetState { status: BtToGui::Ready, headset: Some(Model::YowuSelkirk4), ..Default::default() }
+    }
+
+    // a client hooked up to handle_client, without saying hello yet
+    fn daemon(state: HeadsetState, scripts: bool) -> Daemon {
+        let (tx, rx) = mpsc::channel(4);
+        let (state_tx, state_rx) = watch::channel(state);
+        let (events_tx, events) = mpsc::channel(4);
+        let presets = vec![Preset { name: String::from("on air"), mode: 7, rgb: [255, 0, 0], settings: [63, 0] }];
+        let (client, server) = duplex(4096);
+
+        tokio::spawn(serve_client(Connection::new(server), tx, state_rx, scripts.then_some(events_tx), presets));
+        Daemon { state_tx, rx, events, conn: Connection::new(client) }
+    }
+
+    impl Daemon {
+        async fn send(&mut self, request: Request) -> Option<Reply> {
+            write_message(&mut self.conn.writer, &request).await.unwrap();
+            self.recv().await
+        }
+
+        async fn recv(&mut self) -> Option<Reply> {
+            read_message(&mut self.conn.reader).await.unwrap()
+        }
+
+        async fn hello(&mut self) -> HeadsetState {
+            assert!(matches!(self.send(Request::Hello { version: ipc::VERSION }).await, Some(Reply::Hello { version: ipc::VERSION })));
+
+            match self.recv().await {
+                Some(Reply::State(state)) => state,
+                reply => panic!("expected the state, got {reply:?}"),
+            }
+        }
+    }
+
+    fn error(reply: Option<Reply>) -> String {
+        match reply {
+            Some(Reply::Error { message }) => message,
+            reply => panic!("expected an error, got {reply:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn handshake() {
+        let mut daemon = daemon(ready(), false);
+        let state = daemon.hello().await;
+
+        assert!(matches!(state.status, BtToGui::Ready));
+        assert_eq!(state.headset, Some(Model::YowuSelkirk4));
+    }
+
+    #[tokio::test]
+    async fn version_mismatch() {
+        let mut daemon = daemon(ready(), false);
+
+        let message = error(daemon.send(Request::Hello { version: ipc::VERSION + 1 }).await);
+        assert!(message.contains(&format!("protocol version {}", ipc::VERSION + 1)), "{message}");
+        assert!(daemon.recv().await.is_none(), "the daemon hangs up");
+
+        let mut daemon = self::daemon(ready(), false);
+        assert_eq!(error(daemon.send(Request::SetAudioProfile { profile: 1 }).await), "expected hello");
+    }
+
+    #[tokio::test]
+    async fn commands() {
+        let mut daemon = daemon(ready(), true);
+        daemon.hello().await;
+
+        let data = CmdData { mode: 3, rgb: [0, 128, 255], settings: [40, 20] };
+        assert!(matches!(daemon.send(Request::SetMode(data)).await, Some(Reply::Done)));
+        assert!(matches!(daemon.rx.try_recv(), Ok(BtCommands::SetMode(sent)) if sent == data));
+
+        assert!(matches!(daemon.send(Request::SetAudioProfile { profile: 2 }).await, Some(Reply::Done)));
+        assert!(matches!(daemon.rx.try_recv(), Ok(BtCommands::SetAudioProfile(2))));
+
+        assert!(matches!(daemon.send(Request::ApplyPreset { name: String::from("ON AIR") }).await, Some(Reply::Done)));
+        assert!(matches!(daemon.rx.try_recv(), Ok(BtCommands::SetMode(CmdData { mode: 7, rgb: [255, 0, 0], settings: [63, 0] }))));
+
+        assert_eq!(error(daemon.send(Request::ApplyPreset { name: String::from("off air") }).await), "no preset named \"off air\"");
+        assert_eq!(error(daemon.send(Request::Hello { version: ipc::VERSION }).await), "already said hello");
+
+        assert!(matches!(daemon.send(Request::Event { name: String::from("meeting") }).await, Some(Reply::Done)));
+        assert_eq!(daemon.events.try_recv().as_deref(), Ok("meeting"));
+
+        assert!(daemon.rx.try_recv().is_err(), "errors don't send anything");
+    }
+
+    #[tokio::test]
+    async fn commands_need_a_headset() {
+        let mut daemon = daemon(HeadsetState::default(), false);
+        daemon.hello().await;
+
+        assert_eq!(error(daemon.send(Request::SetMode(CmdData::default())).await), "headset not connected");
+        assert_eq!(error(daemon.send(Request::Event { name: String::from("meeting") }).await), "scripts aren't running");
+        assert!(daemon.rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn state_pushes() {
+        let mut daemon = daemon(HeadsetState::default(), false);
+        assert!(matches!(daemon.hello().await.status, BtToGui::Init));
+
+        daemon.state_tx.send_replace(HeadsetState { battery: Some(80), lighting: Some(CmdData { mode: 2, ..Default::default() }), ..ready() });
+
+        let Some(Reply::State(state)) = daemon.recv().await else { panic!("expected a state push") };
+        assert!(matches!(state.status, BtToGui::Ready));
+        assert_eq!(state.battery, Some(80));
+        assert_eq!(state.lighting.map(|lighting| lighting.mode), Some(2));
+
+        // the bt task going away isn't the client's problem, commands just get an error
+        let Daemon { state_tx, rx, mut conn, .. } = daemon;
+        drop((state_tx, rx));
+        write_message(&mut conn.writer, &Request::SetAudioProfile { profile: 1 }).await.unwrap();
+        assert_eq!(error(read_message(&mut conn.reader).await.unwrap()), "bluetooth task is gone");
+    }
+}
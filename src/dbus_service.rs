@@ -0,0 +1,324 @@
+// session bus service, so desktop scripts and applets can talk to the headset
+//   busctl --user call io.github.catcaller.Headset /io/github/catcaller/Headset io.github.catcaller.Headset SetColor yyy 255 0 0
+
+use tokio::sync::{mpsc, watch};
+use zbus::{dbus_interface, fdo, ConnectionBuilder};
+
+use crate::bt::{BtCommands, BtToGui, CmdData, HeadsetState};
+use crate::config::Preset;
+
+pub const NAME: &str = "io.github.catcaller.Headset";
+pub const PATH: &str = "/io/github/catcaller/Headset";
+
+struct Headset {
+    tx: mpsc::Sender<BtCommands>,
+    presets: Vec<Preset>,
+    state: HeadsetState,
+}
+
+impl Headset {
+    async fn send(&self, command: BtCommands) -> fdo::Result<()> {
+        if !matches!(self.state.status, BtToGui::Ready) {
+            return Err(fdo::Error::Failed(String::from("headset not connected")));
+        }
+
+        self.tx.send(command).await.map_err(|_| fdo::Error::Failed(String::from("bluetooth task is gone")))
+    }
+
+    // everything sent so far, merged, 0 = don't know
+    fn lighting(&self) -> CmdData {
+        self.state.lighting.unwrap_or_default()
+    }
+}
+
+#[dbus_interface(name = "io.github.catcaller.Headset")]
+impl Headset {
+    async fn set_color(&self, r: u8, g: u8, b: u8) -> fdo::Result<()> {
+        self.send(BtCommands::SetMode(CmdData { rgb: [r, g, b], ..Default::default() })).await
+    }
+
+    async fn set_mode(&self, mode: u8) -> fdo::Result<()> {
+        if !(1 ..= 8).contains(&mode) {
+            return Err(fdo::Error::InvalidArgs(String::from("mode must be 1-8")));
+        }
+
+        self.send(BtCommands::SetMode(CmdData { mode, ..Default::default() })).await
+    }
+
+    async fn set_settings(&self, first: u8, second: u8) -> fdo::Result<()> {
+        if first > 63 || second > 63 {
+            return Err(fdo::Error::InvalidArgs(String::from("settings must be 0-63")));
+        }
+
+        self.send(BtCommands::SetMode(CmdData { settings: [first, second], ..Default::default() })).await
+    }
+
+    async fn set_audio_profile(&self, profile: u8) -> fdo::Result<()> {
+        if profile > 3 {
+            return Err(fdo::Error::InvalidArgs(String::from("audio profile must be 0-3")));
+        }
+
+        self.send(BtCommands::SetAudioProfile(profile)).await
+    }
+
+    async fn apply_preset(&self, name: String) -> fdo::Result<()> {
+        let preset = Preset::find(&self.presets, &name)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("no preset named \"{name}\"")))?;
+
+        self.send(BtCommands::SetMode(CmdData::from(preset))).await
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> String {
+        self.state.status.name().to_string()
+    }
+
+    #[dbus_interface(property)]
+    fn connected(&self) -> bool {
+        matches!(self.state.status, BtToGui::Ready)
+    }
+
+    #[dbus_interface(property)]
+    fn battery(&self) -> i32 {
+        self.state.battery.map_or(-1, i32::from)
+    }
+
+    #[dbus_interface(property)]
+    fn color(&self) -> (u8, u8, u8) {
+        let [r, g, b] = self.lighting().rgb;
+        (r, g, b)
+    }
+
+    #[dbus_interface(property)]
+    fn mode(&self) -> u8 {
+        self.lighting().mode
+    }
+
+    #[dbus_interface(property)]
+    fn settings(&self) -> (u8, u8) {
+        let [first, second] = self.lighting().settings;
+        (first, second)
+    }
+
+    #[dbus_interface(property)]
+    fn audio_profile(&self) -> i32 {
+        self.state.audio_profile.map_or(-1, i32::from)
+    }
+}
+
+pub async fn serve(presets: Vec<Preset>, tx: mpsc::Sender<BtCommands>, state_rx: watch::Receiver<HeadsetState>) {
+    let result = match ConnectionBuilder::session() {
+        Ok(bus) => run(bus, presets, tx, state_rx).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        println!("dbus: {e}");
+    }
+}
+
+async fn run(bus: ConnectionBuilder<'_>, presets: Vec<Preset>, tx: mpsc::Sender<BtCommands>, mut state_rx: watch::Receiver<HeadsetState>) -> zbus::Result<()> {
+    let state = state_rx.borrow_and_update().clone();
+
+    let connection = bus
+        .name(NAME)?
+        .serve_at(PATH, Headset { tx, presets, state })?
+        .build()
+        .await?;
+
+    let iface_ref = connection.object_server().interface::<_, Headset>(PATH).await?;
+
+    // properties changed signals
+    while state_rx.changed().await.is_ok() {
+        let state = state_rx.borrow_and_update().clone();
+        let mut iface = iface_ref.get_mut().await;
+        let old = std::mem::replace(&mut iface.state, state);
+        let ctxt = iface_ref.signal_context();
+
+        if old.status.name() != iface.state.status.name() {
+            iface.status_changed(ctxt).await?;
+            iface.connected_changed(ctxt).await?;
+        }
+
+        if old.battery != iface.state.battery {
+            iface.battery_changed(ctxt).await?;
+        }
+
+        if old.lighting != iface.state.lighting {
+            iface.color_changed(ctxt).await?;
+            iface.mode_changed(ctxt).await?;
+            iface.settings_changed(ctxt).await?;
+        }
+
+        if old.audio_profile != iface.state.audio_profile {
+            iface.audio_profile_changed(ctxt).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// a dbus-daemon of our own for the tests, here and in tray.rs
+#[cfg(test)]
+pub mod test_bus {
+    use std::io::{BufRead, BufReader};
+    use std::path::PathBuf;
+    use std::process::{Child, Command, Stdio};
+
+    // a bus of our own, gone when dropped
+    pub struct Bus(Child, PathBuf);
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+            let _ = std::fs::remove_dir_all(&self.1);
+        }
+    }
+
+    // None without a dbus-daemon to run. name keeps tests running at the same time apart
+    pub fn private_bus(name: &str) -> Option<(Bus, String)> {
+        let dir = std::env::temp_dir().join(format!("catcaller-dbus-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).ok()?;
+
+        let child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .arg(format!("--address=unix:path={}", dir.join("bus").display()))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+
+        let mut bus = match child {
+            Ok(child) => Bus(child, dir),
+            Err(e) => {
+                println!("no dbus-daemon ({e}), skipping");
+                return None;
+            }
+        };
+
+        let mut address = String::new();
+        BufReader::new(bus.0.stdout.take()?).read_line(&mut address).ok()?;
+
+        Some((bus, address.trim().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use futures_util::StreamExt;
+    use tokio::time::{timeout, Duration};
+    use zbus::zvariant::{OwnedValue, Value};
+    use zbus::{CacheProperties, Connection, Proxy, ProxyBuilder, SignalStream};
+
+    use super::*;
+    use super::test_bus::private_bus;
+
+    async fn proxy(connection: &Connection) -> Proxy<'static> {
+        ProxyBuilder::new_bare(connection)
+            .destination(NAME).unwrap()
+            .path(PATH).unwrap()
+            .interface(NAME).unwrap()
+            .cache_properties(CacheProperties::No)
+            .build().await.unwrap()
+    }
+
+    async fn properties(connection: &Connection) -> Proxy<'static> {
+        ProxyBuilder::new_bare(connection)
+            .destination(NAME).unwrap()
+            .path(PATH).unwrap()
+            .interface("org.freedesktop.DBus.Properties").unwrap()
+            .build().await.unwrap()
+    }
+
+    // what the next PropertiesChanged signals say, until all of `names` came up
+    async fn properties_changed(signals: &mut SignalStream<'_>, names: &[&str]) -> HashMap<String, OwnedValue> {
+        let mut changed = HashMap::new();
+
+        while !names.iter().all(|name| changed.contains_key(*name)) {
+            let signal = timeout(Duration::from_secs(2), signals.next()).await
+                .unwrap_or_else(|_| panic!("no PropertiesChanged for all of {names:?}, only {changed:?}"))
+                .unwrap();
+
+            let (interface, properties, _): (String, HashMap<String, OwnedValue>, Vec<String>) = signal.body().unwrap();
+            assert_eq!(interface, NAME);
+            changed.extend(properties);
+        }
+
+        changed
+    }
+
+    #[tokio::test]
+    async fn service() {
+        let Some((_bus, address)) = private_bus("service") else { return };
+
+        let (tx, mut rx) = mpsc::channel(8);
+        let (state_tx, state_rx) = watch::channel(HeadsetState { status: BtToGui::Ready, ..Default::default() });
+        let presets = vec![Preset { name: String::from("On Air"), mode: 7, rgb: [255, 0, 0], settings: [63, 0] }];
+        tokio::spawn(run(ConnectionBuilder::address(address.as_str()).unwrap(), presets, tx, state_rx));
+
+        let client = ConnectionBuilder::address(address.as_str()).unwrap().build().await.unwrap();
+        let proxy = proxy(&client).await;
+
+        // the service needs a moment to get its name
+        let mut tries = 0;
+        while proxy.get_property::<String>("Status").await.is_err() {
+            tries += 1;
+            assert!(tries < 50, "the service never showed up");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let mut sent = Vec::new();
+
+        for (method, result) in [
+            ("SetMode", proxy.call_method("SetMode", &(2u8)).await),
+            ("SetColor", proxy.call_method("SetColor", &(0u8, 0u8, 255u8)).await),
+            ("SetSettings", proxy.call_method("SetSettings", &(10u8, 20u8)).await),
+            ("SetAudioProfile", proxy.call_method("SetAudioProfile", &(3u8)).await),
+            ("ApplyPreset", proxy.call_method("ApplyPreset", &("on air")).await),
+        ] {
+            assert!(result.is_ok(), "{method}: {result:?}");
+            sent.push(format!("{:?}", rx.try_recv().unwrap()));
+        }
+
+        assert_eq!(sent, [
+            "SetMode(CmdData { mode: 2, rgb: [0, 0, 0], settings: [0, 0] })",
+            "SetMode(CmdData { mode: 0, rgb: [0, 0, 255], settings: [0, 0] })",
+            "SetMode(CmdData { mode: 0, rgb: [0, 0, 0], settings: [10, 20] })",
+            "SetAudioProfile(3)",
+            "SetMode(CmdData { mode: 7, rgb: [255, 0, 0], settings: [63, 0] })",
+        ]);
+
+        for (method, args) in [("SetMode", (9u8,)), ("SetAudioProfile", (4u8,))] {
+            let error = proxy.call_method(method, &args).await.unwrap_err();
+            assert!(error.to_string().contains("InvalidArgs"), "{method}: {error}");
+        }
+
+        assert!(proxy.call_method("ApplyPreset", &("off air")).await.is_err());
+
+        let mut signals = properties(&client).await.receive_signal("PropertiesChanged").await.unwrap();
+
+        // what bt_stuff reports after SetMode(2) and then SetColor
+        state_tx.send_modify(|state| {
+            state.lighting = Some(CmdData { mode: 2, rgb: [0, 0, 255], settings: [0, 0] });
+            state.battery = Some(80);
+        });
+
+        let changed = properties_changed(&mut signals, &["Battery", "Color", "Mode", "Settings"]).await;
+        assert_eq!(*changed["Battery"], Value::from(80i32));
+        assert_eq!(*changed["Color"], Value::from((0u8, 0u8, 255u8)));
+        assert_eq!(*changed["Mode"], Value::from(2u8));
+        assert_eq!(*changed["Settings"], Value::from((0u8, 0u8)));
+        assert_eq!(proxy.get_property::<i32>("AudioProfile").await.unwrap(), -1);
+
+        state_tx.send_modify(|state| state.status = BtToGui::Found(catcaller::Model::YowuSelkirk4));
+
+        let changed = properties_changed(&mut signals, &["Status", "Connected"]).await;
+        assert_eq!(*changed["Status"], Value::from("found"));
+        assert_eq!(*changed["Connected"], Value::from(false));
+
+        assert!(!proxy.get_property::<bool>("Connected").await.unwrap());
+        assert!(proxy.call_method("SetMode", &(2u8)).await.unwrap_err().to_string().contains("not connected"));
+    }
+}
@@ -0,0 +1,322 @@
+// Art-Net and sACN (E1.31) input. the headset is patched like a 6 channel fixture:
+// R, G, B, mode, setting 1, setting 2
+
+use std::error::Error;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, Duration};
+
+use crate::bt::{BtCommands, CmdData};
+
+const ARTNET_PORT: u16 = 6454;
+const SACN_PORT: u16 = 5568;
+const FOOTPRINT: usize = 6;
+
+type Parser = fn(&[u8]) -> Option<(u16, &[u8])>; //packet -> universe, dmx data
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DmxConfig {
+    pub bind: Ipv4Addr,
+    pub artnet: bool,
+    pub sacn: bool,
+    pub universe: u16, //used as is for both protocols. art-net counts from 0, sACN from 1
+    pub address: u16,  //start address, 1-512
+    pub interval_ms: u64, //max rate we send to the headset at
+}
+
+impl Default for DmxConfig {
+    fn default() -> Self {
+        Self {
+            bind: Ipv4Addr::UNSPECIFIED,
+            artnet: true,
+            sacn: true,
+            universe: 1,
+            address: 1,
+            interval_ms: 40,
+        }
+    }
+}
+
+pub async fn listen(config: DmxConfig, tx: mpsc::Sender<BtCommands>) {
+    let (frame_tx, frame_rx) = watch::channel(None);
+    let frame_tx = Arc::new(frame_tx);
+
+    if config.artnet {
+        match UdpSocket::bind((config.bind, ARTNET_PORT)).await {
+            Ok(socket) => { tokio::spawn(receive(socket, config.clone(), frame_tx.clone(), parse_artnet)); }
+            Err(e) => println!("dmx: can't listen for art-net: {e}"),
+        }
+    }
+
+    if config.sacn {
+        match sacn_socket(&config).await {
+            Ok(socket) => { tokio::spawn(receive(socket, config.clone(), frame_tx.clone(), parse_sacn)); }
+            Err(e) => println!("dmx: can't listen for sACN: {e}"),
+        }
+    }
+
+    drop(frame_tx);
+    forward(frame_rx, tx, Duration::from_millis(config.interval_ms)).await;
+}
+
+async fn sacn_socket(config: &DmxConfig) -> Result<UdpSocket, Box<dyn Error + Send + Sync>> {
+    let socket = UdpSocket::bind((config.bind, SACN_PORT)).await?;
+    let [hi, lo] = config.universe.to_be_bytes();
+    socket.join_multicast_v4(Ipv4Addr::new(239, 255, hi, lo), config.bind)?; //unicast works regardless
+
+    Ok(socket)
+}
+
+async fn receive(
+    socket: UdpSocket,
+    config: DmxConfig,
+    frame_tx: Arc<watch::Sender<Option<CmdData>>>,
+    parse: Parser,
+) {
+    let mut buf = [0; 1024];
+
+    loop {
+        let len = match socket.recv_from(&mut buf).await {
+            Ok((len, _)) => len,
+            Err(e) => return println!("dmx: {e}"),
+        };
+
+        if let Some((universe, dmx)) = parse(&buf[.. len]) {
+            if universe == config.universe {
+                if let Some(data) = footprint(dmx, config.address) {
+                    frame_tx.send_if_modified(|frame| frame.replace(data) != Some(data));
+                }
+            }
+        }
+    }
+}
+
+// only ever sends the latest frame, anything in between gets dropped
+async fn forward(mut frame_rx: watch::Receiver<Option<CmdData>>, tx: mpsc::Sender<BtCommands>, interval: Duration) {
+    while frame_rx.changed().await.is_ok() {
+        let frame = *frame_rx.borrow_and_update();
+
+        if let Some(data) = frame {
+            if tx.send(BtCommands::SetMode(data)).await.is_err() {
+                return;
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
+
+pub fn footprint(dmx: &[u8], address: u16) -> Option<CmdData> {
+    let start = (address as usize).checked_sub(1)?;
+    let slots = dmx.get(start .. start + FOOTPRINT)?;
+
+    Some(CmdData {
+        mode: (slots[3] / 25).min(8), //0-24: color only, then 25 wide ranges for the 8 modes
+        rgb: [slots[0], slots[1], slots[2]],
+        settings: [slots[4] >> 2, slots[5] >> 2], //0-63
+    })
+}
+
+// ArtDmx: id, opcode, protocol version, sequence, physical, universe, length, data
+pub fn parse_artnet(packet: &[u8]) -> Option<(u16, &[u8])> {
+    if packet.len() < 18 || &packet[0 .. 8] != b"Art-Net\0" {
+        return None;
+    }
+
+    if u16::from_le_bytes([packet[8], packet[9]]) != 0x5000 {
+        return None; //not ArtDmx
+    }
+
+    let universe = u16::from_le_bytes([packet[14], packet[15]]) & 0x7FFF;
+    let len = u16::from_be_bytes([packet[16], packet[17]]) as usize;
+
+    Some((universe, packet.get(18 .. 18 + len)?))
+}
+
+// E1.31 data packet: root layer, framing layer, DMP layer
+pub fn parse_sacn(packet: &[u8]) -> Option<(u16, &[u8])> {
+    if packet.len() < 126 || &packet[4 .. 16] != b"ASC-E1.17\0\0\0" {
+        return None;
+    }
+
+    let root_vector = u32::from_be_bytes(packet[18 .. 22].try_into().unwrap());
+    let framing_vector = u32::from_be_bytes(packet[40 .. 44].try_into().unwrap());
+
+    if root_vector != 0x04 || framing_vector != 0x02 || packet[117] != 0x02 {
+        return None;
+    }
+
+    let options = packet[112];
+    if options & 0x40 != 0 {
+        return None; //preview data, not meant for output
+    }
+
+    let universe = u16::from_be_bytes([packet[113], packet[114]]);
+    let count = u16::from_be_bytes([packet[123], packet[124]]) as usize;
+
+    if packet[125] != 0 {
+        return None; //not a DMX512 null start code
+    }
+
+    Some((universe, packet.get(126 .. 125 + count)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // headers of captured packets, universe 1 with all 512 slots. what follows is the dmx data
+    const ARTDMX: &str = "4172742d4e6574000050000e5f00010002 00";
+    const SACN: &str = "
+        001000004153432d45312e3137000000726e000000045cd1b7a0e2f34c19a8e1
+        d05c2b7a9e317258000000027341434e56696577000000000000000000000000
+        0000000000000000000000000000000000000000000000000000000000000000
+        0000000000000000000000006400002a000001720b02a100000001020100";
+
+    // R, G, B, mode (flash), brightness, speed at address 1
+    const SLOTS: [u8; 6] = [255, 128, 0, 60, 255, 100];
+    const PATCHED: CmdData = CmdData { mode: 2, rgb: [255, 128, 0], settings: [63, 25] };
+
+    fn capture(header: &str) -> Vec<u8> {
+        let hex: String = header.split_whitespace().collect();
+        let mut packet: Vec<u8> = (0 .. hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i .. i + 2], 16).unwrap()).collect();
+
+        let mut dmx = [0; 512];
+        dmx[.. 6].copy_from_slice(&SLOTS);
+        packet.extend(dmx);
+        packet
+    }
+
+    #[test]
+    fn artnet() {
+        let packet = capture(ARTDMX);
+        let (universe, dmx) = parse_artnet(&packet).unwrap();
+
+        assert_eq!((universe, dmx.len()), (1, 512));
+        assert_eq!(footprint(dmx, 1), Some(PATCHED));
+    }
+
+    #[test]
+    fn artnet_not_dmx() {
+        let mut poll = capture(ARTDMX);
+        poll[8 .. 10].copy_from_slice(&0x2000u16.to_le_bytes()); //ArtPoll
+        assert_eq!(parse_artnet(&poll), None);
+
+        let mut other = capture(ARTDMX);
+        other[0 .. 8].copy_from_slice(b"Art-Nyt\0");
+        assert_eq!(parse_artnet(&other), None);
+    }
+
+    #[test]
+    fn artnet_truncated() {
+        let packet = capture(ARTDMX);
+
+        assert_eq!(parse_artnet(&packet[.. 17]), None);
+        assert_eq!(parse_artnet(&packet[.. 400]), None, "shorter than its length field");
+    }
+
+    #[test]
+    fn artnet_net_and_subnet() {
+        let mut packet = capture(ARTDMX);
+        packet[14 .. 16].copy_from_slice(&[0x23, 0x01]); //net 1, sub-net 2, universe 3
+        assert_eq!(parse_artnet(&packet).unwrap().0, 0x0123);
+    }
+
+    #[test]
+    fn sacn() {
+        let packet = capture(SACN);
+        let (universe, dmx) = parse_sacn(&packet).unwrap();
+
+        assert_eq!((universe, dmx.len()), (1, 512));
+        assert_eq!(footprint(dmx, 1), Some(PATCHED));
+    }
+
+    #[test]
+    fn sacn_preview() {
+        let mut packet = capture(SACN);
+        packet[112] |= 0x40;
+        assert_eq!(parse_sacn(&packet), None);
+    }
+
+    #[test]
+    fn sacn_start_code() {
+        let mut packet = capture(SACN);
+        packet[125] = 0xCC; //RDM
+        assert_eq!(parse_sacn(&packet), None);
+    }
+
+    #[test]
+    fn sacn_not_data() {
+        let mut packet = capture(SACN);
+        packet[40 .. 44].copy_from_slice(&1u32.to_be_bytes()); //a sync packet's framing vector
+        assert_eq!(parse_sacn(&packet), None);
+    }
+
+    #[test]
+    fn sacn_truncated() {
+        let packet = capture(SACN);
+
+        assert_eq!(parse_sacn(&packet[.. 125]), None);
+        assert_eq!(parse_sacn(&packet[.. 300]), None, "shorter than its property count");
+    }
+
+    #[test]
+    fn footprint_at_the_end() {
+        let mut dmx = [0; 512];
+        dmx[506 ..].copy_from_slice(&SLOTS);
+
+        assert_eq!(footprint(&dmx, 507), Some(PATCHED));
+        assert_eq!(footprint(&dmx, 508), None, "would run past slot 512");
+        assert_eq!(footprint(&dmx, 0), None);
+        assert_eq!(footprint(&dmx[.. 6], 1), Some(CmdData::default()), "short universes are fine as long as it fits");
+    }
+
+    #[test]
+    fn mode_bands() {
+        let mode = |value: u8| footprint(&[0, 0, 0, value, 0, 0], 1).unwrap().mode;
+
+        for (values, expected) in [(0 ..= 24, 0), (25 ..= 49, 1), (50 ..= 74, 2), (200 ..= 224, 8), (225 ..= 255, 8)] {
+            for value in values {
+                assert_eq!(mode(value), expected, "{value}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn loopback() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let (frame_tx, frame_rx) = watch::channel(None);
+        let (tx, mut rx) = mpsc::channel(4);
+
+        tokio::spawn(receive(socket, DmxConfig::default(), Arc::new(frame_tx), parse_artnet));
+        tokio::spawn(forward(frame_rx, tx, Duration::from_millis(10)));
+
+        let console = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut packet = capture(ARTDMX);
+        let wait = Duration::from_secs(2);
+
+        console.send_to(&packet, addr).await.unwrap();
+        assert!(matches!(tokio::time::timeout(wait, rx.recv()).await, Ok(Some(BtCommands::SetMode(PATCHED)))));
+
+        // another universe, then the same frame again, neither gets sent
+        packet[14] = 2;
+        console.send_to(&packet, addr).await.unwrap();
+        packet[14] = 1;
+        console.send_to(&packet, addr).await.unwrap();
+
+        packet[18] = 0;
+        console.send_to(&packet, addr).await.unwrap();
+
+        match tokio::time::timeout(wait, rx.recv()).await {
+            Ok(Some(BtCommands::SetMode(data))) => assert_eq!(data.rgb, [0, 128, 0]),
+            other => panic!("{other:?}"),
+        }
+    }
+}
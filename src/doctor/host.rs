@@ -0,0 +1,185 @@
+// the real machine: files as they are, bluez over the system bus
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use zbus::fdo::{self, ObjectManagerProxy};
+use zbus::zvariant::OwnedValue;
+
+use super::{Adapter, Bluez, BusError, Device, System};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Host;
+
+impl System for Host {
+    fn read(&self, path: &str) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
+
+    fn list(&self, dir: &str) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir).into_iter().flatten()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    async fn bluez(&self) -> Result<Bluez, BusError> {
+        // a bus that never answers shouldn't leave the doctor hanging too
+        match tokio::time::timeout(TIMEOUT, bluez()).await {
+            Ok(result) => result,
+            Err(_) => Err(BusError::Other(format!("no answer in {} seconds", TIMEOUT.as_secs()))),
+        }
+    }
+}
+
+async fn bluez() -> Result<Bluez, BusError> {
+    let conn = zbus::Connection::system().await.map_err(|e| BusError::NoBus(e.to_string()))?;
+    read(&conn).await
+}
+
+async fn read(conn: &zbus::Connection) -> Result<Bluez, BusError> {
+    let objects = async {
+        ObjectManagerProxy::builder(conn).destination("org.bluez")?.path("/")?.build().await?
+            .get_managed_objects().await
+    };
+
+    let mut bluez = Bluez::default();
+
+    for (path, interfaces) in objects.await.map_err(bus_error)? {
+        for (interface, props) in interfaces {
+            match interface.as_str() {
+                "org.bluez.Adapter1" => bluez.adapters.push(Adapter {
+                    name: path.rsplit('/').next().unwrap_or_default().to_string(),
+                    address: string(&props, "Address").unwrap_or_default(),
+                    powered: flag(&props, "Powered"),
+                }),
+
+                "org.bluez.Device1" => bluez.devices.push(Device {
+                    address: string(&props, "Address").unwrap_or_default(),
+                    name: string(&props, "Name"),
+                    paired: flag(&props, "Paired") || flag(&props, "Bonded"),
+                    connected: flag(&props, "Connected"),
+                    rssi: props.get("RSSI").and_then(|value| value.downcast_ref::<i16>()).copied(),
+                }),
+
+                _ => (),
+            }
+        }
+    }
+
+    bluez.adapters.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(bluez)
+}
+
+fn bus_error(e: fdo::Error) -> BusError {
+    match e {
+        fdo::Error::ServiceUnknown(_) | fdo::Error::NameHasNoOwner(_) => BusError::NotRunning,
+        fdo::Error::AccessDenied(e) => BusError::AccessDenied(e.split(',').next().unwrap_or_default().to_string()), //the rest lists every rule it matched
+        fdo::Error::ZBus(zbus::Error::MethodError(name, _, _)) if name.as_str() == "org.freedesktop.DBus.Error.ServiceUnknown" => BusError::NotRunning,
+        e => BusError::Other(e.to_string()),
+    }
+}
+
+fn string(props: &HashMap<String, OwnedValue>, name: &str) -> Option<String> {
+    props.get(name)?.downcast_ref::<str>().map(str::to_string)
+}
+
+fn flag(props: &HashMap<String, OwnedValue>, name: &str) -> bool {
+    props.get(name).and_then(|value| value.downcast_ref::<bool>()).copied().unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::zvariant::{OwnedObjectPath, Value};
+    use zbus::{dbus_interface, ConnectionBuilder};
+
+    use super::*;
+    use crate::dbus_service::test_bus::private_bus;
+
+    type Objects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+    // bluetoothd as far as the doctor sees it
+    struct StandIn {
+        objects: Objects,
+        denied: bool,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.DBus.ObjectManager")]
+    impl StandIn {
+        fn get_managed_objects(&self) -> fdo::Result<Objects> {
+            match self.denied {
+                // what the system bus says to a user its policy doesn't let talk to bluez
+                true => Err(fdo::Error::AccessDenied(String::from("Rejected send message, 2 matched rules; type=\"method_call\""))),
+                false => Ok(self.objects.clone()),
+            }
+        }
+    }
+
+    fn object(path: &str, interface: &str, props: Vec<(&str, Value<'_>)>) -> (OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>) {
+        let props = props.into_iter().map(|(name, value)| (name.to_string(), OwnedValue::from(value))).collect();
+        let interfaces = HashMap::from([(interface.to_string(), props), (String::from("org.freedesktop.DBus.Properties"), HashMap::new())]);
+
+        (OwnedObjectPath::try_from(path).unwrap(), interfaces)
+    }
+
+    fn objects(powered: bool) -> Objects {
+        Objects::from([
+            object("/org/bluez/hci1", "org.bluez.Adapter1", vec![("Address", Value::from("00:1A:7D:DA:71:14")), ("Powered", Value::from(false))]),
+            object("/org/bluez/hci0", "org.bluez.Adapter1", vec![("Address", Value::from("5C:F3:70:9E:21:0B")), ("Powered", Value::from(powered))]),
+            object("/org/bluez/hci0/dev_F4_4E_FD_00_11_22", "org.bluez.Device1", vec![
+                ("Address", Value::from("F4:4E:FD:00:11:22")),
+                ("Name", Value::from("YOWU-SELKIRK-4")),
+                ("Paired", Value::from(false)),
+                ("Bonded", Value::from(true)),
+                ("Connected", Value::from(true)),
+                ("RSSI", Value::from(-58i16)),
+            ]),
+            object("/org/bluez/hci0/dev_00_11_22_33_44_55", "org.bluez.Device1", vec![
+                ("Address", Value::from("00:11:22:33:44:55")),
+                ("Paired", Value::from(true)),
+            ]),
+        ])
+    }
+
+    fn summary(result: Result<Bluez, BusError>) -> String {
+        match result {
+            Ok(bluez) => {
+                let mut devices: Vec<_> = bluez.devices.iter().map(|device| format!("{device:?}")).collect();
+                devices.sort();
+                format!("{:?}\n{}", bluez.adapters, devices.join("\n"))
+            }
+
+            Err(e) => format!("{e:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn bluez_on_the_bus() {
+        let Some((_bus, address)) = private_bus("bluez") else { return };
+        let client = ConnectionBuilder::address(address.as_str()).unwrap().build().await.unwrap();
+
+        assert_eq!(summary(read(&client).await), "NotRunning");
+
+        let service = ConnectionBuilder::address(address.as_str()).unwrap()
+            .serve_at("/", StandIn { objects: objects(true), denied: false }).unwrap()
+            .name("org.bluez").unwrap()
+            .build().await.unwrap();
+
+        assert_eq!(summary(read(&client).await), [
+            r#"[Adapter { name: "hci0", address: "5C:F3:70:9E:21:0B", powered: true }, Adapter { name: "hci1", address: "00:1A:7D:DA:71:14", powered: false }]"#,
+            r#"Device { address: "00:11:22:33:44:55", name: None, paired: true, connected: false, rssi: None }"#,
+            r#"Device { address: "F4:4E:FD:00:11:22", name: Some("YOWU-SELKIRK-4"), paired: true, connected: true, rssi: Some(-58) }"#,
+        ].join("\n"));
+
+        let stand_in = service.object_server().interface::<_, StandIn>("/").await.unwrap();
+
+        stand_in.get_mut().await.objects = objects(false);
+        assert!(summary(read(&client).await).starts_with(r#"[Adapter { name: "hci0", address: "5C:F3:70:9E:21:0B", powered: false }"#));
+
+        stand_in.get_mut().await.denied = true;
+        assert_eq!(summary(read(&client).await), r#"AccessDenied("Rejected send message")"#);
+    }
+}
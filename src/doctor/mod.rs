@@ -0,0 +1,292 @@
+// bluetooth setup checks, for when the headset never shows up ("Searching for BT adapter..."
+// forever). looks at whether bluetoothd answers on the system bus, we're allowed to talk to it,
+// there's an adapter that's powered and not blocked by rfkill, and bluez knows the headset.
+// linux only for now. everything is read through System, so the tests can run the checks against
+// made up machines (golden/doctor_*.txt)
+//   blatand doctor
+
+#[cfg(target_os = "linux")]
+mod host;
+mod page;
+
+use std::fmt::Write as _;
+
+use catcaller::Model;
+
+pub use page::Page;
+
+// what the checks look at
+pub trait System {
+    // a file under /sys, /proc or /etc
+    fn read(&self, path: &str) -> Option<String>;
+    // names of the entries in a directory, sorted
+    fn list(&self, dir: &str) -> Vec<String>;
+    // adapters and devices from bluez's object manager on the system bus
+    async fn bluez(&self) -> Result<Bluez, BusError>;
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Bluez {
+    pub adapters: Vec<Adapter>,
+    pub devices: Vec<Device>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Adapter {
+    pub name: String, //hci0
+    pub address: String,
+    pub powered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub address: String,
+    pub name: Option<String>, //what it advertises itself as
+    pub paired: bool, //or bonded
+    pub connected: bool,
+    pub rssi: Option<i16>, //only while it's in range and being scanned for
+}
+
+#[derive(Debug, Clone)]
+pub enum BusError {
+    NoBus(String),
+    NotRunning, //nobody owns org.bluez
+    AccessDenied(String),
+    Other(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    Warn,
+    Fail,
+    Skip,
+}
+
+impl Outcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Warn => "warn",
+            Outcome::Fail => "FAIL",
+            Outcome::Skip => "skip",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub outcome: Outcome,
+    pub text: String,
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    fn new(outcome: Outcome, text: impl Into<String>) -> Self {
+        Self { outcome, text: text.into(), fix: None }
+    }
+
+    fn fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+}
+
+pub async fn check(system: &impl System) -> Vec<Finding> {
+    let bluez = system.bluez().await;
+    let mut findings = vec![bluetoothd(&bluez), permissions(system, &bluez)];
+
+    let blocked = rfkill(system, &mut findings);
+    adapters(system, &bluez, blocked, &mut findings);
+    headsets(&bluez, &mut findings);
+
+    findings
+}
+
+// the machine this runs on
+pub async fn check_host() -> Vec<Finding> {
+    #[cfg(target_os = "linux")]
+    return check(&host::Host).await;
+
+    #[cfg(not(target_os = "linux"))]
+    vec![Finding::new(Outcome::Skip, "only linux setups can be checked for now")]
+}
+
+// blatand doctor, returns the exit code
+pub async fn run_cli() -> i32 {
+    let findings = check_host().await;
+    print!("{}", format(&findings));
+
+    match findings.iter().any(|finding| finding.outcome == Outcome::Fail) {
+        true => 1,
+        false => 0,
+    }
+}
+
+pub fn format(findings: &[Finding]) -> String {
+    let mut text = String::new();
+
+    for finding in findings {
+        writeln!(text, "{:<5} {}", finding.outcome.label(), finding.text).unwrap();
+
+        if let Some(fix) = &finding.fix {
+            writeln!(text, "      fix: {fix}").unwrap();
+        }
+    }
+
+    text
+}
+
+fn bluetoothd(bluez: &Result<Bluez, BusError>) -> Finding {
+    match bluez {
+        Ok(_) => Finding::new(Outcome::Ok, "bluetoothd is running"),
+
+        Err(BusError::NoBus(e)) => Finding::new(Outcome::Fail, format!("can't reach the system bus: {e}"))
+            .fix("bluetoothd is reached over the D-Bus system bus, check that dbus is running with `systemctl status dbus`"),
+
+        Err(BusError::NotRunning) => Finding::new(Outcome::Fail, "bluetoothd isn't running")
+            .fix("start it with `sudo systemctl enable --now bluetooth`, after installing bluez if it's missing"),
+
+        Err(BusError::AccessDenied(_)) => Finding::new(Outcome::Skip, "can't tell if bluetoothd is running, see below"),
+
+        Err(BusError::Other(e)) => Finding::new(Outcome::Fail, format!("bluetoothd doesn't answer: {e}"))
+            .fix("restart it with `sudo systemctl restart bluetooth`"),
+    }
+}
+
+fn permissions(system: &impl System, bluez: &Result<Bluez, BusError>) -> Finding {
+    match bluez {
+        Ok(_) => Finding::new(Outcome::Ok, "allowed to talk to bluetoothd"),
+
+        Err(BusError::AccessDenied(e)) => {
+            let finding = Finding::new(Outcome::Fail, format!("not allowed to talk to bluetoothd: {e}"));
+
+            match bluetooth_group(system) {
+                Some(false) => finding.fix("add yourself to the bluetooth group with `sudo usermod -aG bluetooth $USER`, then log out and back in"),
+                _ => finding.fix("check the policy in /etc/dbus-1/system.d/bluetooth.conf"),
+            }
+        }
+
+        Err(_) => Finding::new(Outcome::Skip, "permissions, needs bluetoothd"),
+    }
+}
+
+// whether this process is in the bluetooth group, None if there's no such group
+fn bluetooth_group(system: &impl System) -> Option<bool> {
+    let groups = system.read("/etc/group")?;
+    let gid = groups.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        (fields.next() == Some("bluetooth")).then(|| fields.nth(1)).flatten()
+    })?;
+
+    let status = system.read("/proc/self/status").unwrap_or_default();
+    let ours = status.lines()
+        .filter_map(|line| line.strip_prefix("Gid:").or_else(|| line.strip_prefix("Groups:")))
+        .flat_map(str::split_whitespace)
+        .any(|ours| ours == gid);
+
+    Some(ours)
+}
+
+// rfkill switches for bluetooth, returns whether any is blocking
+fn rfkill(system: &impl System, findings: &mut Vec<Finding>) -> bool {
+    let read = |entry: &str, file: &str| system.read(&format!("/sys/class/rfkill/{entry}/{file}")).unwrap_or_default().trim().to_string();
+    let mut blocked = false;
+
+    for entry in system.list("/sys/class/rfkill") {
+        if read(&entry, "type") != "bluetooth" {
+            continue;
+        }
+
+        let name = read(&entry, "name");
+
+        if read(&entry, "hard") == "1" {
+            blocked = true;
+            findings.push(Finding::new(Outcome::Fail, format!("{name} is turned off by a hardware switch"))
+                .fix("look for a wireless switch or key (or airplane mode), or a bluetooth option in the BIOS/UEFI settings"));
+        } else if read(&entry, "soft") == "1" {
+            blocked = true;
+            findings.push(Finding::new(Outcome::Fail, format!("{name} is blocked by rfkill"))
+                .fix("unblock it with `rfkill unblock bluetooth`"));
+        }
+    }
+
+    if !blocked {
+        findings.push(Finding::new(Outcome::Ok, "bluetooth isn't blocked by rfkill"));
+    }
+
+    blocked
+}
+
+fn adapters(system: &impl System, bluez: &Result<Bluez, BusError>, blocked: bool, findings: &mut Vec<Finding>) {
+    let kernel: Vec<String> = system.list("/sys/class/bluetooth").into_iter().filter(|name| name.starts_with("hci")).collect();
+
+    let Ok(bluez) = bluez else {
+        // the kernel's view is all there is
+        match kernel.is_empty() {
+            true => findings.push(no_adapter(blocked)),
+            false => findings.push(Finding::new(Outcome::Ok, format!("the kernel found {}", kernel.join(", ")))),
+        }
+
+        return;
+    };
+
+    if bluez.adapters.is_empty() && kernel.is_empty() {
+        return findings.push(no_adapter(blocked));
+    }
+
+    for adapter in &bluez.adapters {
+        let name = format!("adapter {} ({})", adapter.name, adapter.address);
+
+        findings.push(match (adapter.powered, blocked) {
+            (true, _) => Finding::new(Outcome::Ok, format!("{name} is on")),
+            (false, true) => Finding::new(Outcome::Fail, format!("{name} is off")).fix("unblock it first, see above"),
+            (false, false) => Finding::new(Outcome::Fail, format!("{name} is off"))
+                .fix("turn it on with `bluetoothctl power on`, and set AutoEnable=true in /etc/bluetooth/main.conf to have it on after booting"),
+        });
+    }
+
+    for name in kernel.iter().filter(|name| !bluez.adapters.iter().any(|adapter| &adapter.name == *name)) {
+        findings.push(Finding::new(Outcome::Warn, format!("the kernel found {name}, but bluetoothd doesn't list it"))
+            .fix("restart bluetoothd with `sudo systemctl restart bluetooth`"));
+    }
+}
+
+fn no_adapter(blocked: bool) -> Finding {
+    match blocked {
+        true => Finding::new(Outcome::Fail, "no bluetooth adapter").fix("unblock it first, see above"),
+        false => Finding::new(Outcome::Fail, "no bluetooth adapter")
+            .fix("plug one in, or look for driver and firmware errors with `sudo dmesg | grep -i bluetooth`"),
+    }
+}
+
+fn headsets(bluez: &Result<Bluez, BusError>, findings: &mut Vec<Finding>) {
+    let Ok(bluez) = bluez else {
+        return findings.push(Finding::new(Outcome::Skip, "headset, needs bluetoothd"));
+    };
+
+    if !bluez.adapters.iter().any(|adapter| adapter.powered) {
+        return findings.push(Finding::new(Outcome::Skip, "headset, needs an adapter that's on"));
+    }
+
+    let headsets: Vec<(Model, &Device)> = bluez.devices.iter()
+        .filter_map(|device| Some((Model::from_local_name(device.name.as_deref()?)?, device)))
+        .collect();
+
+    if headsets.is_empty() {
+        return findings.push(Finding::new(Outcome::Warn, "bluetoothd hasn't seen a headset")
+            .fix("turn the headset on and keep it close, `bluetoothctl scan on` should list it within a few seconds"));
+    }
+
+    for (model, device) in headsets {
+        let name = format!("{} ({})", model.name(), device.address);
+
+        findings.push(match device {
+            Device { connected: true, .. } => Finding::new(Outcome::Ok, format!("{name} is connected")),
+            Device { paired: true, .. } => Finding::new(Outcome::Ok, format!("{name} is paired")),
+            Device { rssi: Some(rssi), .. } => Finding::new(Outcome::Ok, format!("{name} is in range, {rssi} dBm")),
+            _ => Finding::new(Outcome::Warn, format!("{name} is known, but out of range"))
+                .fix("turn it on and bring it closer"),
+        });
+    }
+}
@@ -0,0 +1,93 @@
+// the gui's diagnostics window, runs the checks in the background when it's opened
+
+use std::sync::{Arc, Mutex};
+
+use egui::{Color32, RichText};
+
+use super::{check_host, Finding, Outcome};
+
+#[derive(Default)]
+pub struct Page {
+    open: bool,
+    findings: Arc<Mutex<Option<Vec<Finding>>>>, //None while checking
+}
+
+impl Page {
+    pub fn open(&mut self) {
+        self.open = true;
+        self.run();
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn run(&mut self) {
+        // a new slot, so a check that's still running from before can't overwrite this one
+        let findings = Arc::new(Mutex::new(None));
+        self.findings = findings.clone();
+
+        tokio::spawn(async move {
+            let result = check_host().await;
+            *findings.lock().unwrap() = Some(result);
+        });
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if !self.open {
+            return;
+        }
+
+        let mut open = self.open;
+        let mut again = false;
+
+        egui::Window::new("Bluetooth diagnostics")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let findings = self.findings.lock().unwrap();
+
+                let Some(findings) = findings.as_ref() else {
+                    ui.horizontal(|ui| {
+                        ui.label("Checking...");
+                        ui.spinner();
+                    });
+                    return;
+                };
+
+                for finding in findings {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label(RichText::new(finding.outcome.label()).monospace().color(color(finding.outcome)));
+                        ui.label(&finding.text);
+                    });
+
+                    if let Some(fix) = &finding.fix {
+                        ui.indent(&finding.text, |ui| ui.label(RichText::new(fix).weak()));
+                    }
+                }
+
+                ui.separator();
+                again = ui.button("Check again").clicked();
+            });
+
+        self.open = open;
+
+        if again {
+            self.run();
+        }
+    }
+}
+
+fn color(outcome: Outcome) -> Color32 {
+    match outcome {
+        Outcome::Ok => Color32::from_rgb(80, 180, 90),
+        Outcome::Warn => Color32::from_rgb(220, 170, 40),
+        Outcome::Fail => Color32::from_rgb(230, 60, 60),
+        Outcome::Skip => Color32::GRAY,
+    }
+}
@@ -0,0 +1,134 @@
+// when the gui paints: as soon as something changed, when egui asks for it (animations), and not at
+// all while nothing happens, so an idle window doesn't use any cpu. never more than one frame per
+// FRAME_TIME though
+
+use std::time::{Duration, Instant};
+
+use winit::event_loop::ControlFlow;
+
+pub const FRAME_TIME: Duration = Duration::from_millis(25); //at most 40 fps
+
+pub struct Frames {
+    last: Instant,
+    next: Option<Instant>, //None = sleep until the next event
+}
+
+impl Frames {
+    // the first frame is due right away
+    pub fn new(now: Instant) -> Self {
+        Self { last: now.checked_sub(FRAME_TIME).unwrap_or(now), next: Some(now) }
+    }
+
+    // input, a bt message or anything else that needs to be shown
+    pub fn changed(&mut self, now: Instant) {
+        self.next = Some(now);
+    }
+
+    pub fn due(&self, now: Instant) -> bool {
+        self.next.is_some_and(|t| t.max(self.last + FRAME_TIME) <= now)
+    }
+
+    // repaint_after is what egui returned for the frame, Duration::MAX when it doesn't need another
+    pub fn painted(&mut self, now: Instant, repaint_after: Duration) {
+        self.last = now;
+        self.next = now.checked_add(repaint_after);
+    }
+
+    pub fn control_flow(&self) -> ControlFlow {
+        match self.next {
+            Some(t) => ControlFlow::WaitUntil(t.max(self.last + FRAME_TIME)),
+            None => ControlFlow::Wait,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what the event loop did, on a made up clock
+    #[derive(Default)]
+    struct Run {
+        wakeups: usize,
+        paints: Vec<Duration>,
+        latencies: Vec<Duration>, //from each message to the frame showing it
+    }
+
+    // runs the event loop for `until` with messages arriving at the given times. every frame asks
+    // for the next one after `repaint_after`, like egui does while something animates
+    fn run(messages: &[u64], repaint_after: Duration, until: Duration) -> Run {
+        let start = Instant::now();
+        let mut frames = Frames::new(start);
+        let mut messages: Vec<_> = messages.iter().map(|&ms| Duration::from_millis(ms)).collect();
+        let mut waiting = Vec::new(); //messages not painted yet
+        let mut run = Run::default();
+        let mut now = Duration::ZERO;
+
+        loop {
+            if frames.due(start + now) {
+                frames.painted(start + now, repaint_after);
+                run.paints.push(now);
+                run.latencies.extend(waiting.drain(..).map(|arrived| now - arrived));
+            }
+
+            // sleep until what the control flow says or the next message, whichever comes first
+            let wake = match frames.control_flow() {
+                ControlFlow::WaitUntil(t) => Some(t - start),
+                _ => None,
+            };
+
+            now = match (wake, messages.first()) {
+                (Some(wake), Some(&message)) => wake.min(message),
+                (Some(wake), None) => wake,
+                (None, Some(&message)) => message,
+                (None, None) => return run,
+            };
+
+            if now > until {
+                return run;
+            }
+
+            run.wakeups += 1;
+
+            // everything queued by now gets handled in one go
+            while messages.first().is_some_and(|&message| message <= now) {
+                waiting.push(messages.remove(0));
+                frames.changed(start + now);
+            }
+        }
+    }
+
+    #[test]
+    fn idle_sleeps() {
+        let run = run(&[], Duration::MAX, Duration::from_secs(60));
+        assert_eq!(run.paints, [Duration::ZERO]);
+        assert_eq!(run.wakeups, 0);
+    }
+
+    #[test]
+    fn messages_show_within_a_frame() {
+        let run = run(&[7, 13, 50, 51, 52, 400, 1000], Duration::MAX, Duration::from_secs(60));
+
+        assert_eq!(run.latencies.len(), 7);
+        assert!(run.latencies.iter().all(|&latency| latency <= FRAME_TIME), "{:?}", run.latencies);
+        assert_eq!(run.paints.len(), 6, "the burst at 50 ms takes two frames: {:?}", run.paints);
+        assert_eq!(run.wakeups, 9, "one per message, and one each for the two frames that had to wait");
+    }
+
+    #[test]
+    fn animations_repaint_at_their_rate() {
+        let run = run(&[], Duration::from_millis(33), Duration::from_secs(1));
+        assert_eq!(run.paints.len(), 31);
+        assert!(run.paints.windows(2).all(|pair| pair[1] - pair[0] == Duration::from_millis(33)));
+    }
+
+    #[test]
+    fn never_faster_than_frame_time() {
+        let messages: Vec<u64> = (0 .. 1000).collect(); //one every millisecond
+        let run = run(&messages, Duration::ZERO, Duration::from_secs(1));
+
+        assert!(run.paints.windows(2).all(|pair| pair[1] - pair[0] >= FRAME_TIME));
+        assert_eq!(run.paints.len(), 41);
+        assert!(run.latencies.iter().all(|&latency| latency <= FRAME_TIME));
+    }
+}
@@ -1,122 +1,416 @@
-use std::{ffi::CString, ptr, str::from_utf8};
+use std::{collections::HashMap, error::Error, ffi::CString, ptr, time::{Duration, Instant}};
 
 use egui::Modifiers;
 use glutin::{prelude::{GlConfig, GlDisplay, NotCurrentGlContextSurfaceAccessor}, display::GetGlDisplay, surface::{SurfaceAttributesBuilder, Surface, WindowSurface, GlSurface}, context::PossiblyCurrentContext};
 use raw_window_handle::HasRawWindowHandle;
-use winit::{event::{Event, WindowEvent, ElementState, VirtualKeyCode}, event_loop::ControlFlow};
+use winit::{event::{Event, WindowEvent, ElementState, Ime, MouseScrollDelta, VirtualKeyCode}, event_loop::ControlFlow, window::{Window, WindowBuilder}};
 
 use self::egui_gfx::*;
+use self::soft::SoftRenderer;
+
+pub use self::egui_gfx::GlCallback;
 
 mod egui_gfx;
+mod soft;
+#[cfg(test)]
+mod offscreen;
+
+pub trait Renderer {
+    fn paint(&mut self, primitives: &[egui::ClippedPrimitive], textures_delta: &egui::TexturesDelta, window_size: (u32, u32), pixels_per_point: f32);
+    fn resize(&mut self, window_size: (u32, u32));
+    fn supports_callbacks(&self) -> bool; //whether GlCallback paint callbacks get drawn
+    fn stats(&self) -> FrameStats;
+}
+
+// work done by the last paint call. CATCALLER_FRAME_STATS=1 prints these once per second
+#[derive(Default, Clone, Copy)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    pub buffer_uploads: u32,
+    pub texture_uploads: u32,
+}
+
+#[derive(Default)]
+struct StatsLog {
+    frames: u32,
+    paint_time: Duration,
+    totals: FrameStats,
+    since: Option<Instant>,
+}
+
+impl StatsLog {
+    fn add(&mut self, paint_time: Duration, stats: FrameStats) {
+        let since = *self.since.get_or_insert_with(Instant::now);
+
+        self.frames += 1;
+        self.paint_time += paint_time;
+        self.totals.draw_calls += stats.draw_calls;
+        self.totals.buffer_uploads += stats.buffer_uploads;
+        self.totals.texture_uploads += stats.texture_uploads;
+
+        if since.elapsed() >= Duration::from_secs(1) {
+            let frames = self.frames as f32;
+
+            println!(
+                "{} frames, {:.2} ms painting, {:.1} draw calls, {:.1} buffer uploads, {:.1} texture uploads per frame",
+                self.frames,
+                self.paint_time.as_secs_f32() * 1000.0 / frames,
+                self.totals.draw_calls as f32 / frames,
+                self.totals.buffer_uploads as f32 / frames,
+                self.totals.texture_uploads as f32 / frames,
+            );
+
+            *self = Self::default();
+        }
+    }
+}
+
+pub struct EguiState {
+    pub ctx: egui::Context,
+    pub pos_in_points: Option<egui::Pos2>,
+    pub raw_input: egui::RawInput,
+    pub window_size: (u32, u32), //physical pixels
+    pub scale_factor: f32, //from winit
+    pub zoom: f32, //from the config, on top of the scale factor
+    pub composing: bool, //IME preedit in progress
+}
+
+impl EguiState {
+    pub fn pixels_per_point(&self) -> f32 {
+        self.scale_factor * self.zoom
+    }
+
+    pub fn to_points(&self, position: winit::dpi::PhysicalPosition<f64>) -> egui::Pos2 {
+        (egui::vec2(position.x as f32, position.y as f32) / self.pixels_per_point()).to_pos2()
+    }
+
+    pub fn screen_rect(&self) -> egui::Rect {
+        let size = egui::vec2(self.window_size.0 as f32, self.window_size.1 as f32) / self.pixels_per_point();
+        egui::Rect::from_min_size(egui::Pos2::ZERO, size)
+    }
+}
 
 pub struct GlutinState {
-    pub window: winit::window::Window,
     pub gl_ctx: PossiblyCurrentContext,
+    #[allow(dead_code)]
     pub gl_display: glutin::display::Display,
     pub gl_surface: Surface<WindowSurface>,
 }
 
-pub struct Graphics {
+pub struct GlRenderer {
     pub glutin_state: GlutinState,
+    pub painter: GlPainter,
+}
+
+pub struct Graphics {
+    pub renderer: Box<dyn Renderer>, //declared before the window so it gets dropped first
+    pub window: Window,
     pub egui_state: EguiState,
+    pub clipboard: Option<arboard::Clipboard>,
+    stats_log: Option<StatsLog>,
 }
 
 impl Graphics {
-    pub fn setup(el: &winit::event_loop::EventLoop<()>, window_size: (u32, u32)) -> Self {
-        let wb = winit::window::WindowBuilder::new()
+    // window_size is in logical pixels
+    pub fn setup<T>(el: &winit::event_loop::EventLoop<T>, window_size: (u32, u32), visible: bool) -> Self {
+        let wb = WindowBuilder::new()
         .with_inner_size(winit::dpi::LogicalSize::new(window_size.0, window_size.1))
-        .with_title("Yowu CatCaller");
+        .with_title("Yowu CatCaller")
+        .with_visible(visible);
+
+        // CATCALLER_RENDERER=software skips GL entirely
+        let force_software = std::env::var("CATCALLER_RENDERER").is_ok_and(|r| r == "software");
+
+        let mut window = None;
+
+        let gl_renderer = match force_software {
+            true => None,
+            false => GlRenderer::setup(el, wb.clone(), &mut window)
+                .map_err(|e| println!("can't use OpenGL ({e}), falling back to the software renderer"))
+                .ok(),
+        };
+
+        let window = window.unwrap_or_else(|| wb.build(el).expect("unable to create window"));
+        let window_size: (u32, u32) = window.inner_size().into();
+        let scale_factor = window.scale_factor() as f32;
+
+        let renderer: Box<dyn Renderer> = match gl_renderer {
+            Some(renderer) => Box::new(renderer),
+            None => Box::new(SoftRenderer::new(&window, window_size).expect("unable to set up the software renderer")),
+        };
+
+        Self {
+            renderer,
+            window,
+
+            egui_state: EguiState{
+                ctx: egui::Context::default(),
+                pos_in_points: None,
+                raw_input: egui::RawInput::default(),
+                window_size,
+                scale_factor,
+                zoom: 1.0,
+                composing: false,
+            },
+
+            clipboard: arboard::Clipboard::new()
+                .map_err(|e| println!("no clipboard: {e}"))
+                .ok(),
+
+            stats_log: std::env::var("CATCALLER_FRAME_STATS").is_ok_and(|s| s == "1").then(StatsLog::default),
+        }
+    }
+
+    pub fn begin_frame(&mut self, time: f64) {
+        let egui_state = &mut self.egui_state;
+
+        egui_state.raw_input.time = Some(time);
+        egui_state.raw_input.pixels_per_point = Some(egui_state.pixels_per_point());
+        egui_state.raw_input.screen_rect = Some(egui_state.screen_rect());
+
+        egui_state.ctx.begin_frame(egui_state.raw_input.take());
+    }
+
+    // returns how long until egui wants to be painted again
+    pub fn paint(&mut self) -> Duration {
+        let full_output = self.egui_state.ctx.end_frame();
+        let clipped_primitives = self.egui_state.ctx.tessellate(full_output.shapes); // create triangles to paint
+        self.handle_platform_output(full_output.platform_output);
+
+        let paint_start = Instant::now();
+        self.renderer.paint(&clipped_primitives, &full_output.textures_delta, self.egui_state.window_size, self.egui_state.pixels_per_point());
+
+        if let Some(stats_log) = &mut self.stats_log {
+            stats_log.add(paint_start.elapsed(), self.renderer.stats());
+        }
+
+        full_output.repaint_after
+    }
+
+    fn handle_platform_output(&mut self, output: egui::PlatformOutput) {
+        match translate_cursor_icon(output.cursor_icon) {
+            Some(icon) => {
+                self.window.set_cursor_visible(true);
+                self.window.set_cursor_icon(icon);
+            }
+
+            None => self.window.set_cursor_visible(false),
+        }
+
+        if !output.copied_text.is_empty() {
+            if let Some(clipboard) = &mut self.clipboard {
+                if let Err(e) = clipboard.set_text(output.copied_text) {
+                    println!("can't copy: {e}");
+                }
+            }
+        }
+
+        // IME candidate window goes next to the text cursor
+        self.window.set_ime_allowed(output.text_cursor_pos.is_some());
+
+        if let Some(pos) = output.text_cursor_pos {
+            let pos = pos.to_vec2() * self.egui_state.pixels_per_point();
+            self.window.set_ime_position(winit::dpi::PhysicalPosition::new(pos.x, pos.y));
+        }
+    }
+
+    pub fn resize(&mut self, window_size: (u32, u32)) {
+        self.egui_state.window_size = window_size;
+        self.renderer.resize(window_size);
+    }
 
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.egui_state.zoom = zoom.clamp(0.5, 4.0);
+    }
+}
+
+impl GlRenderer {
+    // the window is handed back through `window_out` whenever it got created, even if GL didn't work out
+    fn setup<T>(el: &winit::event_loop::EventLoop<T>, wb: WindowBuilder, window_out: &mut Option<Window>) -> Result<Self, Box<dyn Error>> {
         let (window, gl_config) = glutin_winit::DisplayBuilder::new()
         .with_window_builder(Some(wb))
-        .build(&el, <_>::default(), |configs| {
+        .build(el, <_>::default(), |configs| {
+            // prefer srgb capable configs, then multisampling. glutin errors out before handing us an empty list
             configs
-                .filter(|c| c.srgb_capable())
-                .max_by_key(|c| c.num_samples())
+                .max_by_key(|c| (c.srgb_capable(), c.num_samples()))
                 .unwrap()
-        }).unwrap();
+        })?;
 
-        let window = window.unwrap();
+        let window = window_out.insert(window.ok_or("no window was created")?);
+        let window_size: (u32, u32) = window.inner_size().into();
         let raw_window_handle = window.raw_window_handle();
         let gl_display = gl_config.display();
 
-        let context_attributes = glutin::context::ContextAttributesBuilder::new()
-            .with_profile(glutin::context::GlProfile::Core)
-            .with_context_api(glutin::context::ContextApi::OpenGl(Some(
-                glutin::context::Version::new(4, 5),
-            )))
-            .build(Some(raw_window_handle));
-
-        let (gl_surface, gl_ctx) = {
-            let attrs = SurfaceAttributesBuilder::<glutin::surface::WindowSurface>::new().build(
-                raw_window_handle,
-                std::num::NonZeroU32::new(window_size.0).unwrap(),
-                std::num::NonZeroU32::new(window_size.1).unwrap(),
-            );
+        let attrs = SurfaceAttributesBuilder::<glutin::surface::WindowSurface>::new().build(
+            raw_window_handle,
+            std::num::NonZeroU32::new(window_size.0).ok_or("window has no width")?,
+            std::num::NonZeroU32::new(window_size.1).ok_or("window has no height")?,
+        );
 
-            let surface = unsafe { match gl_display.create_window_surface(&gl_config, &attrs) {
-                Ok(o) => o,
-                Err(_) => todo!("omg"),
-            }};
+        let gl_surface = unsafe { gl_display.create_window_surface(&gl_config, &attrs)? };
 
-            let context = unsafe { match gl_display.create_context(&gl_config, &context_attributes) {
-                Ok(o) => o,
-                Err(_) => todo!("omg"),
-            }}.make_current(&surface).unwrap();
-            (surface, context)
-        };
+        // 4.5 core gets the DSA painter, 3.3 core and GLES 3 the bind-to-edit one
+        let apis = [
+            (glutin::context::ContextApi::OpenGl(Some(glutin::context::Version::new(4, 5))), glutin::context::GlProfile::Core),
+            (glutin::context::ContextApi::OpenGl(Some(glutin::context::Version::new(3, 3))), glutin::context::GlProfile::Core),
+            (glutin::context::ContextApi::Gles(Some(glutin::context::Version::new(3, 0))), glutin::context::GlProfile::Core),
+        ];
 
-        gl::load_with(|symbol| (gl_display.get_proc_address(&CString::new(symbol).unwrap()) as _));
+        let mut last_error = None;
+        let mut gl_ctx = None;
 
-        unsafe {
-            gl::Enable(gl::BLEND);
-            gl::Disable(gl::DEPTH_TEST);
-            gl::Disable(gl::STENCIL_TEST);
-            gl::Disable(gl::CULL_FACE);
+        for (api, profile) in apis {
+            let context_attributes = glutin::context::ContextAttributesBuilder::new()
+                .with_profile(profile)
+                .with_context_api(api)
+                .build(Some(raw_window_handle));
+
+            match unsafe { gl_display.create_context(&gl_config, &context_attributes) } {
+                Ok(ctx) => {
+                    gl_ctx = Some(ctx);
+                    break;
+                }
+
+                Err(e) => last_error = Some(e),
+            }
         }
 
-        let (vao_e, vbo_e) = setup_vertex_arrays_egui();
-        let vert_e = include_str!("shader_egui.vert");
-        let frag_e = include_str!("shader_egui.frag");
+        let gl_ctx = match (gl_ctx, last_error) {
+            (Some(ctx), _) => ctx.make_current(&gl_surface)?,
+            (None, Some(e)) => return Err(e.into()),
+            (None, None) => unreachable!(),
+        };
 
-        Self {
+        gl::load_with(|symbol| gl_display.get_proc_address(&CString::new(symbol).unwrap()) as _);
+
+        let version = GlVersion::query();
+        println!("using {version}");
+
+        let painter = gl_painter(&version)?;
+
+        Ok(Self {
             glutin_state: GlutinState {
-                window: window,
-                gl_ctx: gl_ctx,
-                gl_display: gl_display,
-                gl_surface: gl_surface,
+                gl_ctx,
+                gl_display,
+                gl_surface,
             },
 
-            egui_state: EguiState{
-                // windowed_context: windowed_context,
-                ctx: egui::Context::default(),
-                pos_in_points: None,
-                raw_input: egui::RawInput::default(),
-                vao: vao_e,
-                vbo: vbo_e,
-                tex: setup_texture_egui(),
-                shader: create_program(vert_e, frag_e),
-                buffer_size: 0,
-                window_size: window_size,
-            },
+            painter,
+        })
+    }
+}
+
+// the painter for the current context
+fn gl_painter(version: &GlVersion) -> Result<GlPainter, Box<dyn Error>> {
+    unsafe {
+        gl::Enable(gl::BLEND);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::STENCIL_TEST);
+        gl::Disable(gl::CULL_FACE);
+    }
+
+    let dsa = version.has_dsa();
+    let (vao_e, vbo_e) = setup_vertex_arrays_egui(dsa);
+    let vert_e = format!("{}\n{}", version.shader_header(), include_str!("shader_egui.vert"));
+    let frag_e = format!("{}\n{}", version.shader_header(), include_str!("shader_egui.frag"));
+
+    let shader = create_program(&vert_e, &frag_e)?;
+    let size_uniform = unsafe { gl::GetUniformLocation(shader, c"size".as_ptr()) };
+
+    Ok(GlPainter {
+        vao: vao_e,
+        vbo: vbo_e,
+        textures: HashMap::new(),
+        shader,
+        size_uniform,
+        buffer_size: 0,
+        dsa,
+        shader_header: version.shader_header(),
+        indices: Vec::new(),
+        vertices: Vec::new(),
+        font_pixels: Vec::new(),
+        stats: FrameStats::default(),
+    })
+}
+
+struct GlVersion {
+    major: i32,
+    minor: i32,
+    gles: bool,
+}
+
+impl GlVersion {
+    fn query() -> Self {
+        let (mut major, mut minor) = (0, 0);
+
+        let gles = unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+
+            let version = gl::GetString(gl::VERSION);
+            !version.is_null() && std::ffi::CStr::from_ptr(version as *const _).to_string_lossy().starts_with("OpenGL ES")
+        };
+
+        Self { major, minor, gles }
+    }
+
+    fn has_dsa(&self) -> bool {
+        !self.gles && (self.major, self.minor) >= (4, 5)
+    }
+
+    fn shader_header(&self) -> &'static str {
+        match (self.gles, self.has_dsa()) {
+            (true, _) => "#version 300 es\nprecision mediump float;",
+            (false, true) => "#version 450 core",
+            (false, false) => "#version 330 core",
         }
     }
+}
 
-    pub fn paint(&mut self) {
+impl std::fmt::Display for GlVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let api = if self.gles { "OpenGL ES" } else { "OpenGL" };
+        let painter = if self.has_dsa() { "DSA" } else { "bind-to-edit" };
+        write!(f, "{api} {}.{} ({painter} painter)", self.major, self.minor)
+    }
+}
+
+impl Renderer for GlRenderer {
+    fn paint(&mut self, primitives: &[egui::ClippedPrimitive], textures_delta: &egui::TexturesDelta, window_size: (u32, u32), pixels_per_point: f32) {
         unsafe {
             gl::ClearColor(0.0, 0.1, 0.2, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::STENCIL_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
 
-        paint_egui(&mut self.egui_state);
+        paint_egui(&mut self.painter, primitives, textures_delta, window_size, pixels_per_point);
 
         self.glutin_state.gl_surface.swap_buffers(&self.glutin_state.gl_ctx).unwrap();
     }
+
+    fn resize(&mut self, window_size: (u32, u32)) {
+        self.glutin_state.gl_surface.resize(
+            &self.glutin_state.gl_ctx,
+            std::num::NonZeroU32::new(window_size.0).unwrap(),
+            std::num::NonZeroU32::new(window_size.1).unwrap(),
+        );
+
+        unsafe {
+            gl::Viewport(0, 0, window_size.0 as i32, window_size.1 as i32);
+        };
+    }
+
+    fn supports_callbacks(&self) -> bool {
+        true
+    }
+
+    fn stats(&self) -> FrameStats {
+        self.painter.stats
+    }
 }
 
-fn create_program(vertex_src: &str, fragment_src: &str) -> u32 {
-    let vertex_handler = compile_shader(vertex_src, gl::VERTEX_SHADER);
-    let fragment_handler = compile_shader(fragment_src, gl::FRAGMENT_SHADER);
+pub fn create_program(vertex_src: &str, fragment_src: &str) -> Result<u32, Box<dyn Error>> {
+    let vertex_handler = compile_shader(vertex_src, gl::VERTEX_SHADER)?;
+    let fragment_handler = compile_shader(fragment_src, gl::FRAGMENT_SHADER)?;
 
     unsafe {
         let program_id = gl::CreateProgram();
@@ -124,16 +418,31 @@ fn create_program(vertex_src: &str, fragment_src: &str) -> u32 {
         gl::AttachShader(program_id, fragment_handler);
 
         gl::LinkProgram(program_id);
-        gl::UseProgram(program_id);
 
         gl::DeleteShader(vertex_handler);
         gl::DeleteShader(fragment_handler);
 
-        program_id
+        // shaders that compiled can still fail to link, e.g. where 330 / 300 es drivers disagree
+        let mut success = i32::from(gl::FALSE);
+
+        gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+        if success != i32::from(gl::TRUE) {
+            let mut len = 0;
+            gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut len);
+
+            let mut info_log = vec![0; len as usize];
+            gl::GetProgramInfoLog(program_id, len, ptr::null_mut(), info_log.as_mut_ptr() as *mut i8);
+            gl::DeleteProgram(program_id);
+            return Err(format!("shader linking failed: {}", String::from_utf8_lossy(&info_log)).into());
+        }
+
+        gl::UseProgram(program_id);
+
+        Ok(program_id)
     }
 }
 
-fn compile_shader(source: &str, shader_type: u32) -> u32 {
+fn compile_shader(source: &str, shader_type: u32) -> Result<u32, Box<dyn Error>> {
     unsafe {
         let shader_handler = gl::CreateShader(shader_type);
         let c_str = CString::new(source.as_bytes()).unwrap();
@@ -149,139 +458,218 @@ fn compile_shader(source: &str, shader_type: u32) -> u32 {
 
             let mut info_log = vec![0; len as usize];
             gl::GetShaderInfoLog(shader_handler, len, ptr::null_mut(), info_log.as_mut_ptr() as *mut i8);
-            println!("Shader compilation failed: {}", from_utf8(&info_log).unwrap());
-            std::process::exit(-1);
+            gl::DeleteShader(shader_handler);
+            return Err(format!("shader compilation failed: {}", String::from_utf8_lossy(&info_log)).into());
         }
 
-        shader_handler
+        Ok(shader_handler)
     }
 }
 
-pub fn event_handling(event: Event<()>, control_flow: &mut ControlFlow, graphics_state: &mut Graphics) {
-    match event {
-        Event::LoopDestroyed => {}
+impl EguiState {
+    // turns window input into egui events. paste reads the clipboard, only called for ctrl + v
+    pub fn input(&mut self, event: WindowEvent, paste: impl FnOnce() -> Option<String>) {
+        match event {
+            WindowEvent::ReceivedCharacter(ch) if is_printable_char(ch) && !self.raw_input.modifiers.ctrl && !self.composing => {
+                self.raw_input.events.push(egui::Event::Text(ch.to_string()));
+            }
 
-        Event::WindowEvent{event, ..} => {
-            match event {
-                WindowEvent::ReceivedCharacter(ch) => {
-                    if is_printable_char(ch) && !graphics_state.egui_state.raw_input.modifiers.ctrl {
-                        graphics_state.egui_state.raw_input.events.push(egui::Event::Text(ch.to_string()));
+            WindowEvent::Ime(ime) => {
+                let event = match ime {
+                    Ime::Enabled | Ime::Disabled => None,
+                    Ime::Preedit(text, _) if !self.composing => {
+                        self.composing = true;
+                        self.raw_input.events.push(egui::Event::CompositionStart);
+                        Some(egui::Event::CompositionUpdate(text))
                     }
-                }
-
-                WindowEvent::KeyboardInput{input, ..} => {
-                    // ui_state.request_redraw = 2;
 
-                    if let Some(keycode) = input.virtual_keycode {
-                        let pressed = input.state == ElementState::Pressed;
+                    Ime::Preedit(text, _) => Some(egui::Event::CompositionUpdate(text)),
+                    Ime::Commit(text) => {
+                        self.composing = false;
+                        Some(egui::Event::CompositionEnd(text))
+                    }
+                };
 
-                        if matches!(keycode, VirtualKeyCode::LAlt | VirtualKeyCode::RAlt) {
-                            graphics_state.egui_state.raw_input.modifiers.alt = pressed;
-                        }
+                self.raw_input.events.extend(event);
+            }
 
-                        if matches!(keycode, VirtualKeyCode::LControl | VirtualKeyCode::RControl) {
-                            graphics_state.egui_state.raw_input.modifiers.ctrl = pressed;
-                        }
+            WindowEvent::ModifiersChanged(state) => {
+                self.raw_input.modifiers = Modifiers {
+                    alt: state.alt(),
+                    ctrl: state.ctrl(),
+                    shift: state.shift(),
+                    mac_cmd: cfg!(target_os = "macos") && state.logo(),
+                    command: if cfg!(target_os = "macos") { state.logo() } else { state.ctrl() },
+                };
+            }
 
-                        if matches!(keycode, VirtualKeyCode::LShift | VirtualKeyCode::RShift) {
-                            graphics_state.egui_state.raw_input.modifiers.shift = pressed;
-                        }
+            WindowEvent::KeyboardInput{input, ..} => {
+                if let Some(keycode) = input.virtual_keycode {
+                    let pressed = input.state == ElementState::Pressed;
+                    let modifiers = self.raw_input.modifiers;
 
-                        if let Some(key) = translate_virtual_key_code(keycode) {
-                            graphics_state.egui_state.raw_input.events.push(
-                                egui::Event::Key{
-                                    key,
-                                    pressed,
-                                    modifiers: graphics_state.egui_state.raw_input.modifiers,
-                                    repeat: false,
+                    if pressed && modifiers.command {
+                        match keycode {
+                            VirtualKeyCode::C => self.raw_input.events.push(egui::Event::Copy),
+                            VirtualKeyCode::X => self.raw_input.events.push(egui::Event::Cut),
+                            VirtualKeyCode::V => {
+                                if let Some(text) = paste() {
+                                    self.raw_input.events.push(egui::Event::Paste(text.replace("\r\n", "\n")));
                                 }
-                            );
-                        }
+                            }
 
-                        if keycode == VirtualKeyCode::Escape && pressed {
-                            *control_flow = ControlFlow::Exit
+                            _ => (),
                         }
                     }
-                }
-
-                WindowEvent::CursorMoved{position, ..} => {
-                    // ui_state.request_redraw = 2;
 
-                    let pos_in_points_temp = egui::pos2(
-                        position.x as f32 / 1.0,
-                        position.y as f32 / 1.0,
-                    );
-                    graphics_state.egui_state.pos_in_points = Some(pos_in_points_temp);
-
-                    graphics_state.egui_state.raw_input.events.push(egui::Event::PointerMoved(pos_in_points_temp));
+                    if let Some(key) = translate_virtual_key_code(keycode) {
+                        self.raw_input.events.push(
+                            egui::Event::Key{
+                                key,
+                                pressed,
+                                modifiers,
+                                repeat: false,
+                            }
+                        );
+                    }
                 }
+            }
 
-                WindowEvent::MouseInput{state, button, ..} => {
-                    // ui_state.request_redraw = 2;
+            WindowEvent::CursorMoved{position, ..} => {
+                let pos_in_points_temp = self.to_points(position);
+                self.pos_in_points = Some(pos_in_points_temp);
 
-                    if let Some(pos_in_points_temp) = graphics_state.egui_state.pos_in_points {
-                        if let Some(button) = match button {
-                            winit::event::MouseButton::Left => Some(egui::PointerButton::Primary),
-                            winit::event::MouseButton::Right => Some(egui::PointerButton::Secondary),
-                            winit::event::MouseButton::Middle => Some(egui::PointerButton::Middle),
-                            _ => None,
-                        }
-                        {
-                            graphics_state.egui_state.raw_input.events.push(
-                                egui::Event::PointerButton{
-                                    pos: pos_in_points_temp,
-                                    button,
-                                    pressed: match state {
-                                        winit::event::ElementState::Pressed => true,
-                                        winit::event::ElementState::Released => false,
-                                    },
-                                    modifiers: Modifiers::default(),
-                                }
-                            );
-                        }
-                    }
-                }
+                self.raw_input.events.push(egui::Event::PointerMoved(pos_in_points_temp));
+            }
 
-                WindowEvent::MouseWheel {delta, ..} => {
-                    // ui_state.request_redraw = 2;
+            WindowEvent::CursorLeft{..} => {
+                self.pos_in_points = None;
+                self.raw_input.events.push(egui::Event::PointerGone);
+            }
 
-                    if let winit::event::MouseScrollDelta::LineDelta(_, y) = delta {
-                        graphics_state.egui_state.raw_input.events.push(
-                            egui::Event::Scroll(egui::vec2(0.0, y * 35.0))
+            WindowEvent::MouseInput{state, button, ..} => {
+                if let Some(pos_in_points_temp) = self.pos_in_points {
+                    if let Some(button) = match button {
+                        winit::event::MouseButton::Left => Some(egui::PointerButton::Primary),
+                        winit::event::MouseButton::Right => Some(egui::PointerButton::Secondary),
+                        winit::event::MouseButton::Middle => Some(egui::PointerButton::Middle),
+                        winit::event::MouseButton::Other(1) => Some(egui::PointerButton::Extra1),
+                        winit::event::MouseButton::Other(2) => Some(egui::PointerButton::Extra2),
+                        _ => None,
+                    }
+                    {
+                        self.raw_input.events.push(
+                            egui::Event::PointerButton{
+                                pos: pos_in_points_temp,
+                                button,
+                                pressed: match state {
+                                    winit::event::ElementState::Pressed => true,
+                                    winit::event::ElementState::Released => false,
+                                },
+                                modifiers: self.raw_input.modifiers,
+                            }
                         );
                     }
                 }
+            }
 
-                WindowEvent::Resized(physical_size) => {
-                    // ui_state.request_redraw = 2;
+            WindowEvent::MouseWheel{delta, ..} => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => egui::vec2(x, y) * 35.0, //points per line
+                    MouseScrollDelta::PixelDelta(delta) => egui::vec2(delta.x as f32, delta.y as f32) / self.pixels_per_point(),
+                };
 
-                    if physical_size.width != 0 && physical_size.height != 0 {
-                        graphics_state.glutin_state.gl_surface.resize(
-                            &graphics_state.glutin_state.gl_ctx,
-                            std::num::NonZeroU32::new(physical_size.width).unwrap(),
-                            std::num::NonZeroU32::new(physical_size.height).unwrap(),
-                        );
-                        graphics_state.egui_state.window_size = (physical_size.width, physical_size.height);
-    
-                        unsafe {
-                            gl::Viewport(0, 0, physical_size.width as i32, physical_size.height as i32);
-                            gl::ProgramUniform2f(graphics_state.egui_state.shader, 3, physical_size.width as f32, physical_size.height as f32);
-                        };
-                    }
-                }
+                let event = match self.raw_input.modifiers.ctrl {
+                    true => egui::Event::Zoom((delta.y / 200.0).exp()), //ctrl + scroll zooms, like in browsers
+                    false => egui::Event::Scroll(delta),
+                };
 
-                WindowEvent::CloseRequested => {
-                    *control_flow = ControlFlow::Exit
-                }
+                self.raw_input.events.push(event);
+            }
 
-                _ => ()
+            WindowEvent::Focused(focused) => {
+                self.raw_input.has_focus = focused;
+
+                if !focused {
+                    self.raw_input.modifiers = Modifiers::default(); //key releases go elsewhere now
+                }
             }
+
+            _ => ()
+        }
+    }
+}
+
+pub fn event_handling<T>(event: Event<T>, control_flow: &mut ControlFlow, graphics_state: &mut Graphics) {
+    let Event::WindowEvent{event, ..} = event else { return };
+
+    match event {
+        WindowEvent::Resized(physical_size) if physical_size.width != 0 && physical_size.height != 0 => {
+            graphics_state.resize((physical_size.width, physical_size.height));
+        }
+
+        WindowEvent::ScaleFactorChanged{scale_factor, new_inner_size} => {
+            graphics_state.egui_state.scale_factor = scale_factor as f32;
+            graphics_state.resize((new_inner_size.width, new_inner_size.height));
+        }
+
+        WindowEvent::CloseRequested => {
+            *control_flow = ControlFlow::Exit
         }
 
-        _ => ()
+        event => {
+            let clipboard = &mut graphics_state.clipboard;
+            graphics_state.egui_state.input(event, || clipboard.as_mut().and_then(|c| c.get_text().ok()));
+        }
     }
 }
 
+fn translate_cursor_icon(icon: egui::CursorIcon) -> Option<winit::window::CursorIcon> {
+    use egui::CursorIcon::*;
+    use winit::window::CursorIcon as Icon;
+
+    Some(
+        match icon {
+            None => return Option::None,
+
+            Default => Icon::Default,
+            ContextMenu => Icon::ContextMenu,
+            Help => Icon::Help,
+            PointingHand => Icon::Hand,
+            Progress => Icon::Progress,
+            Wait => Icon::Wait,
+            Cell => Icon::Cell,
+            Crosshair => Icon::Crosshair,
+            Text => Icon::Text,
+            VerticalText => Icon::VerticalText,
+            Alias => Icon::Alias,
+            Copy => Icon::Copy,
+            Move => Icon::Move,
+            NoDrop => Icon::NoDrop,
+            NotAllowed => Icon::NotAllowed,
+            Grab => Icon::Grab,
+            Grabbing => Icon::Grabbing,
+            AllScroll => Icon::AllScroll,
+            ResizeHorizontal => Icon::EwResize,
+            ResizeNeSw => Icon::NeswResize,
+            ResizeNwSe => Icon::NwseResize,
+            ResizeVertical => Icon::NsResize,
+            ResizeEast => Icon::EResize,
+            ResizeSouthEast => Icon::SeResize,
+            ResizeSouth => Icon::SResize,
+            ResizeSouthWest => Icon::SwResize,
+            ResizeWest => Icon::WResize,
+            ResizeNorthWest => Icon::NwResize,
+            ResizeNorth => Icon::NResize,
+            ResizeNorthEast => Icon::NeResize,
+            ResizeColumn => Icon::ColResize,
+            ResizeRow => Icon::RowResize,
+            ZoomIn => Icon::ZoomIn,
+            ZoomOut => Icon::ZoomOut,
+        }
+    )
+}
+
 fn is_printable_char(chr: char) -> bool {
     let is_in_private_use_area = ('\u{E000}' ..= '\u{F8FF}').contains(&chr)
         || ('\u{F0000}' ..= '\u{FFFFD}').contains(&chr)
@@ -356,3 +744,323 @@ pub fn translate_virtual_key_code(key: VirtualKeyCode) -> Option<egui::Key> {
         }
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glutin::context::{ContextApi, Version};
+    use self::offscreen::Offscreen;
+
+    const SIZE: (u32, u32) = (64, 64);
+
+    fn offscreen(api: ContextApi) -> Option<Offscreen> {
+        Offscreen::or_skip(api, SIZE)
+    }
+
+    // a red square on the background, plus the font atlas egui uploads on its first frame
+    fn red_square() -> (Vec<egui::ClippedPrimitive>, egui::TexturesDelta) {
+        let ctx = egui::Context::default();
+        let raw_input = egui::RawInput { screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(SIZE.0 as f32, SIZE.1 as f32))), ..Default::default() };
+
+        let output = ctx.run(raw_input, |ctx| {
+            let square = egui::Rect::from_min_max(egui::pos2(16.0, 16.0), egui::pos2(48.0, 48.0));
+            ctx.layer_painter(egui::LayerId::background()).rect_filled(square, 0.0, egui::Color32::RED);
+        });
+
+        (ctx.tessellate(output.shapes), output.textures_delta)
+    }
+
+    // paints the square with the painter for `version` and checks what ended up in the framebuffer
+    fn paints_red_square(offscreen: &Offscreen, version: &GlVersion) {
+        let mut painter = gl_painter(version).unwrap();
+        assert_eq!(painter.dsa, version.has_dsa());
+
+        let (primitives, textures_delta) = red_square();
+
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 1.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+
+        paint_egui(&mut painter, &primitives, &textures_delta, SIZE, 1.0);
+
+        assert_eq!(unsafe { gl::GetError() }, gl::NO_ERROR, "{version}");
+        assert_eq!(offscreen.pixel(32, 32), [255, 0, 0, 255], "{version}");
+        assert_eq!(offscreen.pixel(4, 4), [0, 0, 255, 255], "{version}");
+    }
+
+    fn egui_state(scale_factor: f32, zoom: f32) -> EguiState {
+        EguiState {
+            ctx: egui::Context::default(),
+            pos_in_points: None,
+            raw_input: egui::RawInput::default(),
+            window_size: (800, 600),
+            scale_factor,
+            zoom,
+            composing: false,
+        }
+    }
+
+    #[test]
+    fn points_and_pixels() {
+        for (scale_factor, zoom, pixels_per_point) in [(1.0, 1.0, 1.0), (1.25, 1.0, 1.25), (1.5, 1.0, 1.5), (2.0, 1.0, 2.0), (2.0, 1.5, 3.0), (1.0, 0.5, 0.5)] {
+            let state = egui_state(scale_factor, zoom);
+            assert_eq!(state.pixels_per_point(), pixels_per_point);
+
+            let size = egui::vec2(800.0, 600.0) / pixels_per_point;
+            assert_eq!(state.screen_rect(), egui::Rect::from_min_size(egui::Pos2::ZERO, size), "at {pixels_per_point}");
+
+            // corners and the middle of the window land on the same spots of the screen rect
+            for (x, y) in [(0.0, 0.0), (400.0, 300.0), (800.0, 600.0)] {
+                let pos = state.to_points(winit::dpi::PhysicalPosition::new(x, y));
+                let expected = state.screen_rect().min + egui::vec2(x as f32 / 800.0, y as f32 / 600.0) * size;
+                assert!((pos - expected).length() < 1e-3, "{x},{y} at {pixels_per_point}: {pos:?}");
+            }
+        }
+    }
+
+    // a window moved to a screen with another scale factor keeps its size in points
+    #[test]
+    fn scale_factor_change() {
+        let mut state = egui_state(1.0, 1.0);
+        let before = state.screen_rect();
+
+        state.scale_factor = 1.5;
+        state.window_size = (1200, 900);
+        assert_eq!(state.screen_rect(), before);
+        assert_eq!(state.to_points(winit::dpi::PhysicalPosition::new(600.0, 450.0)), egui::pos2(400.0, 300.0));
+    }
+
+    fn device() -> winit::event::DeviceId {
+        unsafe { winit::event::DeviceId::dummy() }
+    }
+
+    fn take_events(state: &mut EguiState) -> Vec<egui::Event> {
+        std::mem::take(&mut state.raw_input.events)
+    }
+
+    #[allow(deprecated)] //winit still wants the old modifiers fields filled in
+    fn cursor_moved(x: f64, y: f64) -> WindowEvent<'static> {
+        WindowEvent::CursorMoved { device_id: device(), position: winit::dpi::PhysicalPosition::new(x, y), modifiers: Default::default() }
+    }
+
+    #[allow(deprecated)]
+    fn mouse_input(state: ElementState, button: winit::event::MouseButton) -> WindowEvent<'static> {
+        WindowEvent::MouseInput { device_id: device(), state, button, modifiers: Default::default() }
+    }
+
+    #[allow(deprecated)]
+    fn mouse_wheel(delta: MouseScrollDelta) -> WindowEvent<'static> {
+        WindowEvent::MouseWheel { device_id: device(), delta, phase: winit::event::TouchPhase::Moved, modifiers: Default::default() }
+    }
+
+    #[allow(deprecated)]
+    fn key(keycode: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        let input = winit::event::KeyboardInput { scancode: 0, state, virtual_keycode: Some(keycode), modifiers: Default::default() };
+        WindowEvent::KeyboardInput { device_id: device(), input, is_synthetic: false }
+    }
+
+    fn modifiers(state: winit::event::ModifiersState) -> WindowEvent<'static> {
+        WindowEvent::ModifiersChanged(state)
+    }
+
+    const CTRL: Modifiers = Modifiers { alt: false, ctrl: true, shift: false, mac_cmd: false, command: true };
+
+    #[test]
+    fn pointer() {
+        let mut state = egui_state(2.0, 1.0);
+        let no_paste = || panic!("nothing should read the clipboard");
+
+        state.input(mouse_input(ElementState::Pressed, winit::event::MouseButton::Left), no_paste);
+        assert_eq!(take_events(&mut state), [], "a click before the pointer was anywhere");
+
+        state.input(modifiers(winit::event::ModifiersState::SHIFT | winit::event::ModifiersState::CTRL), no_paste);
+        state.input(cursor_moved(200.0, 100.0), no_paste);
+        state.input(mouse_input(ElementState::Pressed, winit::event::MouseButton::Left), no_paste);
+        state.input(mouse_input(ElementState::Released, winit::event::MouseButton::Right), no_paste);
+        state.input(mouse_input(ElementState::Pressed, winit::event::MouseButton::Other(7)), no_paste);
+
+        let modifiers = Modifiers { shift: true, ..CTRL };
+        let pos = egui::pos2(100.0, 50.0);
+
+        assert_eq!(take_events(&mut state), [
+            egui::Event::PointerMoved(pos),
+            egui::Event::PointerButton { pos, button: egui::PointerButton::Primary, pressed: true, modifiers },
+            egui::Event::PointerButton { pos, button: egui::PointerButton::Secondary, pressed: false, modifiers },
+        ]);
+
+        state.input(WindowEvent::CursorLeft { device_id: device() }, no_paste);
+        state.input(mouse_input(ElementState::Released, winit::event::MouseButton::Left), no_paste);
+        assert_eq!(take_events(&mut state), [egui::Event::PointerGone]);
+        assert_eq!(state.pos_in_points, None);
+    }
+
+    #[test]
+    fn scrolling() {
+        let mut state = egui_state(1.5, 1.0);
+        let no_paste = || panic!("nothing should read the clipboard");
+
+        state.input(mouse_wheel(MouseScrollDelta::LineDelta(1.0, -2.0)), no_paste);
+        state.input(mouse_wheel(MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition::new(30.0, 60.0))), no_paste);
+        assert_eq!(take_events(&mut state), [egui::Event::Scroll(egui::vec2(35.0, -70.0)), egui::Event::Scroll(egui::vec2(20.0, 40.0))]);
+
+        // ctrl + scroll zooms, in on scrolling up
+        state.input(modifiers(winit::event::ModifiersState::CTRL), no_paste);
+        state.input(mouse_wheel(MouseScrollDelta::LineDelta(0.0, 1.0)), no_paste);
+        state.input(mouse_wheel(MouseScrollDelta::LineDelta(0.0, -1.0)), no_paste);
+
+        match take_events(&mut state)[..] {
+            [egui::Event::Zoom(zoom_in), egui::Event::Zoom(zoom_out)] => {
+                assert!(zoom_in > 1.0 && zoom_out < 1.0);
+                assert!((zoom_in * zoom_out - 1.0).abs() < 1e-6);
+            }
+
+            ref events => panic!("{events:?}"),
+        }
+    }
+
+    #[test]
+    fn keys_and_clipboard() {
+        let mut state = egui_state(1.0, 1.0);
+
+        state.input(key(VirtualKeyCode::A, ElementState::Pressed), || None);
+        state.input(key(VirtualKeyCode::A, ElementState::Released), || None);
+        state.input(key(VirtualKeyCode::Apps, ElementState::Pressed), || None); //nothing in egui for it
+
+        assert_eq!(take_events(&mut state), [
+            egui::Event::Key { key: egui::Key::A, pressed: true, modifiers: Modifiers::NONE, repeat: false },
+            egui::Event::Key { key: egui::Key::A, pressed: false, modifiers: Modifiers::NONE, repeat: false },
+        ]);
+
+        state.input(modifiers(winit::event::ModifiersState::CTRL), || None);
+        state.input(key(VirtualKeyCode::C, ElementState::Pressed), || panic!("copying doesn't read the clipboard"));
+        state.input(key(VirtualKeyCode::X, ElementState::Pressed), || panic!("neither does cutting"));
+        state.input(key(VirtualKeyCode::V, ElementState::Pressed), || Some(String::from("#ff8000\r\n")));
+        state.input(key(VirtualKeyCode::V, ElementState::Pressed), || None); //nothing in the clipboard, or no clipboard
+
+        let key = |key| egui::Event::Key { key, pressed: true, modifiers: CTRL, repeat: false };
+
+        assert_eq!(take_events(&mut state), [
+            egui::Event::Copy,
+            key(egui::Key::C),
+            egui::Event::Cut,
+            key(egui::Key::X),
+            egui::Event::Paste(String::from("#ff8000\n")),
+            key(egui::Key::V),
+            key(egui::Key::V),
+        ]);
+    }
+
+    #[test]
+    fn text_and_ime() {
+        let mut state = egui_state(1.0, 1.0);
+
+        state.input(WindowEvent::ReceivedCharacter('a'), || None);
+        state.input(WindowEvent::ReceivedCharacter('\u{8}'), || None); //backspace comes as a key
+        assert_eq!(take_events(&mut state), [egui::Event::Text(String::from("a"))]);
+
+        state.input(WindowEvent::Ime(Ime::Enabled), || None);
+        state.input(WindowEvent::Ime(Ime::Preedit(String::from("ni"), Some((2, 2)))), || None);
+        state.input(WindowEvent::ReceivedCharacter('n'), || None); //belongs to the composition
+        state.input(WindowEvent::Ime(Ime::Preedit(String::from("nih"), Some((3, 3)))), || None);
+        state.input(WindowEvent::Ime(Ime::Commit(String::from("你好"))), || None);
+        state.input(WindowEvent::ReceivedCharacter('!'), || None);
+
+        assert_eq!(take_events(&mut state), [
+            egui::Event::CompositionStart,
+            egui::Event::CompositionUpdate(String::from("ni")),
+            egui::Event::CompositionUpdate(String::from("nih")),
+            egui::Event::CompositionEnd(String::from("你好")),
+            egui::Event::Text(String::from("!")),
+        ]);
+
+        // ctrl + a is a shortcut, not text
+        state.input(modifiers(winit::event::ModifiersState::CTRL), || None);
+        state.input(WindowEvent::ReceivedCharacter('a'), || None);
+        assert_eq!(take_events(&mut state), []);
+    }
+
+    #[test]
+    fn focus() {
+        let mut state = egui_state(1.0, 1.0);
+
+        state.input(WindowEvent::Focused(true), || None);
+        state.input(modifiers(winit::event::ModifiersState::ALT), || None);
+        assert!(state.raw_input.has_focus);
+        assert!(state.raw_input.modifiers.alt);
+
+        // the alt release goes to whatever window alt + tab switched to
+        state.input(WindowEvent::Focused(false), || None);
+        assert!(!state.raw_input.has_focus);
+        assert_eq!(state.raw_input.modifiers, Modifiers::NONE);
+    }
+
+    #[test]
+    fn link_failure_is_an_error() {
+        let Some(_offscreen) = offscreen(ContextApi::OpenGl(Some(Version::new(4, 5)))) else { return };
+        let version = GlVersion::query();
+
+        let vert = format!("{}\n{}", version.shader_header(), include_str!("shader_egui.vert"));
+        let frag = format!("{}\nout vec4 color; void not_main() {{ color = vec4(1.0); }}", version.shader_header()); //compiles, can't link
+
+        let e = create_program(&vert, &frag).unwrap_err();
+        assert!(e.to_string().starts_with("shader linking failed"), "{e}");
+    }
+
+    #[test]
+    fn dsa_painter() {
+        let Some(offscreen) = offscreen(ContextApi::OpenGl(Some(Version::new(4, 5)))) else { return };
+        let version = GlVersion::query();
+
+        if !version.has_dsa() {
+            println!("{version} has no DSA, skipping");
+            return;
+        }
+
+        paints_red_square(&offscreen, &version);
+    }
+
+    // the 330 shaders and bind-to-edit calls, on a context that could do more
+    #[test]
+    fn bind_to_edit_painter() {
+        let Some(offscreen) = offscreen(ContextApi::OpenGl(Some(Version::new(4, 5)))) else { return };
+        paints_red_square(&offscreen, &GlVersion { major: 3, minor: 3, gles: false });
+    }
+
+    #[test]
+    fn gles_painter() {
+        let Some(offscreen) = offscreen(ContextApi::Gles(Some(Version::new(3, 0)))) else { return };
+        let version = GlVersion::query();
+        assert!(version.gles, "{version}");
+
+        paints_red_square(&offscreen, &version);
+    }
+
+    // a driver that really only does 3.3, the way mesa pretends to with MESA_GL_VERSION_OVERRIDE.
+    // the variable has to be there before the driver loads, so this runs itself again in a child process
+    #[test]
+    fn mesa_gl_33() {
+        if std::env::var_os("MESA_GL_VERSION_OVERRIDE").is_none() {
+            if offscreen(ContextApi::OpenGl(Some(Version::new(4, 5)))).is_none() {
+                return;
+            }
+
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "graphics::tests::mesa_gl_33", "--nocapture"])
+                .env("MESA_GL_VERSION_OVERRIDE", "3.3")
+                .status()
+                .unwrap();
+
+            assert!(status.success());
+            return;
+        }
+
+        assert!(Offscreen::new(ContextApi::OpenGl(Some(Version::new(4, 5))), SIZE).is_err(), "4.5 should be out of reach");
+
+        let offscreen = Offscreen::new(ContextApi::OpenGl(Some(Version::new(3, 3))), SIZE).unwrap();
+        let version = GlVersion::query();
+        assert_eq!(version.to_string(), "OpenGL 3.3 (bind-to-edit painter)");
+
+        paints_red_square(&offscreen, &version);
+    }
+}
@@ -0,0 +1,60 @@
+// a GL context without a window, for the tests: surfaceless on the first EGL device (mesa's
+// llvmpipe when there's no gpu), drawing into a renderbuffer
+
+use std::ffi::CString;
+
+use glutin::api::egl::{context::PossiblyCurrentContext, device::Device, display::Display};
+use glutin::config::{ConfigSurfaceTypes, ConfigTemplateBuilder};
+use glutin::context::{ContextApi, ContextAttributesBuilder, GlProfile};
+use glutin::prelude::*;
+
+pub struct Offscreen {
+    pub size: (u32, u32),
+    _ctx: PossiblyCurrentContext, //current on the thread that made it
+}
+
+impl Offscreen {
+    pub fn new(api: ContextApi, size: (u32, u32)) -> Result<Self, String> {
+        let device = Device::query_devices().map_err(|e| e.to_string())?.next().ok_or("no EGL device")?;
+        let display = unsafe { Display::with_device(&device, None) }.map_err(|e| e.to_string())?;
+
+        let template = ConfigTemplateBuilder::new().with_surface_type(ConfigSurfaceTypes::PBUFFER).build();
+        let config = unsafe { display.find_configs(template) }.map_err(|e| e.to_string())?.next().ok_or("no EGL config")?;
+
+        let attributes = ContextAttributesBuilder::new().with_profile(GlProfile::Core).with_context_api(api).build(None);
+        let ctx = unsafe { display.create_context(&config, &attributes) }.map_err(|e| e.to_string())?;
+        let ctx = ctx.make_current_surfaceless().map_err(|e| e.to_string())?;
+
+        gl::load_with(|symbol| display.get_proc_address(&CString::new(symbol).unwrap()) as _);
+
+        unsafe {
+            let (mut framebuffer, mut renderbuffer) = (0, 0);
+            gl::GenRenderbuffers(1, &mut renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, size.0 as i32, size.1 as i32);
+
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, renderbuffer);
+            gl::Viewport(0, 0, size.0 as i32, size.1 as i32);
+        }
+
+        Ok(Self { size, _ctx: ctx })
+    }
+
+    // None, after saying so, where there's no EGL to test with
+    pub fn or_skip(api: ContextApi, size: (u32, u32)) -> Option<Self> {
+        Self::new(api, size).map_err(|e| println!("no offscreen GL ({e}), skipping")).ok()
+    }
+
+    // rgba, top row first like egui's coordinates
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let mut pixel = [0; 4];
+
+        unsafe {
+            gl::ReadPixels(x as i32, (self.size.1 - 1 - y) as i32, 1, 1, gl::RGBA, gl::UNSIGNED_BYTE, pixel.as_mut_ptr() as *mut _);
+        }
+
+        pixel
+    }
+}
@@ -1,4 +1,4 @@
-#version 450 core
+// the #version line is prepended in graphics/mod.rs, depending on which context we got
 
 //-----
 
@@ -25,6 +25,6 @@ vec4 srgba_gamma_from_linear(vec4 rgba) { // 0-1 sRGBA gamma  from  0-1 linear
 }
 
 void main() {
-   vec4 texture_in_gamma = srgba_gamma_from_linear(texture2D(tex_sampler, uv));
+   vec4 texture_in_gamma = srgba_gamma_from_linear(texture(tex_sampler, uv));
    color_out = color * texture_in_gamma;
 }
@@ -1,4 +1,4 @@
-#version 450 core
+// the #version line is prepended in graphics/mod.rs, depending on which context we got
 
 //-----
 
@@ -6,7 +6,7 @@ layout (location = 0) in vec2 vertices;
 layout (location = 1) in vec2 uv_in;
 layout (location = 2) in vec4 color_in;
 
-layout (location = 3) uniform vec2 size = vec2(720.0, 720.0);
+uniform vec2 size;
 
 out vec2 uv;
 out vec4 color;
@@ -0,0 +1,47 @@
+// the #version line is prepended in graphics/mod.rs, depending on which context we got
+
+//-----
+
+uniform vec2 size;
+uniform vec3 left;
+uniform vec3 right;
+
+//-----
+
+in vec2 pos;
+
+out vec4 color_out;
+
+//-----
+
+const vec3 body = vec3(0.27, 0.25, 0.30);
+
+vec4 over(vec4 top, vec4 bottom) { // premultiplied alpha
+    return top + bottom * (1.0 - top.a);
+}
+
+vec4 cup(vec2 p, vec2 center, vec3 light, float aa) {
+    float d = length((p - center) * vec2(1.0, 0.75)) - 0.28; // upright ellipse
+
+    float fill = 1.0 - smoothstep(-aa, 0.0, d);
+    float ring = smoothstep(-0.09 - aa, -0.09, d) * fill;
+    float glow = exp(-max(d, 0.0) * 14.0) * 0.6 * step(0.0, d);
+
+    vec4 result = vec4(light * glow, glow * max(light.r, max(light.g, light.b)));
+    result = over(vec4(body * fill, fill), result);
+    return over(vec4(light * ring, ring), result);
+}
+
+void main() {
+    vec2 p = vec2(pos.x * size.x / size.y, pos.y);
+    float aa = 2.0 / size.y;
+
+    float band_d = abs(length(p - vec2(0.0, -0.3)) - 0.62) - 0.06;
+    float band = (1.0 - smoothstep(-aa, 0.0, band_d)) * step(-0.3, p.y);
+
+    vec4 result = vec4(body * band, band);
+    result = over(cup(p, vec2(-0.62, -0.35), left, aa), result);
+    result = over(cup(p, vec2(0.62, -0.35), right, aa), result);
+
+    color_out = result;
+}
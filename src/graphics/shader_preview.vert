@@ -0,0 +1,13 @@
+// the #version line is prepended in graphics/mod.rs, depending on which context we got
+
+//-----
+
+out vec2 pos;
+
+//-----
+
+void main() { // one triangle covering the viewport, no vertex buffer needed
+    vec2 corner = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    pos = corner * 2.0 - 1.0;
+    gl_Position = vec4(pos, 0.0, 1.0);
+}
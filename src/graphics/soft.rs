@@ -0,0 +1,223 @@
+// CPU fallback for when there's no usable OpenGL (old GPUs, VMs, remote desktops).
+// rasterizes egui's meshes into a softbuffer surface, following what the GL painter does:
+// vertex color * gamma encoded texel, premultiplied alpha blending
+
+use std::{collections::HashMap, error::Error, num::NonZeroU32};
+
+use winit::window::Window;
+
+use super::{FrameStats, Renderer};
+
+const CLEAR_COLOR: [f32; 3] = [0.0, 0.1, 0.2];
+
+struct Texture {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>, //0-1, rgb gamma encoded like the shader does
+}
+
+pub struct SoftRenderer {
+    surface: softbuffer::Surface,
+    _context: softbuffer::Context,
+    textures: HashMap<egui::TextureId, Texture>,
+    frame: Vec<[f32; 3]>,
+    gamma_lut: [f32; 256],
+    stats: FrameStats,
+}
+
+impl SoftRenderer {
+    pub fn new(window: &Window, window_size: (u32, u32)) -> Result<Self, Box<dyn Error>> {
+        let context = unsafe { softbuffer::Context::new(window)? };
+        let surface = unsafe { softbuffer::Surface::new(&context, window)? };
+
+        let mut renderer = Self {
+            surface,
+            _context: context,
+            textures: HashMap::new(),
+            frame: Vec::new(),
+            gamma_lut: std::array::from_fn(|x| srgb_gamma_from_linear(x as f32 / 255.0)),
+            stats: FrameStats::default(),
+        };
+
+        renderer.resize(window_size);
+        Ok(renderer)
+    }
+
+    fn update_textures(&mut self, textures_delta: &egui::TexturesDelta) {
+        for (id, image_delta) in &textures_delta.set {
+            let pixels: Vec<[f32; 4]> = match &image_delta.image {
+                egui::ImageData::Color(image) => image.pixels.iter().map(|c| self.encode(c.to_array())).collect(),
+                egui::ImageData::Font(image) => image.srgba_pixels(Some(1.0)).map(|c| self.encode(c.to_array())).collect(),
+            };
+
+            let [width, height] = image_delta.image.size();
+
+            match image_delta.pos {
+                Some([x, y]) => {
+                    let Some(texture) = self.textures.get_mut(id) else { continue };
+
+                    for row in 0 .. height.min(texture.height.saturating_sub(y)) {
+                        let len = width.min(texture.width.saturating_sub(x));
+                        let dst = (y + row) * texture.width + x;
+                        texture.pixels[dst .. dst + len].copy_from_slice(&pixels[row * width .. row * width + len]);
+                    }
+                }
+
+                None => {
+                    self.textures.insert(*id, Texture { width, height, pixels });
+                }
+            }
+        }
+    }
+
+    fn encode(&self, [r, g, b, a]: [u8; 4]) -> [f32; 4] {
+        [self.gamma_lut[r as usize], self.gamma_lut[g as usize], self.gamma_lut[b as usize], a as f32 / 255.0]
+    }
+
+    fn draw_mesh(&mut self, mesh: &egui::Mesh, clip: [usize; 4], width: usize, pixels_per_point: f32) {
+        let Some(texture) = self.textures.get(&mesh.texture_id) else { return };
+        let [clip_x0, clip_y0, clip_x1, clip_y1] = clip;
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [v0, v1, v2] = [0, 1, 2].map(|i| {
+                let vertex = mesh.vertices[triangle[i] as usize];
+                egui::epaint::Vertex { pos: (vertex.pos.to_vec2() * pixels_per_point).to_pos2(), ..vertex } //points to pixels
+            });
+
+            let area = edge(v0.pos, v1.pos, v2.pos);
+            if area == 0.0 {
+                continue;
+            }
+
+            let min_x = (v0.pos.x.min(v1.pos.x).min(v2.pos.x).floor().max(0.0) as usize).max(clip_x0);
+            let min_y = (v0.pos.y.min(v1.pos.y).min(v2.pos.y).floor().max(0.0) as usize).max(clip_y0);
+            let max_x = (v0.pos.x.max(v1.pos.x).max(v2.pos.x).ceil().max(0.0) as usize).min(clip_x1);
+            let max_y = (v0.pos.y.max(v1.pos.y).max(v2.pos.y).ceil().max(0.0) as usize).min(clip_y1);
+
+            let [c0, c1, c2] = [v0, v1, v2].map(|v| v.color.to_array().map(|c| c as f32 / 255.0));
+
+            for y in min_y .. max_y {
+                for x in min_x .. max_x {
+                    let p = egui::pos2(x as f32 + 0.5, y as f32 + 0.5);
+
+                    // barycentric weights, sign of area takes care of winding
+                    let w0 = edge(v1.pos, v2.pos, p) / area;
+                    let w1 = edge(v2.pos, v0.pos, p) / area;
+                    let w2 = 1.0 - w0 - w1;
+
+                    if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                        continue;
+                    }
+
+                    let uv = v0.uv.to_vec2() * w0 + v1.uv.to_vec2() * w1 + v2.uv.to_vec2() * w2;
+                    let texel = sample(texture, uv.x, uv.y);
+
+                    let src: [f32; 4] = std::array::from_fn(|i| (c0[i] * w0 + c1[i] * w1 + c2[i] * w2) * texel[i]);
+                    let dst = &mut self.frame[y * width + x];
+
+                    for i in 0 .. 3 {
+                        dst[i] = src[i] + dst[i] * (1.0 - src[3]); //ONE, ONE_MINUS_SRC_ALPHA
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Renderer for SoftRenderer {
+    fn paint(&mut self, primitives: &[egui::ClippedPrimitive], textures_delta: &egui::TexturesDelta, window_size: (u32, u32), pixels_per_point: f32) {
+        self.stats = FrameStats { texture_uploads: textures_delta.set.len() as u32, ..Default::default() };
+        self.update_textures(textures_delta);
+
+        let (width, height) = (window_size.0 as usize, window_size.1 as usize);
+        self.frame.clear();
+        self.frame.resize(width * height, CLEAR_COLOR);
+
+        for egui::ClippedPrimitive{clip_rect, primitive} in primitives {
+            let clip_rect = egui::Rect::from_min_max(
+                (clip_rect.min.to_vec2() * pixels_per_point).to_pos2(),
+                (clip_rect.max.to_vec2() * pixels_per_point).to_pos2(),
+            );
+            let clip = [
+                (clip_rect.min.x.max(0.0) as usize).min(width),
+                (clip_rect.min.y.max(0.0) as usize).min(height),
+                (clip_rect.max.x.max(0.0) as usize).min(width),
+                (clip_rect.max.y.max(0.0) as usize).min(height),
+            ];
+
+            match primitive {
+                egui::epaint::Primitive::Mesh(mesh) => {
+                    self.draw_mesh(mesh, clip, width, pixels_per_point);
+                    self.stats.draw_calls += 1;
+                }
+
+                egui::epaint::Primitive::Callback(_) => (), //needs GL
+            }
+        }
+
+        for id in &textures_delta.free {
+            self.textures.remove(id);
+        }
+
+        let mut buffer = match self.surface.buffer_mut() {
+            Ok(buffer) => buffer,
+            Err(e) => return println!("software renderer: {e}"),
+        };
+
+        for (dst, [r, g, b]) in buffer.iter_mut().zip(&self.frame) {
+            let [r, g, b] = [r, g, b].map(|c| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u32);
+            *dst = r << 16 | g << 8 | b;
+        }
+
+        if let Err(e) = buffer.present() {
+            println!("software renderer: {e}");
+        }
+    }
+
+    fn resize(&mut self, window_size: (u32, u32)) {
+        if let (Some(width), Some(height)) = (NonZeroU32::new(window_size.0), NonZeroU32::new(window_size.1)) {
+            if let Err(e) = self.surface.resize(width, height) {
+                println!("software renderer: {e}");
+            }
+        }
+    }
+
+    fn supports_callbacks(&self) -> bool {
+        false
+    }
+
+    fn stats(&self) -> FrameStats {
+        self.stats
+    }
+}
+
+fn edge(a: egui::Pos2, b: egui::Pos2, p: egui::Pos2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// bilinear, clamped to edge
+fn sample(texture: &Texture, u: f32, v: f32) -> [f32; 4] {
+    let x = (u * texture.width as f32 - 0.5).clamp(0.0, (texture.width - 1) as f32);
+    let y = (v * texture.height as f32 - 0.5).clamp(0.0, (texture.height - 1) as f32);
+
+    let (x0, y0) = (x as usize, y as usize);
+    let (x1, y1) = ((x0 + 1).min(texture.width - 1), (y0 + 1).min(texture.height - 1));
+    let (fx, fy) = (x.fract(), y.fract());
+
+    let px = |x, y| texture.pixels[y * texture.width + x];
+    let (a, b, c, d) = (px(x0, y0), px(x1, y0), px(x0, y1), px(x1, y1));
+
+    std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+fn srgb_gamma_from_linear(x: f32) -> f32 {
+    if x < 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
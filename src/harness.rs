@@ -0,0 +1,670 @@
+// runs create_ui off-screen with made up input and a simulated headset, and compares what comes
+// out (texts on screen, a digest of the tessellated meshes and the BtCommands sent) against the
+// files in golden/. the tui gets the same treatment on a virtual terminal (tui_*.txt), and the
+// bluetooth doctor runs against made up machines (doctor_*.txt):
+//   cargo test harness                      compare
+//   CATCALLER_BLESS=1 cargo test harness    rewrite the golden files after an intended change
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use egui::epaint::{ClippedShape, Primitive, Shape};
+use futures_util::FutureExt;
+use egui::{Event, Key, Modifiers, PointerButton, Pos2, RawInput, Rect, Vec2};
+use catcaller::Model;
+use ratatui::backend::TestBackend;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::Color;
+use ratatui::Terminal;
+use tokio::sync::mpsc;
+
+use crate::bt::{BtCommands, BtToGui};
+use crate::config::Preset;
+use crate::doctor::{self, Adapter, Bluez, BusError, Device, System};
+use crate::keys::Bindings;
+use crate::theme::Themes;
+use crate::tui::Tui;
+use crate::ui::{self, UiState};
+
+const SCREEN: Vec2 = Vec2::new(400.0, 400.0);
+const TERMINAL: (u16, u16) = (80, 24);
+const FRAME_TIME: f64 = 1.0 / 40.0;
+
+pub struct Harness {
+    ctx: egui::Context,
+    ui_state: UiState,
+    tx: mpsc::Sender<BtCommands>,
+    rx: mpsc::Receiver<BtCommands>,
+    time: f64,
+    shapes: Vec<ClippedShape>,
+    repaint_after: Duration, //what egui asked of the event loop after the last frame
+    log: String, //becomes the golden file
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel(16);
+
+        let ui_state = UiState {
+            presets: presets(),
+            bindings: Bindings::new(&HashMap::new()), //defaults only, not the user's config
+            ..Default::default()
+        };
+
+        let mut harness = Self { ctx: egui::Context::default(), ui_state, tx, rx, time: 0.0, shapes: Vec::new(), repaint_after: Duration::ZERO, log: String::new() };
+        harness.frame(Vec::new()); //first frame only lays things out
+        harness
+    }
+
+    pub fn frame(&mut self, events: Vec<Event>) {
+        let input = RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, SCREEN)),
+            pixels_per_point: Some(1.0),
+            time: Some(self.time),
+            events,
+            ..Default::default()
+        };
+
+        self.time += FRAME_TIME;
+
+        self.ctx.begin_frame(input);
+        ui::create_ui(&mut self.ctx, &self.tx, &mut self.ui_state);
+        let output = self.ctx.end_frame();
+        self.shapes = output.shapes;
+        self.repaint_after = output.repaint_after;
+    }
+
+    // the same thing main does with messages from the bt task
+    pub fn bt(&mut self, bt_recv: BtToGui) {
+        self.ui_state.handle_bt(bt_recv);
+        self.frame(Vec::new());
+    }
+
+    pub fn click(&mut self, pos: Pos2) {
+        self.frame(vec![Event::PointerMoved(pos)]);
+        self.frame(vec![pointer_button(pos, true)]);
+        self.frame(vec![pointer_button(pos, false)]);
+        self.settle();
+    }
+
+    // clicks the middle of the first text on screen that reads `text`
+    pub fn click_text(&mut self, text: &str) {
+        match self.texts().into_iter().find(|(label, _)| label == text) {
+            Some((_, rect)) => self.click(rect.center()),
+            None => writeln!(self.log, "!! no \"{text}\" on screen").unwrap(),
+        }
+    }
+
+    pub fn drag(&mut self, from: Pos2, to: Pos2) {
+        self.frame(vec![Event::PointerMoved(from)]);
+        self.frame(vec![pointer_button(from, true)]);
+
+        for step in 1 ..= 4 {
+            self.frame(vec![Event::PointerMoved(from + (to - from) * (step as f32 / 4.0))]);
+        }
+
+        self.frame(vec![pointer_button(to, false)]);
+    }
+
+    pub fn press(&mut self, modifiers: Modifiers, key: Key) {
+        self.frame(vec![
+            Event::Key { key, pressed: true, repeat: false, modifiers },
+            Event::Key { key, pressed: false, repeat: false, modifiers },
+        ]);
+
+        self.settle();
+    }
+
+    // lets windows that were just opened lay themselves out and fade in
+    pub fn settle(&mut self) {
+        let frames = (self.ctx.style().animation_time as f64 / FRAME_TIME).ceil() as usize + 1;
+
+        for _ in 0 .. frames {
+            self.frame(Vec::new());
+        }
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        self.frame(vec![Event::Text(text.to_string())]);
+    }
+
+    // texts on screen with where they are, in paint order
+    pub fn texts(&self) -> Vec<(String, Rect)> {
+        fn collect(shape: &Shape, texts: &mut Vec<(String, Rect)>) {
+            match shape {
+                Shape::Text(text) => texts.push((text.galley.text().to_string(), text.galley.rect.translate(text.pos.to_vec2()))),
+                Shape::Vec(shapes) => shapes.iter().for_each(|shape| collect(shape, texts)),
+                _ => (),
+            }
+        }
+
+        let mut texts = Vec::new();
+
+        for ClippedShape(_, shape) in &self.shapes {
+            collect(shape, &mut texts);
+        }
+
+        texts
+    }
+
+    // writes what's on screen and what got sent since the last snapshot to the log
+    pub fn snapshot(&mut self, title: &str) {
+        writeln!(self.log, "== {title}").unwrap();
+
+        for (text, rect) in self.texts() {
+            writeln!(self.log, "text {text:?} at {:.0},{:.0}", rect.min.x, rect.min.y).unwrap();
+        }
+
+        let primitives = self.ctx.tessellate(self.shapes.clone());
+
+        for primitive in &primitives {
+            let Primitive::Mesh(mesh) = &primitive.primitive else {
+                writeln!(self.log, "paint callback").unwrap();
+                continue;
+            };
+
+            // positions rounded to a quarter pixel so float noise doesn't show up as a change
+            let mut hash = Fnv::default();
+
+            for vertex in &mesh.vertices {
+                hash.write(&((vertex.pos.x * 4.0).round() as i32).to_le_bytes());
+                hash.write(&((vertex.pos.y * 4.0).round() as i32).to_le_bytes());
+                hash.write(&vertex.color.to_array());
+            }
+
+            for index in &mesh.indices {
+                hash.write(&index.to_le_bytes());
+            }
+
+            writeln!(self.log, "mesh {:?} {} vertices {} indices {:016x}", mesh.texture_id, mesh.vertices.len(), mesh.indices.len(), hash.0).unwrap();
+        }
+
+        while let Ok(command) = self.rx.try_recv() {
+            writeln!(self.log, "sent {command:?}").unwrap();
+        }
+
+        self.log.push('\n');
+    }
+}
+
+fn presets() -> Vec<Preset> {
+    vec![Preset { name: String::from("on air"), mode: 1, rgb: [255, 0, 0], settings: [63, 0] }]
+}
+
+fn pointer_button(pos: Pos2, pressed: bool) -> Event {
+    Event::PointerButton { pos, button: PointerButton::Primary, pressed, modifiers: Modifiers::NONE }
+}
+
+// fnv-1a, stable across rust versions unlike DefaultHasher
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+// the tui on a virtual terminal
+pub struct TuiHarness {
+    tui: Tui,
+    terminal: Terminal<TestBackend>,
+    tx: mpsc::Sender<BtCommands>,
+    rx: mpsc::Receiver<BtCommands>,
+    log: String,
+}
+
+impl TuiHarness {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel(16);
+        let terminal = Terminal::new(TestBackend::new(TERMINAL.0, TERMINAL.1)).unwrap();
+
+        Self { tui: Tui::new(presets()), terminal, tx, rx, log: String::new() }
+    }
+
+    pub fn bt(&mut self, bt_recv: BtToGui) {
+        self.tui.ui_state.handle_bt(bt_recv);
+    }
+
+    pub fn key(&mut self, code: KeyCode) {
+        self.key_with(KeyModifiers::NONE, code);
+    }
+
+    pub fn key_with(&mut self, modifiers: KeyModifiers, code: KeyCode) {
+        self.tui.handle_key(KeyEvent::new(code, modifiers), &self.tx);
+    }
+
+    pub fn keys(&mut self, text: &str) {
+        text.chars().for_each(|c| self.key(KeyCode::Char(c)));
+    }
+
+    // the terminal's text, the truecolor swatch and what got sent since the last snapshot
+    pub fn snapshot(&mut self, title: &str) {
+        writeln!(self.log, "== {title}").unwrap();
+
+        let tui = &self.tui;
+        self.terminal.draw(|frame| tui.draw(frame)).unwrap();
+
+        let buffer = self.terminal.backend().buffer();
+        let mut swatch = None;
+
+        for y in 0 .. buffer.area.height {
+            let line: String = (0 .. buffer.area.width).map(|x| {
+                let cell = &buffer[(x, y)];
+
+                if let Color::Rgb(r, g, b) = cell.bg {
+                    swatch = Some((r, g, b));
+                }
+
+                cell.symbol()
+            }).collect();
+
+            writeln!(self.log, "|{}", line.trim_end()).unwrap();
+        }
+
+        if let Some((r, g, b)) = swatch {
+            writeln!(self.log, "swatch {r},{g},{b}").unwrap();
+        }
+
+        while let Ok(command) = self.rx.try_recv() {
+            writeln!(self.log, "sent {command:?}").unwrap();
+        }
+
+        self.log.push('\n');
+    }
+}
+
+// a machine for the doctor: files by path, and a stand-in for bluez on the system bus
+pub struct FakeSystem {
+    files: HashMap<String, String>,
+    bluez: Result<Bluez, BusError>,
+}
+
+impl FakeSystem {
+    // everything in order, with the headset paired
+    pub fn healthy() -> Self {
+        let mut system = Self { files: HashMap::new(), bluez: Ok(Bluez::default()) };
+
+        system.file("/etc/group", "root:x:0:\nbluetooth:x:112:\nuser:x:1000:");
+        system.file("/proc/self/status", "Name:\tblatand\nGid:\t1000\t1000\t1000\t1000\nGroups:\t112 1000");
+        system.file("/sys/class/bluetooth/hci0/address", "00:1A:7D:DA:71:13");
+        system.rfkill("rfkill0", "hci0", "0", "0");
+        system.adapter(true);
+        system.device("YOWU-SELKIRK-4", true, None);
+        system
+    }
+
+    pub fn file(&mut self, path: &str, contents: &str) {
+        self.files.insert(path.to_string(), contents.to_string());
+    }
+
+    pub fn rfkill(&mut self, entry: &str, name: &str, soft: &str, hard: &str) {
+        for (file, contents) in [("type", "bluetooth"), ("name", name), ("soft", soft), ("hard", hard)] {
+            self.file(&format!("/sys/class/rfkill/{entry}/{file}"), &format!("{contents}\n"));
+        }
+    }
+
+    pub fn adapter(&mut self, powered: bool) {
+        if let Ok(bluez) = &mut self.bluez {
+            bluez.adapters = vec![Adapter { name: String::from("hci0"), address: String::from("00:1A:7D:DA:71:13"), powered }];
+        }
+    }
+
+    pub fn device(&mut self, name: &str, paired: bool, rssi: Option<i16>) {
+        if let Ok(bluez) = &mut self.bluez {
+            let address = format!("C0:4A:0E:00:00:{:02X}", bluez.devices.len());
+            bluez.devices.push(Device { address, name: Some(name.to_string()), paired, connected: false, rssi });
+        }
+    }
+}
+
+impl System for FakeSystem {
+    fn read(&self, path: &str) -> Option<String> {
+        self.files.get(path).cloned()
+    }
+
+    fn list(&self, dir: &str) -> Vec<String> {
+        let mut names: Vec<String> = self.files.keys()
+            .filter_map(|path| path.strip_prefix(dir)?.strip_prefix('/')?.split('/').next())
+            .map(str::to_string)
+            .collect();
+
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    async fn bluez(&self) -> Result<Bluez, BusError> {
+        self.bluez.clone()
+    }
+}
+
+// the states the real bt task reports, in order
+const CONNECTING: [BtToGui; 5] = [
+    BtToGui::Init,
+    BtToGui::AdapterConnected,
+    BtToGui::Found(Model::YowuSelkirk4),
+    BtToGui::Connected,
+    BtToGui::Ready,
+];
+
+// headset that walks through the states the real bt task reports
+fn connect(harness: &mut Harness) {
+    for status in CONNECTING {
+        let name = status.name();
+        harness.bt(status);
+        harness.snapshot(name);
+    }
+}
+
+// connected, in a theme, with a window open so there's more than buttons to look at
+fn themed(harness: &mut Harness, themes: Themes) {
+    harness.ui_state.themes = themes;
+
+    for status in CONNECTING {
+        harness.bt(status);
+    }
+
+    harness.settle();
+    harness.snapshot("connected");
+    harness.press(Modifiers::NONE, Key::P);
+    harness.snapshot("opened presets");
+}
+
+fn tui_connect(harness: &mut TuiHarness) {
+    for status in CONNECTING {
+        harness.bt(status);
+    }
+}
+
+type Scenario = (&'static str, fn(&mut Harness));
+type TuiScenario = (&'static str, fn(&mut TuiHarness));
+type DoctorScenario = (&'static str, fn(&mut FakeSystem));
+
+fn scenarios() -> Vec<Scenario> {
+    vec![
+        ("connect", connect),
+
+        ("mode_buttons", |h| {
+            connect(h);
+            h.click_text("Breath");
+            h.snapshot("clicked breath");
+            h.click_text("Lights off");
+            h.snapshot("clicked lights off");
+        }),
+
+        ("shortcuts", |h| {
+            connect(h);
+            h.press(Modifiers::NONE, Key::Num2);
+            h.snapshot("pressed 2");
+            h.press(Modifiers::NONE, Key::P);
+            h.snapshot("opened presets");
+            h.click_text("on air");
+            h.snapshot("applied on air");
+            h.press(Modifiers::NONE, Key::Escape);
+            h.snapshot("closed presets");
+        }),
+
+        ("palette", |h| {
+            connect(h);
+            h.press(Modifiers::COMMAND, Key::K);
+            h.snapshot("opened palette");
+            h.type_text("rhythm");
+            h.snapshot("typed rhythm");
+            h.press(Modifiers::NONE, Key::Enter);
+            h.snapshot("picked rhythm");
+        }),
+
+        ("brightness", |h| {
+            connect(h);
+
+            let Some((_, label)) = h.texts().into_iter().find(|(text, _)| text == "Brightness") else {
+                return h.snapshot("no brightness slider");
+            };
+
+            // the slider sits left of its label
+            let y = label.center().y;
+            h.drag(Pos2::new(20.0, y), Pos2::new(label.min.x - 60.0, y));
+            h.click(Pos2::new(label.max.x + 20.0, y)); //apply
+            h.snapshot("dragged brightness");
+        }),
+
+        // from the palette, as there's no shortcut for them by default
+        ("brightness_steps", |h| {
+            connect(h);
+
+            for action in ["brightness up", "brightness down", "brightness down"] {
+                h.press(Modifiers::COMMAND, Key::K);
+                h.type_text(action);
+                h.press(Modifiers::NONE, Key::Enter);
+                h.snapshot(action);
+            }
+        }),
+
+        ("theme_light", |h| themed(h, Themes::load_from(None, "light"))),
+        ("theme_dark", |h| themed(h, Themes::load_from(None, "dark"))),
+        ("theme_high_contrast", |h| themed(h, Themes::load_from(None, "high-contrast"))),
+        ("theme_user", |h| themed(h, Themes::load_from(Some(golden_dir().join("themes")), "mint"))),
+
+        ("disconnect", |h| {
+            connect(h);
+            h.bt(BtToGui::Init);
+            h.snapshot("lost the headset");
+            h.click_text("Breath"); //gone with the controls, nothing gets sent
+            h.snapshot("clicked where breath was");
+        }),
+    ]
+}
+
+fn tui_scenarios() -> Vec<TuiScenario> {
+    vec![
+        ("tui_connect", |h| {
+            for status in CONNECTING {
+                let name = status.name();
+                h.bt(status);
+                h.snapshot(name);
+            }
+        }),
+
+        ("tui_modes", |h| {
+            tui_connect(h);
+            h.key(KeyCode::Right);
+            h.key(KeyCode::Right);
+            h.key(KeyCode::Enter);
+            h.snapshot("picked breath");
+            h.key(KeyCode::Char('6'));
+            h.snapshot("pressed 6");
+        }),
+
+        ("tui_color", |h| {
+            tui_connect(h);
+            h.key(KeyCode::Tab);
+            h.keys("255");
+            h.key(KeyCode::Down);
+            h.keys("128");
+            h.key(KeyCode::Enter);
+            h.snapshot("typed rgb");
+            h.key(KeyCode::Char('h'));
+            h.snapshot("switched to hsv");
+            h.key(KeyCode::Down);
+            h.key(KeyCode::Down);
+            h.key_with(KeyModifiers::SHIFT, KeyCode::Left);
+            h.key(KeyCode::Enter);
+            h.snapshot("darker");
+        }),
+
+        ("tui_settings", |h| {
+            tui_connect(h);
+            h.key(KeyCode::Tab);
+            h.key(KeyCode::Tab);
+            h.key_with(KeyModifiers::SHIFT, KeyCode::Right);
+            h.key(KeyCode::Right);
+            h.key(KeyCode::Enter);
+            h.snapshot("brightness 9");
+            h.key(KeyCode::Tab);
+            h.key(KeyCode::Right);
+            h.key(KeyCode::Right);
+            h.key(KeyCode::Enter);
+            h.snapshot("audio profile 2");
+            h.key(KeyCode::Tab);
+            h.key(KeyCode::Enter);
+            h.snapshot("applied on air");
+        }),
+
+        ("tui_queue_full", |h| {
+            tui_connect(h);
+            h.key(KeyCode::Tab);
+            h.key(KeyCode::Tab);
+
+            for _ in 0 .. 17 {
+                h.key(KeyCode::Enter); //one more than the queue holds
+            }
+
+            h.snapshot("applied too often");
+        }),
+
+        ("tui_disconnect", |h| {
+            tui_connect(h);
+            h.bt(BtToGui::Init);
+            h.key(KeyCode::Char('3')); //nothing gets sent without a headset
+            h.snapshot("lost the headset");
+            h.key(KeyCode::Char('q'));
+            writeln!(h.log, "quit {}", h.tui.ui_state.quit).unwrap();
+        }),
+    ]
+}
+
+fn doctor_scenarios() -> Vec<DoctorScenario> {
+    vec![
+        ("doctor_healthy", |_| ()),
+
+        ("doctor_no_bus", |s| s.bluez = Err(BusError::NoBus(String::from("No such file or directory (os error 2)")))),
+
+        ("doctor_no_bluetoothd", |s| s.bluez = Err(BusError::NotRunning)),
+
+        ("doctor_denied", |s| {
+            s.bluez = Err(BusError::AccessDenied(String::from("Rejected send message")));
+            s.file("/proc/self/status", "Name:\tblatand\nGid:\t1000\t1000\t1000\t1000\nGroups:\t1000");
+        }),
+
+        ("doctor_soft_blocked", |s| {
+            s.rfkill("rfkill0", "hci0", "1", "0");
+            s.adapter(false);
+        }),
+
+        ("doctor_hard_blocked", |s| {
+            // a laptop switch that takes the adapter away entirely
+            s.files.retain(|path, _| !path.starts_with("/sys/class/bluetooth") && !path.starts_with("/sys/class/rfkill/rfkill0"));
+            s.rfkill("rfkill1", "tpacpi_bluetooth_sw", "0", "1");
+            s.bluez = Ok(Bluez::default());
+        }),
+
+        ("doctor_powered_off", |s| s.adapter(false)),
+
+        ("doctor_no_adapter", |s| {
+            s.files.retain(|path, _| !path.starts_with("/sys/class"));
+            s.bluez = Ok(Bluez::default());
+        }),
+
+        ("doctor_no_headset", |s| {
+            s.bluez = Ok(Bluez::default());
+            s.adapter(true);
+            s.device("Pixel 7", true, Some(-48));
+        }),
+
+        ("doctor_headset_seen", |s| {
+            s.bluez = Ok(Bluez::default());
+            s.adapter(true);
+            s.device("YOWU-SELKIRK-4", false, Some(-63));
+            s.device("YOWU-SELKIRK-4", false, None);
+            s.file("/sys/class/bluetooth/hci1/address", "00:1A:7D:DA:71:14");
+        }),
+    ]
+}
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("golden")
+}
+
+// compares each log with its golden file, or rewrites them with CATCALLER_BLESS=1
+fn check(logs: impl IntoIterator<Item = (&'static str, String)>) {
+    let bless = std::env::var("CATCALLER_BLESS").is_ok_and(|s| s == "1");
+    let mut failed = Vec::new();
+
+    for (name, log) in logs {
+        let path = golden_dir().join(format!("{name}.txt"));
+
+        if bless {
+            std::fs::write(&path, &log).unwrap_or_else(|e| panic!("can't write {}: {e}", path.display()));
+            continue;
+        }
+
+        let golden = std::fs::read_to_string(&path).unwrap_or_default();
+
+        match golden.lines().zip(log.lines()).position(|(expected, got)| expected != got) {
+            None if golden.lines().count() == log.lines().count() => (),
+            mismatch => {
+                let line = mismatch.unwrap_or_else(|| golden.lines().count().min(log.lines().count()));
+                println!("{name}: differs from {} at line {}", path.display(), line + 1);
+                println!("  expected: {}", golden.lines().nth(line).unwrap_or("<end>"));
+                println!("  got:      {}", log.lines().nth(line).unwrap_or("<end>"));
+                failed.push(name);
+            }
+        }
+    }
+
+    assert!(failed.is_empty(), "{failed:?} differ from golden/, CATCALLER_BLESS=1 cargo test rewrites them after an intended change");
+}
+
+#[test]
+fn gui() {
+    check(scenarios().into_iter().map(|(name, scenario)| {
+        let mut harness = Harness::new();
+        scenario(&mut harness);
+        (name, harness.log)
+    }));
+}
+
+// once connected and settled the gui doesn't ask for frames, so the event loop sleeps (see
+// frames.rs) until something happens
+#[test]
+fn idle_gui_sleeps() {
+    let mut harness = Harness::new();
+    connect(&mut harness);
+    harness.settle();
+    assert_eq!(harness.repaint_after, Duration::MAX);
+
+    // only while the preview animates
+    harness.click_text("Breath");
+    assert!(harness.repaint_after < Duration::from_millis(50));
+    harness.click_text("Lights off");
+    assert_eq!(harness.repaint_after, Duration::MAX);
+}
+
+#[test]
+fn tui() {
+    check(tui_scenarios().into_iter().map(|(name, scenario)| {
+        let mut harness = TuiHarness::new();
+        scenario(&mut harness);
+        (name, harness.log)
+    }));
+}
+
+#[test]
+fn doctor() {
+    // the checks only wait on the stand-in bluez, which answers right away
+    check(doctor_scenarios().into_iter().map(|(name, scenario)| {
+        let mut system = FakeSystem::healthy();
+        scenario(&mut system);
+        (name, doctor::format(&doctor::check(&system).now_or_never().unwrap()))
+    }));
+}
@@ -0,0 +1,365 @@
+// system wide hotkeys, the "global" entries of [keys]. x11 grabs the keys on the root window,
+// wayland goes through the GlobalShortcuts portal, which lets the desktop confirm or change them.
+// the tests try the x11 side under Xvfb with keys from xdotool
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use egui::{Key, KeyboardShortcut};
+use futures_util::StreamExt;
+use winit::event_loop::EventLoopProxy;
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::{ConnectionExt as _, GrabMode, ModMask};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{dbus_proxy, Connection};
+
+use crate::keys::Action;
+use crate::ui::GuiEvent;
+
+pub fn spawn(bindings: Vec<(KeyboardShortcut, Action)>, proxy: EventLoopProxy<GuiEvent>) {
+    if bindings.is_empty() {
+        return;
+    }
+
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        tokio::spawn(async move {
+            if let Err(e) = portal(bindings, proxy).await {
+                println!("global hotkeys: {e}");
+            }
+        });
+    } else {
+        std::thread::spawn(move || {
+            if let Err(e) = x11(bindings, None, |event| proxy.send_event(event).is_ok()) {
+                println!("global hotkeys: {e}");
+            }
+        });
+    }
+}
+
+// display is $DISPLAY when None. gui returns false once there's nobody to send to anymore
+fn x11(bindings: Vec<(KeyboardShortcut, Action)>, display: Option<&str>, gui: impl Fn(GuiEvent) -> bool) -> Result<(), Box<dyn Error>> {
+    let (conn, screen) = x11rb::connect(display)?;
+    let root = conn.setup().roots[screen].root;
+    let (min_keycode, max_keycode) = (conn.setup().min_keycode, conn.setup().max_keycode);
+
+    let mapping = conn.get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?.reply()?;
+    let keycode = |keysym: u32| mapping.keysyms
+        .chunks(mapping.keysyms_per_keycode as usize)
+        .position(|keysyms| keysyms.contains(&keysym))
+        .map(|idx| min_keycode + idx as u8);
+
+    let mut grabs = Vec::new();
+
+    for (shortcut, action) in bindings {
+        let Some(keycode) = keysym(shortcut.key).and_then(|(keysym, _)| keycode(keysym)) else {
+            println!("global hotkeys: {:?} isn't on the keyboard", shortcut.key);
+            continue;
+        };
+
+        let modifiers = x11_modifiers(&shortcut);
+
+        // grabs are per exact modifier state, so also grab with caps lock and num lock on
+        let grabbed = [ModMask::from(0u16), ModMask::LOCK, ModMask::M2, ModMask::LOCK | ModMask::M2]
+            .into_iter()
+            .try_for_each(|locks| conn.grab_key(true, root, modifiers | locks, keycode, GrabMode::ASYNC, GrabMode::ASYNC)?.check());
+
+        match grabbed {
+            Ok(_) => grabs.push((keycode, modifiers, action)),
+            Err(e) => println!("global hotkeys: can't grab the shortcut for {} ({e}), another program might have it", action.name()),
+        }
+    }
+
+    let relevant = u16::from(ModMask::CONTROL | ModMask::SHIFT | ModMask::M1);
+
+    loop {
+        let x11rb::protocol::Event::KeyPress(event) = conn.wait_for_event()? else {
+            continue;
+        };
+
+        let state = u16::from(event.state) & relevant;
+
+        for (_, _, action) in grabs.iter().filter(|(keycode, modifiers, _)| *keycode == event.detail && u16::from(*modifiers) == state) {
+            if !gui(GuiEvent::Action(action.clone())) {
+                return Ok(()); //event loop is gone
+            }
+        }
+    }
+}
+
+fn x11_modifiers(shortcut: &KeyboardShortcut) -> ModMask {
+    let mut modifiers = ModMask::from(0u16);
+
+    if shortcut.modifiers.ctrl || shortcut.modifiers.command {
+        modifiers |= ModMask::CONTROL;
+    }
+
+    if shortcut.modifiers.shift {
+        modifiers |= ModMask::SHIFT;
+    }
+
+    if shortcut.modifiers.alt {
+        modifiers |= ModMask::M1;
+    }
+
+    modifiers
+}
+
+// x11 keysym and its name, which the portal uses for triggers
+fn keysym(key: Key) -> Option<(u32, String)> {
+    let name = key.name();
+
+    // letters and digits are their ascii codes
+    if let [c] = name.as_bytes() {
+        let c = c.to_ascii_lowercase();
+        return Some((c as u32, (c as char).to_string()));
+    }
+
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+        return Some((0xffbe + n - 1, name.to_string()));
+    }
+
+    let (keysym, name) = match key {
+        Key::ArrowDown => (0xff54, "Down"),
+        Key::ArrowLeft => (0xff51, "Left"),
+        Key::ArrowRight => (0xff53, "Right"),
+        Key::ArrowUp => (0xff52, "Up"),
+        Key::Escape => (0xff1b, "Escape"),
+        Key::Tab => (0xff09, "Tab"),
+        Key::Backspace => (0xff08, "BackSpace"),
+        Key::Enter => (0xff0d, "Return"),
+        Key::Space => (0x20, "space"),
+        Key::Insert => (0xff63, "Insert"),
+        Key::Delete => (0xffff, "Delete"),
+        Key::Home => (0xff50, "Home"),
+        Key::End => (0xff57, "End"),
+        Key::PageUp => (0xff55, "Prior"),
+        Key::PageDown => (0xff56, "Next"),
+        Key::Minus => (0x2d, "minus"),
+        Key::PlusEquals => (0x3d, "equal"),
+        _ => return None,
+    };
+
+    Some((keysym, name.to_string()))
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.GlobalShortcuts",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait GlobalShortcuts {
+    fn create_session(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<OwnedObjectPath>;
+
+    fn bind_shortcuts(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        shortcuts: &[(&str, HashMap<&str, Value<'_>>)],
+        parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    #[dbus_proxy(signal)]
+    fn activated(&self, session_handle: ObjectPath<'_>, shortcut_id: &str, timestamp: u64, options: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(interface = "org.freedesktop.portal.Request", default_service = "org.freedesktop.portal.Desktop")]
+trait Request {
+    #[dbus_proxy(signal)]
+    fn response(&self, response: u32, results: HashMap<String, OwnedValue>) -> zbus::Result<()>;
+}
+
+async fn portal(bindings: Vec<(KeyboardShortcut, Action)>, proxy: EventLoopProxy<GuiEvent>) -> zbus::Result<()> {
+    let conn = Connection::session().await?;
+    let shortcuts_proxy = GlobalShortcutsProxy::new(&conn).await?;
+
+    // request and session paths are made from our bus name and a token of our choosing
+    let sender = conn.unique_name().map(|name| name.trim_start_matches(':').replace('.', "_")).unwrap_or_default();
+    let session = ObjectPath::try_from(format!("/org/freedesktop/portal/desktop/session/{sender}/catcaller"))?;
+
+    let options = HashMap::from([("handle_token", Value::from("catcaller_session")), ("session_handle_token", Value::from("catcaller"))]);
+    portal_request(&conn, &sender, "catcaller_session", shortcuts_proxy.create_session(options)).await?;
+
+    let ids: Vec<String> = bindings.iter().map(|(_, action)| action.name()).collect();
+    let shortcuts: Vec<(&str, HashMap<&str, Value>)> = bindings.iter().zip(&ids)
+        .map(|((shortcut, action), id)| {
+            let mut options = HashMap::from([("description", Value::from(action.label()))]);
+
+            if let Some(trigger) = portal_trigger(shortcut) {
+                options.insert("preferred_trigger", Value::from(trigger));
+            }
+
+            (id.as_str(), options)
+        })
+        .collect();
+
+    let options = HashMap::from([("handle_token", Value::from("catcaller_bind"))]);
+    portal_request(&conn, &sender, "catcaller_bind", shortcuts_proxy.bind_shortcuts(&session, &shortcuts, "", options)).await?;
+
+    let mut activated = shortcuts_proxy.receive_activated().await?;
+
+    while let Some(signal) = activated.next().await {
+        let args = signal.args()?;
+
+        if let Some((_, action)) = bindings.iter().find(|(_, action)| action.name() == args.shortcut_id) {
+            if proxy.send_event(GuiEvent::Action(action.clone())).is_err() {
+                break; //event loop is gone
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// portal calls answer with a Request object that signals the result later
+async fn portal_request(
+    conn: &Connection,
+    sender: &str,
+    token: &str,
+    call: impl std::future::Future<Output = zbus::Result<OwnedObjectPath>>,
+) -> zbus::Result<HashMap<String, OwnedValue>> {
+    let request = RequestProxy::builder(conn)
+        .path(format!("/org/freedesktop/portal/desktop/request/{sender}/{token}"))?
+        .build()
+        .await?;
+
+    let mut responses = request.receive_response().await?; //before the call, so the answer can't be missed
+    call.await?;
+
+    let response = responses.next().await.ok_or_else(|| zbus::Error::Failure(String::from("portal went away")))?;
+    let args = response.args()?;
+
+    match args.response {
+        0 => Ok(args.results),
+        1 => Err(zbus::Error::Failure(String::from("cancelled by the user"))),
+        _ => Err(zbus::Error::Failure(String::from("portal request failed"))),
+    }
+}
+
+// "CTRL+ALT+r", see the xdg shortcuts spec
+fn portal_trigger(shortcut: &KeyboardShortcut) -> Option<String> {
+    let mut trigger = String::new();
+
+    if shortcut.modifiers.ctrl || shortcut.modifiers.command {
+        trigger.push_str("CTRL+");
+    }
+
+    if shortcut.modifiers.alt {
+        trigger.push_str("ALT+");
+    }
+
+    if shortcut.modifiers.shift {
+        trigger.push_str("SHIFT+");
+    }
+
+    Some(trigger + keysym(shortcut.key)?.1.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::process::{Child, Command, Stdio};
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    use egui::Modifiers;
+
+    use super::*;
+
+    // an X server of our own, gone when dropped
+    struct Xvfb(Child);
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    // a missing tool skips the test, except under CI where that would only hide that it never ran
+    fn skip(tool: &str, e: std::io::Error) {
+        assert!(std::env::var_os("CI").is_none(), "no {tool} ({e}), and CI is set");
+        println!("no {tool} ({e}), skipping");
+    }
+
+    // None without Xvfb and xdotool to run
+    fn xvfb() -> Option<(Xvfb, String)> {
+        if let Err(e) = Command::new("xdotool").arg("version").stdout(Stdio::null()).status() {
+            skip("xdotool", e);
+            return None;
+        }
+
+        let display = format!(":{}", 100 + std::process::id() % 800);
+
+        let child = Command::new("Xvfb")
+            .args([display.as_str(), "-nolisten", "tcp"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        let xvfb = match child {
+            Ok(child) => Xvfb(child),
+            Err(e) => {
+                skip("Xvfb", e);
+                return None;
+            }
+        };
+
+        let start = Instant::now();
+
+        while !Path::new(&format!("/tmp/.X11-unix/X{}", &display[1 ..])).exists() {
+            assert!(start.elapsed() < Duration::from_secs(10), "Xvfb never came up");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        Some((xvfb, display))
+    }
+
+    fn xdotool(display: &str, keys: &str) {
+        let status = Command::new("xdotool").args(["key", keys]).env("DISPLAY", display).status().unwrap();
+        assert!(status.success(), "xdotool key {keys}");
+    }
+
+    #[test]
+    fn x11_grabs() {
+        let Some((_xvfb, display)) = xvfb() else { return };
+
+        let on_air = Action::Preset(String::from("on air"));
+        let bindings = vec![
+            (KeyboardShortcut::new(Modifiers::CTRL | Modifiers::ALT, Key::R), on_air.clone()),
+            (KeyboardShortcut::new(Modifiers::NONE, Key::F5), Action::BrightnessUp),
+        ];
+
+        let (tx, rx) = mpsc::channel();
+        let thread_display = display.clone();
+        std::thread::spawn(move || {
+            let _ = x11(bindings, Some(&thread_display), |event| tx.send(event).is_ok()); //errors out when Xvfb goes
+        });
+
+        // the grabs take a moment, press until the first one comes through
+        let start = Instant::now();
+
+        let first = loop {
+            xdotool(&display, "F5");
+
+            if let Ok(event) = rx.recv_timeout(Duration::from_millis(200)) {
+                break event;
+            }
+
+            assert!(start.elapsed() < Duration::from_secs(5), "F5 never arrived");
+        };
+
+        assert!(matches!(first, GuiEvent::Action(Action::BrightnessUp)), "{first:?}");
+        while rx.recv_timeout(Duration::from_millis(200)).is_ok() {} //presses that were still on the way
+
+        // without alt it isn't ours, with caps lock on it still is
+        xdotool(&display, "ctrl+r");
+        xdotool(&display, "ctrl+alt+r");
+        xdotool(&display, "Caps_Lock");
+        xdotool(&display, "ctrl+alt+r");
+
+        let events: Vec<_> = std::iter::from_fn(|| rx.recv_timeout(Duration::from_secs(2)).ok()).take(2).collect();
+        assert_eq!(events.len(), 2, "{events:?}");
+        assert!(events.iter().all(|event| matches!(event, GuiEvent::Action(action) if *action == on_air)), "{events:?}");
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err(), "ctrl + r isn't grabbed");
+    }
+}
@@ -0,0 +1,296 @@
+// daemon <-> client protocol. one json message per line over a unix socket.
+// the client opens with Hello, the daemon answers with its own Hello (or an Error if the
+// versions don't match) followed by the current State. after that the daemon pushes a
+// State whenever something changes, and answers every command with Done or Error.
+
+use std::fs::DirBuilder;
+use std::future::Future;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines, ReadHalf, WriteHalf};
+use tokio::net::UnixStream;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, Duration};
+
+use crate::bt::{BtCommands, BtToGui, CmdData, HeadsetState};
+
+pub const VERSION: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Hello { version: u32 },
+    SetMode(CmdData),
+    SetAudioProfile { profile: u8 },
+    ApplyPreset { name: String },
+    Event { name: String }, //on_event in scripts
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    Hello { version: u32 },
+    State(HeadsetState),
+    Done,
+    Error { message: String },
+}
+
+// XDG_RUNTIME_DIR is ours alone already. without it the socket goes in a directory of our own in
+// /tmp, where anyone could have made it first to listen in or hand out a fake daemon
+pub fn socket_path() -> io::Result<PathBuf> {
+    match dirs::runtime_dir() {
+        Some(dir) => Ok(dir.join("catcaller.sock")),
+        None => {
+            let uid = unsafe { libc::geteuid() };
+            Ok(private_dir(&std::env::temp_dir().join(format!("catcaller-{uid}")))?.join("catcaller.sock"))
+        }
+    }
+}
+
+// made if it isn't there, and refused unless it's a directory only we can get into
+fn private_dir(dir: &Path) -> io::Result<&Path> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => (),
+    }
+
+    let metadata = std::fs::symlink_metadata(dir)?;
+    let refuse = |why: &str| Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} {why}", dir.display())));
+
+    if !metadata.is_dir() {
+        return refuse("isn't a directory");
+    }
+
+    if metadata.uid() != unsafe { libc::geteuid() } {
+        return refuse("belongs to someone else");
+    }
+
+    if metadata.mode() & 0o077 != 0 {
+        return refuse("is open to other users");
+    }
+
+    Ok(dir)
+}
+
+pub struct Connection<S> {
+    pub reader: Lines<BufReader<ReadHalf<S>>>,
+    pub writer: WriteHalf<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite> Connection<S> {
+    pub fn new(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self { reader: BufReader::new(reader).lines(), writer }
+    }
+
+    // the client's side of the handshake
+    pub async fn handshake(stream: S) -> io::Result<Self> {
+        let mut conn = Self::new(stream);
+        write_message(&mut conn.writer, &Request::Hello { version: VERSION }).await?;
+
+        match read_message(&mut conn.reader).await? {
+            Some(Reply::Hello { .. }) => Ok(conn),
+            Some(Reply::Error { message }) => Err(io::Error::other(message)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected handshake reply")),
+        }
+    }
+}
+
+impl Connection<UnixStream> {
+    // connect to a running daemon and do the handshake
+    pub async fn connect() -> io::Result<Self> {
+        Self::handshake(UnixStream::connect(socket_path()?).await?).await
+    }
+}
+
+pub async fn read_message<R, T>(reader: &mut Lines<BufReader<R>>) -> io::Result<Option<T>>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    match reader.next_line().await? {
+        Some(line) => serde_json::from_str(&line).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        None => Ok(None),
+    }
+}
+
+pub async fn write_message<W, T>(writer: &mut W, message: &T) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await
+}
+
+impl From<BtCommands> for Request {
+    fn from(command: BtCommands) -> Self {
+        match command {
+            BtCommands::SetMode(data) => Request::SetMode(data),
+            BtCommands::SetAudioProfile(profile) => Request::SetAudioProfile { profile },
+        }
+    }
+}
+
+// stands in for bt_stuff when the gui is a client of the daemon. keeps trying to reconnect
+// if the daemon goes away
+pub async fn bridge<S, F, Fut>(mut conn: Connection<S>, mut reconnect: F, mut rx: mpsc::Receiver<BtCommands>, gui_tx: mpsc::Sender<BtToGui>, state_tx: watch::Sender<HeadsetState>)
+where
+    S: AsyncRead + AsyncWrite,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<Connection<S>>>,
+{
+    loop {
+        match forward(&mut conn, &mut rx, &gui_tx, &state_tx).await {
+            Ok(_) => return, //gui is gone
+            Err(e) => println!("lost connection to the daemon: {e}"),
+        }
+
+        state_tx.send_replace(HeadsetState::default());
+
+        if gui_tx.send(BtToGui::Init).await.is_err() {
+            return;
+        }
+
+        conn = loop {
+            sleep(Duration::from_secs(2)).await;
+
+            if let Ok(conn) = reconnect().await {
+                break conn;
+            }
+        };
+    }
+}
+
+async fn forward<S: AsyncRead + AsyncWrite>(conn: &mut Connection<S>, rx: &mut mpsc::Receiver<BtCommands>, gui_tx: &mpsc::Sender<BtToGui>, state_tx: &watch::Sender<HeadsetState>) -> io::Result<()> {
+    let mut last_status = None;
+
+    loop {
+        tokio::select! {
+            command = rx.recv() => {
+                let Some(command) = command else { return Ok(()) };
+                write_message(&mut conn.writer, &Request::from(command)).await?;
+            }
+
+            reply = read_message(&mut conn.reader) => match reply? {
+                Some(Reply::State(state)) => {
+                    state_tx.send_replace(state.clone());
+
+                    if last_status != Some(state.status.name()) {
+                        // the gui picks up the headset name from Found, which it misses when
+                        // joining a daemon that's further along
+                        if let (None, Some(headset)) = (last_status, state.headset) {
+                            if !matches!(state.status, BtToGui::Init | BtToGui::AdapterConnected | BtToGui::Found(_))
+                                && gui_tx.send(BtToGui::Found(headset)).await.is_err() {
+                                return Ok(());
+                            }
+                        }
+
+                        last_status = Some(state.status.name());
+
+                        if gui_tx.send(state.status).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                }
+
+                Some(Reply::Error { message }) => println!("daemon: {message}"),
+                Some(_) => (),
+                None => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "daemon closed the connection")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use catcaller::Model;
+    use tokio::io::{duplex, DuplexStream};
+    use tokio::task::JoinHandle;
+
+    use super::*;
+    use crate::daemon::serve_client;
+
+    // hands out connections to a stand-in daemon, keeping its side of each one so the test can
+    // hang up
+    #[derive(Clone)]
+    struct Daemon {
+        tx: mpsc::Sender<BtCommands>,
+        state_rx: watch::Receiver<HeadsetState>,
+        clients: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    }
+
+    impl Daemon {
+        async fn connect(self) -> io::Result<Connection<DuplexStream>> {
+            let (client, server) = duplex(4096);
+            let handle = tokio::spawn(serve_client(Connection::new(server), self.tx.clone(), self.state_rx.clone(), None, Vec::new()));
+            self.clients.lock().unwrap().push(handle);
+            Connection::handshake(client).await
+        }
+
+        fn hang_up(&self) {
+            self.clients.lock().unwrap().drain(..).for_each(|client| client.abort());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bridge_reconnects() {
+        let ready = HeadsetState { status: BtToGui::Ready, headset: Some(Model::YowuSelkirk4), ..Default::default() };
+        let (tx, mut daemon_rx) = mpsc::channel(4);
+        let (_daemon_state, state_rx) = watch::channel(ready);
+        let daemon = Daemon { tx, state_rx, clients: Default::default() };
+
+        let (gui_tx, mut gui_rx) = mpsc::channel(4);
+        let (state_tx, mut state) = watch::channel(HeadsetState::default());
+        let (tx, rx) = mpsc::channel(4);
+
+        let conn = daemon.clone().connect().await.unwrap();
+        let reconnect = { let daemon = daemon.clone(); move || daemon.clone().connect() };
+        tokio::spawn(bridge(conn, reconnect, rx, gui_tx, state_tx));
+
+        // joining a daemon that's already connected still tells the gui which headset it is
+        for _ in 0 .. 2 {
+            assert!(matches!(gui_rx.recv().await, Some(BtToGui::Found(Model::YowuSelkirk4))));
+            assert!(matches!(gui_rx.recv().await, Some(BtToGui::Ready)));
+            assert!(matches!(state.borrow_and_update().status, BtToGui::Ready));
+
+            tx.send(BtCommands::SetAudioProfile(3)).await.unwrap();
+            assert!(matches!(daemon_rx.recv().await, Some(BtCommands::SetAudioProfile(3))));
+
+            daemon.hang_up();
+
+            assert!(matches!(gui_rx.recv().await, Some(BtToGui::Init)));
+            assert!(matches!(state.borrow_and_update().status, BtToGui::Init), "the state is forgotten until it's back");
+        }
+    }
+
+    #[test]
+    fn socket_dir_is_private() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let base = std::env::temp_dir().join(format!("catcaller-ipc-test-{}", std::process::id()));
+        let dir = base.join("ours");
+        std::fs::create_dir_all(&base).unwrap();
+
+        assert_eq!(private_dir(&dir).unwrap(), dir);
+        assert_eq!(std::fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+        assert!(private_dir(&dir).is_ok(), "and it's still fine the second time");
+
+        // someone else made it first: open to others, or a link to somewhere else
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(private_dir(&dir).unwrap_err().to_string().ends_with("is open to other users"));
+
+        let link = base.join("link");
+        symlink(&dir, &link).unwrap();
+        assert!(private_dir(&link).unwrap_err().to_string().ends_with("isn't a directory"));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
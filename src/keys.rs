@@ -0,0 +1,203 @@
+// keyboard shortcuts. the defaults below can be changed in the [keys] table of the config file,
+// action name = shortcut, or "" to unbind:
+//   [keys]
+//   palette = "Ctrl+Shift+P"
+//   "mode 6" = "O"
+//   "preset on air" = "F1"
+// shortcuts starting with "global" work while the window is unfocused or hidden (linux, see hotkeys.rs):
+//   "preset on air" = "global Ctrl+Alt+R"
+
+use std::collections::HashMap;
+
+use egui::{Key, KeyboardShortcut, Modifiers};
+
+use crate::bt::MODES;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Mode(u8), //1-8, like the mode buttons
+    Preset(String),
+    ApplyColor,
+    BrightnessUp,
+    BrightnessDown,
+    Presets, //open the presets window
+    Doctor, //open the bluetooth diagnostics
+    Theme(String), //or theme::AUTO
+    Palette,
+    Quit,
+}
+
+impl Action {
+    // what the command palette shows
+    pub fn label(&self) -> String {
+        match self {
+            Action::Mode(mode) => format!("Mode: {}", MODES[*mode as usize - 1]),
+            Action::Preset(name) => format!("Preset: {name}"),
+            Action::ApplyColor => String::from("Apply color"),
+            Action::BrightnessUp => String::from("Brightness up"),
+            Action::BrightnessDown => String::from("Brightness down"),
+            Action::Presets => String::from("Open presets"),
+            Action::Doctor => String::from("Bluetooth diagnostics"),
+            Action::Theme(name) => format!("Theme: {name}"),
+            Action::Palette => String::from("Command palette"),
+            Action::Quit => String::from("Quit"),
+        }
+    }
+
+    // as written in the config file
+    pub fn name(&self) -> String {
+        match self {
+            Action::Mode(mode) => format!("mode {mode}"),
+            Action::Preset(name) => format!("preset {name}"),
+            Action::ApplyColor => String::from("apply color"),
+            Action::BrightnessUp => String::from("brightness up"),
+            Action::BrightnessDown => String::from("brightness down"),
+            Action::Presets => String::from("presets"),
+            Action::Doctor => String::from("doctor"),
+            Action::Theme(name) => format!("theme {name}"),
+            Action::Palette => String::from("palette"),
+            Action::Quit => String::from("quit"),
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        let name = name.trim();
+
+        if let Some(preset) = name.strip_prefix("preset ") {
+            return Some(Action::Preset(preset.trim().to_string()));
+        }
+
+        if let Some(theme) = name.strip_prefix("theme ") {
+            return Some(Action::Theme(theme.trim().to_string()));
+        }
+
+        Some(match name.to_ascii_lowercase().as_str() {
+            "apply color" => Action::ApplyColor,
+            "brightness up" => Action::BrightnessUp,
+            "brightness down" => Action::BrightnessDown,
+            "lights off" => Action::Mode(6),
+            "presets" => Action::Presets,
+            "doctor" => Action::Doctor,
+            "palette" => Action::Palette,
+            "quit" => Action::Quit,
+            other => {
+                let mode = other.strip_prefix("mode ")?.trim().parse().ok().filter(|m| (1 ..= 8).contains(m))?;
+                Action::Mode(mode)
+            }
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct Bindings {
+    bindings: Vec<(KeyboardShortcut, Action)>,
+    global: Vec<(KeyboardShortcut, Action)>,
+}
+
+impl Bindings {
+    pub fn new(config: &HashMap<String, String>) -> Self {
+        const NUMBERS: [Key; 8] = [Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8];
+
+        let mut bindings: Vec<_> = (1 ..= 8)
+            .map(|mode| (KeyboardShortcut::new(Modifiers::NONE, NUMBERS[mode as usize - 1]), Action::Mode(mode)))
+            .collect();
+
+        bindings.push((KeyboardShortcut::new(Modifiers::NONE, Key::P), Action::Presets));
+        bindings.push((KeyboardShortcut::new(Modifiers::COMMAND, Key::K), Action::Palette));
+        bindings.push((KeyboardShortcut::new(Modifiers::COMMAND, Key::Q), Action::Quit));
+
+        let mut global = Vec::new();
+
+        for (name, shortcut) in config {
+            let Some(action) = Action::parse(name) else {
+                println!("[keys]: unknown action \"{name}\"");
+                continue;
+            };
+
+            let (bindings, shortcut) = match shortcut.trim().strip_prefix("global ") {
+                Some(shortcut) => (&mut global, shortcut),
+                None => (&mut bindings, shortcut.as_str()),
+            };
+
+            bindings.retain(|(_, bound)| *bound != action);
+
+            if shortcut.trim().is_empty() {
+                continue; //unbound
+            }
+
+            match parse_shortcut(shortcut) {
+                Some(shortcut) => {
+                    bindings.retain(|(bound, _)| *bound != shortcut); //one action per shortcut
+                    bindings.push((shortcut, action));
+                }
+
+                None => println!("[keys]: can't parse \"{shortcut}\" for {name}"),
+            }
+        }
+
+        Self { bindings, global }
+    }
+
+    pub fn shortcut(&self, action: &Action) -> Option<&KeyboardShortcut> {
+        self.bindings.iter().find(|(_, bound)| bound == action).map(|(shortcut, _)| shortcut)
+    }
+
+    pub fn global(&self) -> &[(KeyboardShortcut, Action)] {
+        &self.global
+    }
+
+    // consumes the key presses of every triggered binding
+    pub fn pressed(&self, ctx: &egui::Context) -> Vec<Action> {
+        if ctx.wants_keyboard_input() {
+            return Vec::new(); //typing into a text field
+        }
+
+        ctx.input_mut(|input| {
+            self.bindings
+                .iter()
+                .filter(|(shortcut, _)| input.consume_shortcut(shortcut))
+                .map(|(_, action)| action.clone())
+                .collect()
+        })
+    }
+}
+
+// "Ctrl+Shift+K", "F1", "5". Ctrl is Cmd on macOS
+fn parse_shortcut(text: &str) -> Option<KeyboardShortcut> {
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let key = parts.pop()?;
+    let mut modifiers = Modifiers::NONE;
+
+    for part in parts {
+        modifiers = modifiers | match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "cmd" | "command" => Modifiers::COMMAND,
+            "shift" => Modifiers::SHIFT,
+            "alt" | "option" => Modifiers::ALT,
+            _ => return None,
+        };
+    }
+
+    Some(KeyboardShortcut::new(modifiers, parse_key(key)?))
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    use Key::*;
+
+    const KEYS: [Key; 65] = [
+        ArrowDown, ArrowLeft, ArrowRight, ArrowUp, Escape, Tab, Backspace, Enter, Space,
+        Insert, Delete, Home, End, PageUp, PageDown, Minus, PlusEquals,
+        Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9,
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    ];
+
+    let name = match name.to_ascii_lowercase().as_str() {
+        "esc" => "escape",
+        "return" => "enter",
+        "-" => "minus",
+        "=" => "plus",
+        _ => name,
+    }.to_string();
+
+    KEYS.into_iter().find(|key| key.name().eq_ignore_ascii_case(&name))
+}
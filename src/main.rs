@@ -1,59 +1,180 @@
 #![windows_subsystem = "windows"]
 
-const TIME: u64 = 25000;
-
 mod graphics;
+mod frames;
 mod bt;
 mod ui;
+mod preview;
+mod keys;
+mod palette;
+mod theme;
+#[cfg(test)]
+mod harness;
+mod tui;
+mod config;
+mod openrgb;
+mod dmx;
+mod osc;
+mod web;
+mod scripting;
+mod safety;
+mod doctor;
+#[cfg(target_os = "linux")]
+mod dbus_service;
+#[cfg(target_os = "linux")]
+mod tray;
+#[cfg(target_os = "linux")]
+mod hotkeys;
+#[cfg(target_os = "linux")]
+mod color_scheme;
+mod backend;
+#[cfg(unix)]
+mod ipc;
+#[cfg(unix)]
+mod daemon;
+#[cfg(unix)]
+mod cli;
+#[cfg(unix)]
+mod capture;
+
+use std::time::Instant;
 
 use tokio::sync::mpsc;
-use ui::{UiState, set_egui_visuals};
-use winit::event_loop::{EventLoop, ControlFlow};
-use bt::{bt_stuff, BtToGui};
+use ui::{GuiEvent, UiState};
+use winit::{event::{Event, WindowEvent}, event_loop::{EventLoopBuilder, ControlFlow}};
 
 #[tokio::main]
 async fn main() {
-    let el = EventLoop::new();
-    let mut graphics_state = graphics::Graphics::setup(&el, (400, 400));
-    set_egui_visuals(&mut graphics_state.egui_state.ctx);
+    let config = config::Config::load();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("tui") => return tui::run(config).await,
+        Some("doctor") => std::process::exit(doctor::run_cli().await),
+        #[cfg(unix)]
+        Some("daemon") => return daemon::run(config).await,
+        Some("--minimized") | None => (),
+        #[cfg(unix)]
+        Some(_) => std::process::exit(cli::run(&args).await),
+        #[cfg(not(unix))]
+        Some(_) => (),
+    }
+
+    // closing the window only hides it while there's a tray icon to bring it back
+    let mut tray_active = cfg!(target_os = "linux") && config.gui.tray;
+    let minimized = tray_active && (config.gui.start_minimized || args.iter().any(|arg| arg == "--minimized"));
+    let mut window_visible = !minimized;
 
-    let mut ui_state = UiState::default();
+    let el = EventLoopBuilder::<GuiEvent>::with_user_event().build();
+    let mut graphics_state = graphics::Graphics::setup(&el, (400, 400), window_visible);
+    graphics_state.set_zoom(config.gui.zoom);
+
+    let mut ui_state = UiState {
+        preview: preview::Preview::new(graphics_state.renderer.supports_callbacks()),
+        presets: config.presets.clone(),
+        bindings: keys::Bindings::new(&config.keys),
+        themes: theme::Themes::load(&config.gui.theme),
+        unsafe_flashing: config.safety.allow_unsafe_flashing,
+        ..Default::default()
+    };
 
-    let (tx, mut rx) = mpsc::channel(4);
     let (tx2, mut rx2) = mpsc::channel(4);
+    let backend = backend::start(&config, tx2).await;
+    let tx = backend.tx.clone();
+
+    #[cfg(target_os = "linux")]
+    if tray_active {
+        tokio::spawn(tray::serve(config.presets.clone(), backend.tx, backend.state_rx, el.create_proxy()));
+    }
+
+    #[cfg(target_os = "linux")]
+    hotkeys::spawn(ui_state.bindings.global().to_vec(), el.create_proxy());
+
+    #[cfg(target_os = "linux")]
+    tokio::spawn(color_scheme::follow(el.create_proxy()));
 
+    // bt messages wake the event loop up instead of it polling for them
+    let proxy = el.create_proxy();
     tokio::spawn(async move {
-        match bt_stuff(&mut rx, &tx2).await {
-            Ok(_) => (),
-            Err(e) => println!("error! {e}"),
-        };
+        while let Some(bt_recv) = rx2.recv().await {
+            if proxy.send_event(GuiEvent::Bt(bt_recv)).is_err() {
+                break; //event loop is gone
+            }
+        }
     });
 
-    let mut last_time = std::time::Instant::now();
-    let mut frame_time = std::time::Duration::new(0, 0);
+    let start_time = Instant::now();
+    let mut frames = frames::Frames::new(start_time);
 
     el.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::WaitUntil(std::time::Instant::now() + std::time::Duration::from_millis(3));
+        match event {
+            // all queued messages get handled before MainEventsCleared, so a burst ends up in one frame
+            Event::UserEvent(GuiEvent::Bt(bt_recv)) => {
+                ui_state.handle_bt(bt_recv);
+                frames.changed(Instant::now());
+            }
+
+            Event::UserEvent(GuiEvent::Action(action)) => {
+                ui::run_action(action, &tx, &mut ui_state);
+                frames.changed(Instant::now());
+            }
+
+            Event::UserEvent(GuiEvent::SystemDark(dark)) => {
+                ui_state.themes.set_system_dark(dark);
+                frames.changed(Instant::now());
+            }
 
-        let current_time = std::time::Instant::now();
-        frame_time += current_time - last_time;
-        last_time = current_time;
+            Event::UserEvent(GuiEvent::ToggleWindow) => {
+                window_visible = !window_visible;
+                graphics_state.window.set_visible(window_visible);
+                frames.changed(Instant::now());
+            }
+
+            Event::UserEvent(GuiEvent::TrayGone) => {
+                tray_active = false;
+                window_visible = true;
+                graphics_state.window.set_visible(true);
+                frames.changed(Instant::now());
+            }
 
-        graphics::event_handling(event, control_flow, &mut graphics_state);
+            Event::UserEvent(GuiEvent::Quit) => *control_flow = ControlFlow::Exit,
 
-        while frame_time >= std::time::Duration::from_micros(TIME) {
-            frame_time -= std::time::Duration::from_micros(TIME);
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } if tray_active => {
+                window_visible = false;
+                graphics_state.window.set_visible(false);
+            }
 
-            if let Ok(bt_recv) = rx2.try_recv() {
-                ui_state.bt_state = bt_recv;
-                if let BtToGui::Found(asd) = &ui_state.bt_state {
-                    ui_state.headset_type = asd.name();
+            Event::MainEventsCleared => {
+                if window_visible && frames.due(Instant::now()) {
+                    graphics_state.window.request_redraw();
                 }
             }
 
-            graphics_state.egui_state.ctx.begin_frame(graphics_state.egui_state.raw_input.take());
-            ui::create_ui(&mut graphics_state.egui_state.ctx, &tx, &mut ui_state);
-            graphics_state.paint();
+            Event::RedrawRequested(_) => {
+                let now = Instant::now();
+
+                graphics_state.begin_frame(now.duration_since(start_time).as_secs_f64());
+                ui::create_ui(&mut graphics_state.egui_state.ctx, &tx, &mut ui_state);
+                let repaint_after = graphics_state.paint();
+
+                frames.painted(now, repaint_after);
+            }
+
+            event => {
+                if matches!(event, Event::WindowEvent{..}) {
+                    frames.changed(Instant::now());
+                }
+
+                graphics::event_handling(event, control_flow, &mut graphics_state);
+            }
+        }
+
+        if ui_state.quit {
+            *control_flow = ControlFlow::Exit;
+        }
+
+        if !matches!(control_flow, ControlFlow::ExitWithCode(_)) {
+            *control_flow = frames.control_flow();
         }
     });
 }
@@ -0,0 +1,554 @@
+// OpenRGB SDK client. connects to a running OpenRGB server and mirrors the color of one
+// of its LEDs onto the headset.
+// protocol: https://gitlab.com/CalcProgrammer1/OpenRGB/-/wikis/OpenRGB-SDK-Documentation
+
+use std::error::Error;
+
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, Duration};
+
+use crate::bt::{BtCommands, CmdData, HeadsetState};
+
+const REQUEST_CONTROLLER_COUNT: u32 = 0;
+const REQUEST_CONTROLLER_DATA: u32 = 1;
+const SET_CLIENT_NAME: u32 = 50;
+const DEVICE_LIST_UPDATED: u32 = 100;
+
+const MAX_PACKET: u32 = 4 << 20; //controller data for thousands of leds is still well under this
+
+const LIGHTS_OFF: u8 = 6;
+const LIGHTS_ON: u8 = 7;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OpenRgbConfig {
+    pub host: String,
+    pub port: u16,
+    pub device: String, //matched against the controller name, case insensitive
+    pub led: usize,
+    pub interval_ms: u64, //poll rate, also the max rate we send colors to the headset at
+}
+
+impl Default for OpenRgbConfig {
+    fn default() -> Self {
+        Self {
+            host: String::from("127.0.0.1"),
+            port: 6742,
+            device: String::new(),
+            led: 0,
+            interval_ms: 100,
+        }
+    }
+}
+
+pub async fn mirror(config: OpenRgbConfig, tx: mpsc::Sender<BtCommands>, state_rx: watch::Receiver<HeadsetState>) {
+    loop {
+        if let Err(e) = mirror_session(&config, &tx, &state_rx).await {
+            println!("openrgb: {e}");
+        }
+
+        if tx.is_closed() {
+            return;
+        }
+
+        sleep(Duration::from_secs(3)).await; //server gone, try again later
+    }
+}
+
+async fn mirror_session(config: &OpenRgbConfig, tx: &mpsc::Sender<BtCommands>, state_rx: &watch::Receiver<HeadsetState>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut client = Client::connect(&config.host, config.port).await?;
+    let mut device_idx = None;
+    let mut last_rgb = None;
+    let mut mode_before_off = None; //while the led is black
+    let interval = Duration::from_millis(config.interval_ms.max(20));
+
+    loop {
+        if client.list_updated {
+            client.list_updated = false;
+            device_idx = None;
+        }
+
+        let idx = match device_idx {
+            Some(idx) => idx,
+            None => match client.find_device(&config.device).await? {
+                Some(idx) => *device_idx.insert(idx),
+                None => {
+                    println!("openrgb: no device matching \"{}\"", config.device);
+                    sleep(Duration::from_secs(3)).await;
+                    continue;
+                }
+            },
+        };
+
+        let controller = client.controller(idx).await?;
+
+        if client.list_updated {
+            continue; //idx might be another device by now
+        }
+
+        if let Some(&rgb) = controller.colors.get(config.led) {
+            if last_rgb != Some(rgb) {
+                // an rgb of 0, 0, 0 would leave the color as it is, so black is the lights off mode
+                // and the led lighting up again brings back the mode from before
+                let data = match rgb {
+                    [0, 0, 0] => {
+                        let shown = state_rx.borrow().lighting.map_or(0, |lighting| lighting.mode);
+                        mode_before_off.get_or_insert(if shown == 0 || shown == LIGHTS_OFF { LIGHTS_ON } else { shown });
+                        CmdData { mode: LIGHTS_OFF, ..Default::default() }
+                    }
+
+                    _ => CmdData { mode: mode_before_off.take().unwrap_or(0), rgb, ..Default::default() },
+                };
+
+                if tx.send(BtCommands::SetMode(data)).await.is_err() {
+                    return Ok(()); //bt task is gone
+                }
+
+                last_rgb = Some(rgb);
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
+
+pub struct Controller {
+    pub name: String,
+    pub colors: Vec<[u8; 3]>,
+}
+
+struct Client {
+    stream: TcpStream,
+    list_updated: bool,
+}
+
+impl Client {
+    async fn connect(host: &str, port: u16) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let stream = TcpStream::connect((host, port)).await?;
+        let mut client = Self { stream, list_updated: false };
+        client.send(0, SET_CLIENT_NAME, b"Yowu CatCaller\0").await?;
+
+        Ok(client)
+    }
+
+    async fn find_device(&mut self, name: &str) -> Result<Option<u32>, Box<dyn Error + Send + Sync>> {
+        let reply = self.request(0, REQUEST_CONTROLLER_COUNT, &[]).await?;
+        let count = Reader::new(&reply).u32()?;
+        let name = name.to_lowercase();
+
+        for idx in 0 .. count {
+            if self.controller(idx).await?.name.to_lowercase().contains(&name) {
+                return Ok(Some(idx));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn controller(&mut self, idx: u32) -> Result<Controller, Box<dyn Error + Send + Sync>> {
+        let reply = self.request(idx, REQUEST_CONTROLLER_DATA, &[]).await?;
+        Ok(parse_controller(&reply)?)
+    }
+
+    async fn request(&mut self, device: u32, id: u32, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        self.send(device, id, data).await?;
+
+        loop {
+            let (reply_id, reply) = self.recv().await?;
+
+            match reply_id {
+                DEVICE_LIST_UPDATED => self.list_updated = true,
+                _ if reply_id == id => return Ok(reply),
+                _ => (),
+            }
+        }
+    }
+
+    async fn send(&mut self, device: u32, id: u32, data: &[u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut packet = Vec::with_capacity(16 + data.len());
+        packet.extend_from_slice(b"ORGB");
+        packet.extend_from_slice(&device.to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        packet.extend_from_slice(data);
+
+        self.stream.write_all(&packet).await?;
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Result<(u32, Vec<u8>), Box<dyn Error + Send + Sync>> {
+        let mut header = [0; 16];
+        self.stream.read_exact(&mut header).await?;
+
+        if &header[0 .. 4] != b"ORGB" {
+            return Err("bad packet header".into());
+        }
+
+        let id = u32::from_le_bytes(header[8 .. 12].try_into().unwrap());
+        let size = u32::from_le_bytes(header[12 .. 16].try_into().unwrap());
+
+        if size > MAX_PACKET {
+            return Err(format!("packet of {size} bytes, that's no OpenRGB server").into());
+        }
+
+        let mut data = vec![0; size as usize];
+        self.stream.read_exact(&mut data).await?;
+
+        Ok((id, data))
+    }
+}
+
+// controller data as sent for protocol version 0, which is what the server assumes
+// when the client never asks for anything newer
+pub fn parse_controller(data: &[u8]) -> Result<Controller, &'static str> {
+    let mut r = Reader::new(data);
+
+    r.u32()?; //data size
+    r.u32()?; //device type
+    let name = r.string()?;
+    for _ in 0 .. 4 { r.string()?; } //description, version, serial, location
+
+    let num_modes = r.u16()?;
+    r.u32()?; //active mode
+
+    for _ in 0 .. num_modes {
+        r.string()?; //name
+        r.skip(4 * 9)?; //value, flags, speed min/max, colors min/max, speed, direction, color mode
+        let num_colors = r.u16()?;
+        r.skip(num_colors as usize * 4)?;
+    }
+
+    let num_zones = r.u16()?;
+
+    for _ in 0 .. num_zones {
+        r.string()?; //name
+        r.skip(4 * 4)?; //type, leds min/max/count
+        let matrix_len = r.u16()?;
+        r.skip(matrix_len as usize)?;
+    }
+
+    let num_leds = r.u16()?;
+
+    for _ in 0 .. num_leds {
+        r.string()?; //name
+        r.u32()?; //value
+    }
+
+    let num_colors = r.u16()?;
+    let mut colors = Vec::with_capacity(num_colors as usize);
+
+    for _ in 0 .. num_colors {
+        let [red, green, blue, _] = r.u32()?.to_le_bytes();
+        colors.push([red, green, blue]);
+    }
+
+    Ok(Controller { name, colors })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if self.data.len() < len {
+            return Err("controller data too short");
+        }
+
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), &'static str> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, &'static str> {
+        let len = self.u16()? as usize;
+        let bytes = self.take(len)?;
+        let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes); //null terminated
+
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::net::TcpListener;
+    use tokio::time::{timeout, Instant};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Server {
+        devices: Vec<(&'static str, Vec<[u8; 3]>)>,
+        list_updated: bool, //sent before the next reply
+        requests: Vec<(Instant, u32)>,
+    }
+
+    fn string(data: &mut Vec<u8>, s: &str) {
+        data.extend_from_slice(&(s.len() as u16 + 1).to_le_bytes());
+        data.extend_from_slice(s.as_bytes());
+        data.push(0);
+    }
+
+    // protocol version 0, no modes or zones
+    fn controller_data(name: &str, colors: &[[u8; 3]]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&4u32.to_le_bytes()); //keyboard
+
+        for s in [name, "", "1.0", "", "HID: /dev/hidraw3"] {
+            string(&mut data, s);
+        }
+
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&(colors.len() as u16).to_le_bytes());
+
+        for i in 0 .. colors.len() {
+            string(&mut data, &format!("Key {i}"));
+            data.extend_from_slice(&0u32.to_le_bytes());
+        }
+
+        data.extend_from_slice(&(colors.len() as u16).to_le_bytes());
+
+        for &[r, g, b] in colors {
+            data.extend_from_slice(&[r, g, b, 0]);
+        }
+
+        let size = data.len() as u32 + 4;
+        [size.to_le_bytes().to_vec(), data].concat()
+    }
+
+    fn packet(device: u32, id: u32, data: &[u8]) -> Vec<u8> {
+        [b"ORGB".as_slice(), &device.to_le_bytes(), &id.to_le_bytes(), &(data.len() as u32).to_le_bytes(), data].concat()
+    }
+
+    // an OpenRGB server on localhost, for one client
+    async fn stand_in(server: Arc<Mutex<Server>>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut header = [0; 16];
+
+            while stream.read_exact(&mut header).await.is_ok() {
+                let device = u32::from_le_bytes(header[4 .. 8].try_into().unwrap());
+                let id = u32::from_le_bytes(header[8 .. 12].try_into().unwrap());
+                let mut data = vec![0; u32::from_le_bytes(header[12 .. 16].try_into().unwrap()) as usize];
+                stream.read_exact(&mut data).await.unwrap();
+
+                let reply = {
+                    let mut server = server.lock().unwrap();
+                    server.requests.push((Instant::now(), id));
+
+                    let mut reply = Vec::new();
+
+                    if std::mem::take(&mut server.list_updated) {
+                        reply.extend(packet(0, DEVICE_LIST_UPDATED, &[]));
+                    }
+
+                    match id {
+                        REQUEST_CONTROLLER_COUNT => reply.extend(packet(0, id, &(server.devices.len() as u32).to_le_bytes())),
+                        REQUEST_CONTROLLER_DATA => {
+                            let (name, colors) = &server.devices[device as usize];
+                            reply.extend(packet(device, id, &controller_data(name, colors)));
+                        }
+                        _ => (), //SET_CLIENT_NAME has no reply
+                    }
+
+                    reply
+                };
+
+                stream.write_all(&reply).await.unwrap();
+            }
+        });
+
+        port
+    }
+
+    fn config(port: u16) -> OpenRgbConfig {
+        OpenRgbConfig { port, device: String::from("keyboard"), led: 1, interval_ms: 50, ..Default::default() }
+    }
+
+    async fn next(rx: &mut mpsc::Receiver<BtCommands>) -> CmdData {
+        match timeout(Duration::from_secs(2), rx.recv()).await {
+            Ok(Some(BtCommands::SetMode(data))) => data,
+            other => panic!("{other:?}"),
+        }
+    }
+
+    async fn next_rgb(rx: &mut mpsc::Receiver<BtCommands>) -> [u8; 3] {
+        let data = next(rx).await;
+        assert_eq!(CmdData { rgb: data.rgb, ..Default::default() }, data, "only the color is set");
+        data.rgb
+    }
+
+    #[test]
+    fn parses_controller_data() {
+        let controller = parse_controller(&controller_data("Corsair K70", &[[1, 2, 3], [4, 5, 6]])).unwrap();
+
+        assert_eq!(controller.name, "Corsair K70");
+        assert_eq!(controller.colors, [[1, 2, 3], [4, 5, 6]]);
+        assert!(parse_controller(&controller_data("Corsair K70", &[[1, 2, 3]])[.. 40]).is_err());
+    }
+
+    #[tokio::test]
+    async fn mirrors_one_led() {
+        let server = Arc::new(Mutex::new(Server {
+            devices: vec![("ASUS Aura Motherboard", vec![[9, 9, 9]; 2]), ("Corsair K70 Keyboard", vec![[0, 0, 0], [255, 0, 0]])],
+            ..Default::default()
+        }));
+
+        let port = stand_in(server.clone()).await;
+        let (tx, mut rx) = mpsc::channel(16);
+        tokio::spawn(mirror(config(port), tx, watch::channel(HeadsetState::default()).1));
+
+        assert_eq!(next_rgb(&mut rx).await, [255, 0, 0]);
+
+        // a color that didn't change isn't sent again
+        sleep(Duration::from_millis(300)).await;
+        assert!(rx.try_recv().is_err());
+
+        server.lock().unwrap().devices[1].1[1] = [0, 0, 255];
+        assert_eq!(next_rgb(&mut rx).await, [0, 0, 255]);
+    }
+
+    #[tokio::test]
+    async fn looks_again_after_the_device_list_changed() {
+        let server = Arc::new(Mutex::new(Server {
+            devices: vec![("Corsair K70 Keyboard", vec![[0, 0, 0], [255, 0, 0]])],
+            ..Default::default()
+        }));
+
+        let port = stand_in(server.clone()).await;
+        let (tx, mut rx) = mpsc::channel(16);
+        tokio::spawn(mirror(config(port), tx, watch::channel(HeadsetState::default()).1));
+
+        assert_eq!(next_rgb(&mut rx).await, [255, 0, 0]);
+
+        {
+            // the keyboard moves to index 1
+            let mut server = server.lock().unwrap();
+            server.devices[0].1[1] = [0, 255, 0];
+            server.devices.insert(0, ("ASUS Aura Motherboard", vec![[9, 9, 9]; 2]));
+            server.list_updated = true;
+        }
+
+        assert_eq!(next_rgb(&mut rx).await, [0, 255, 0]);
+        sleep(Duration::from_millis(300)).await;
+        assert!(rx.try_recv().is_err(), "the motherboard isn't mirrored");
+    }
+
+    #[tokio::test]
+    async fn black_is_lights_off() {
+        let server = Arc::new(Mutex::new(Server {
+            devices: vec![("Corsair K70 Keyboard", vec![[0, 0, 0], [255, 0, 0]])],
+            ..Default::default()
+        }));
+
+        let port = stand_in(server.clone()).await;
+        let (tx, mut rx) = mpsc::channel(16);
+        let (state_tx, state_rx) = watch::channel(HeadsetState { lighting: Some(CmdData { mode: 3, rgb: [255, 0, 0], settings: [0, 0] }), ..Default::default() });
+        tokio::spawn(mirror(config(port), tx, state_rx));
+
+        assert_eq!(next_rgb(&mut rx).await, [255, 0, 0]);
+
+        let set = |rgb| server.lock().unwrap().devices[0].1[1] = rgb;
+
+        set([0, 0, 0]);
+        assert_eq!(next(&mut rx).await, CmdData { mode: LIGHTS_OFF, ..Default::default() });
+
+        // what bt_stuff reports after that doesn't change what comes back
+        state_tx.send_modify(|state| state.lighting = Some(CmdData { mode: LIGHTS_OFF, rgb: [255, 0, 0], settings: [0, 0] }));
+        set([0, 0, 255]);
+        assert_eq!(next(&mut rx).await, CmdData { mode: 3, rgb: [0, 0, 255], ..Default::default() });
+        set([0, 255, 0]);
+        assert_eq!(next_rgb(&mut rx).await, [0, 255, 0]);
+
+        // without a mode to go back to the lights just go on
+        state_tx.send_modify(|state| state.lighting = None);
+        set([0, 0, 0]);
+        assert_eq!(next(&mut rx).await, CmdData { mode: LIGHTS_OFF, ..Default::default() });
+        set([255, 255, 255]);
+        assert_eq!(next(&mut rx).await, CmdData { mode: LIGHTS_ON, rgb: [255, 255, 255], ..Default::default() });
+    }
+
+    #[tokio::test]
+    async fn huge_packets_are_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut header = packet(0, REQUEST_CONTROLLER_COUNT, &[]);
+            header[12 .. 16].copy_from_slice(&u32::MAX.to_le_bytes());
+            stream.write_all(&header).await.unwrap();
+            sleep(Duration::from_secs(5)).await;
+        });
+
+        let mut client = Client::connect("127.0.0.1", port).await.unwrap();
+        let error = timeout(Duration::from_secs(2), client.recv()).await.unwrap().unwrap_err();
+        assert!(error.to_string().contains("4294967295 bytes"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn rate_limited() {
+        let server = Arc::new(Mutex::new(Server {
+            devices: vec![("Corsair K70 Keyboard", vec![[0, 0, 0], [255, 0, 0]])],
+            ..Default::default()
+        }));
+
+        let port = stand_in(server.clone()).await;
+        let (tx, mut rx) = mpsc::channel(64);
+        tokio::spawn(mirror(config(port), tx, watch::channel(HeadsetState::default()).1));
+
+        // an effect that changes the color faster than we poll
+        let mut sent = 0;
+
+        for _ in 0 .. 100 {
+            {
+                let mut server = server.lock().unwrap();
+                let green = &mut server.devices[0].1[1][1];
+                *green = green.wrapping_add(1);
+            }
+
+            while rx.try_recv().is_ok() {
+                sent += 1;
+            }
+
+            sleep(Duration::from_millis(5)).await;
+        }
+
+        let polls: Vec<Instant> = server.lock().unwrap().requests.iter()
+            .filter(|&&(_, id)| id == REQUEST_CONTROLLER_DATA)
+            .map(|&(t, _)| t)
+            .collect();
+
+        assert!(polls.len() > 2, "{polls:?}");
+        assert!(sent < polls.len(), "at most one color per poll, {sent} for {} polls", polls.len());
+
+        // the first one is finding the device
+        assert!(polls[1 ..].windows(2).all(|pair| pair[1] - pair[0] >= Duration::from_millis(50)));
+    }
+}
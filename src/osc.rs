@@ -0,0 +1,393 @@
+// OSC over UDP, for TouchOSC / Resolume / QLab and friends
+//   /catcaller/color r g b     ints 0-255 or floats 0-1
+//   /catcaller/mode n          1-8, same order as the mode buttons
+//   /catcaller/brightness f    0-1
+//   /catcaller/speed f         0-1
+//   /catcaller/preset name
+// state changes are echoed back on the same addresses (plus /catcaller/status) if a feedback target is set
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, watch};
+
+use crate::bt::{BtCommands, CmdData, Coalescer, HeadsetState};
+use crate::config::Preset;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OscConfig {
+    pub bind: SocketAddr,
+    pub feedback: Option<SocketAddr>,
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 9000)),
+            feedback: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub addr: String,
+    pub args: Vec<Arg>,
+}
+
+pub async fn serve(config: OscConfig, presets: Vec<Preset>, tx: mpsc::Sender<BtCommands>, state_rx: watch::Receiver<HeadsetState>) {
+    match UdpSocket::bind(config.bind).await {
+        Ok(socket) => run(Arc::new(socket), config.feedback, presets, tx, state_rx).await,
+        Err(e) => println!("osc: can't listen on {}: {e}", config.bind),
+    }
+}
+
+async fn run(socket: Arc<UdpSocket>, feedback_target: Option<SocketAddr>, presets: Vec<Preset>, tx: mpsc::Sender<BtCommands>, state_rx: watch::Receiver<HeadsetState>) {
+    if let Some(target) = feedback_target {
+        tokio::spawn(feedback(socket.clone(), target, state_rx));
+    }
+
+    let commands = Coalescer::new(tx); //faders send a lot more than the headset takes
+    let mut buf = [0; 2048];
+    let mut messages = Vec::new();
+
+    loop {
+        let len = match socket.recv_from(&mut buf).await {
+            Ok((len, _)) => len,
+            Err(e) => return println!("osc: {e}"),
+        };
+
+        messages.clear();
+        if decode(&buf[.. len], &mut messages).is_none() {
+            continue; //malformed, just drop it
+        }
+
+        for message in &messages {
+            let Some(data) = command(message, &presets) else { continue };
+
+            if !commands.send(data) {
+                return;
+            }
+        }
+    }
+}
+
+async fn feedback(socket: Arc<UdpSocket>, target: SocketAddr, mut state_rx: watch::Receiver<HeadsetState>) {
+    while state_rx.changed().await.is_ok() {
+        let state = state_rx.borrow_and_update().clone();
+
+        let mut packets = vec![encode("/catcaller/status", &[Arg::Str(state.status.name().to_string())])];
+
+        // only what's known, a fader at 0 would otherwise jump there
+        let data = state.lighting.unwrap_or_default();
+
+        if data.rgb != [0, 0, 0] {
+            let [r, g, b] = data.rgb.map(|c| Arg::Int(c as i32));
+            packets.push(encode("/catcaller/color", &[r, g, b]));
+        }
+
+        if data.mode != 0 {
+            packets.push(encode("/catcaller/mode", &[Arg::Int(data.mode as i32)]));
+        }
+
+        if data.settings[0] != 0 {
+            packets.push(encode("/catcaller/brightness", &[Arg::Float(data.settings[0] as f32 / 63.0)]));
+        }
+
+        if data.settings[1] != 0 {
+            packets.push(encode("/catcaller/speed", &[Arg::Float(data.settings[1] as f32 / 63.0)]));
+        }
+
+        for packet in packets {
+            if let Err(e) = socket.send_to(&packet, target).await {
+                println!("osc: feedback to {target} failed: {e}");
+            }
+        }
+    }
+}
+
+pub fn command(message: &Message, presets: &[Preset]) -> Option<CmdData> {
+    let args = &message.args;
+
+    match message.addr.as_str() {
+        "/catcaller/color" => {
+            let [r, g, b] = args.get(.. 3)? else { return None };
+            Some(CmdData { rgb: [byte(r)?, byte(g)?, byte(b)?], ..Default::default() })
+        }
+
+        "/catcaller/mode" => {
+            let mode = match args.first()? {
+                Arg::Int(n) => *n,
+                Arg::Float(f) => *f as i32,
+                _ => return None,
+            };
+
+            (1 ..= 8).contains(&mode).then(|| CmdData { mode: mode as u8, ..Default::default() })
+        }
+
+        "/catcaller/brightness" => Some(CmdData { settings: [setting(args.first()?)?, 0], ..Default::default() }),
+        "/catcaller/speed" => Some(CmdData { settings: [0, setting(args.first()?)?], ..Default::default() }),
+
+        "/catcaller/preset" => match args.first()? {
+            Arg::Str(name) => Preset::find(presets, name).map(CmdData::from),
+            _ => None,
+        },
+
+        _ => None,
+    }
+}
+
+fn byte(arg: &Arg) -> Option<u8> {
+    match arg {
+        Arg::Int(n) => Some((*n).clamp(0, 255) as u8),
+        Arg::Float(f) => Some((f.clamp(0.0, 1.0) * 255.0).round() as u8),
+        _ => None,
+    }
+}
+
+fn setting(arg: &Arg) -> Option<u8> {
+    match arg {
+        Arg::Int(n) => Some((*n).clamp(0, 63) as u8),
+        Arg::Float(f) => Some((f.clamp(0.0, 1.0) * 63.0).round() as u8),
+        _ => None,
+    }
+}
+
+// messages and bundles, bundle time tags are ignored and everything is applied right away
+pub fn decode(packet: &[u8], out: &mut Vec<Message>) -> Option<()> {
+    if let Some(mut rest) = packet.strip_prefix(b"#bundle\0") {
+        rest = rest.get(8 ..)?; //time tag
+
+        while !rest.is_empty() {
+            let size = u32::from_be_bytes(rest.get(.. 4)?.try_into().unwrap()) as usize;
+            decode(rest.get(4 .. 4 + size)?, out)?;
+            rest = &rest[4 + size ..];
+        }
+
+        return Some(());
+    }
+
+    let (addr, mut rest) = read_string(packet)?;
+    let (tags, tag_rest) = read_string(rest).unwrap_or((String::from(","), &[]));
+    rest = tag_rest;
+
+    let mut args = Vec::new();
+
+    for tag in tags.strip_prefix(',')?.chars() {
+        match tag {
+            'i' => {
+                args.push(Arg::Int(i32::from_be_bytes(rest.get(.. 4)?.try_into().unwrap())));
+                rest = &rest[4 ..];
+            }
+
+            'f' => {
+                args.push(Arg::Float(f32::from_be_bytes(rest.get(.. 4)?.try_into().unwrap())));
+                rest = &rest[4 ..];
+            }
+
+            's' => {
+                let (s, s_rest) = read_string(rest)?;
+                args.push(Arg::Str(s));
+                rest = s_rest;
+            }
+
+            'T' => args.push(Arg::Bool(true)),
+            'F' => args.push(Arg::Bool(false)),
+            _ => return None, //unknown size, can't read past it
+        }
+    }
+
+    out.push(Message { addr, args });
+    Some(())
+}
+
+pub fn encode(addr: &str, args: &[Arg]) -> Vec<u8> {
+    let mut packet = Vec::new();
+    write_string(&mut packet, addr);
+
+    let tags: String = args.iter().map(|arg| match arg {
+        Arg::Int(_) => 'i',
+        Arg::Float(_) => 'f',
+        Arg::Str(_) => 's',
+        Arg::Bool(true) => 'T',
+        Arg::Bool(false) => 'F',
+    }).collect();
+
+    write_string(&mut packet, &format!(",{tags}"));
+
+    for arg in args {
+        match arg {
+            Arg::Int(n) => packet.extend_from_slice(&n.to_be_bytes()),
+            Arg::Float(f) => packet.extend_from_slice(&f.to_be_bytes()),
+            Arg::Str(s) => write_string(&mut packet, s),
+            Arg::Bool(_) => (),
+        }
+    }
+
+    packet
+}
+
+// null terminated, padded to a multiple of 4 bytes
+fn read_string(data: &[u8]) -> Option<(String, &[u8])> {
+    let len = data.iter().position(|&b| b == 0)?;
+    let padded = (len + 4) & !3;
+    let s = String::from_utf8_lossy(&data[.. len]).into_owned();
+
+    Some((s, data.get(padded ..)?))
+}
+
+fn write_string(packet: &mut Vec<u8>, s: &str) {
+    packet.extend_from_slice(s.as_bytes());
+    packet.resize((packet.len() + 4) & !3, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::{timeout, Duration};
+
+    use super::*;
+    use crate::bt::BtToGui;
+
+    fn message(addr: &str, args: Vec<Arg>) -> Message {
+        Message { addr: addr.to_string(), args }
+    }
+
+    #[test]
+    fn round_trip() {
+        let sent = message("/catcaller/preset", vec![Arg::Str(String::from("on air")), Arg::Int(-3), Arg::Float(0.5), Arg::Bool(true)]);
+        let packet = encode(&sent.addr, &sent.args);
+        let mut received = Vec::new();
+
+        assert_eq!(packet.len() % 4, 0);
+        assert_eq!(decode(&packet, &mut received), Some(()));
+        assert_eq!(received, [sent]);
+    }
+
+    #[test]
+    fn bundles() {
+        let first = encode("/catcaller/mode", &[Arg::Int(2)]);
+        let second = encode("/catcaller/speed", &[Arg::Float(1.0)]);
+
+        let mut bundle = b"#bundle\0\0\0\0\0\0\0\0\x01".to_vec();
+
+        for packet in [&first, &second] {
+            bundle.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+            bundle.extend_from_slice(packet);
+        }
+
+        let mut received = Vec::new();
+        assert_eq!(decode(&bundle, &mut received), Some(()));
+        assert_eq!(received.iter().map(|m| m.addr.as_str()).collect::<Vec<_>>(), ["/catcaller/mode", "/catcaller/speed"]);
+
+        assert_eq!(decode(&bundle[.. bundle.len() - 4], &mut Vec::new()), None, "truncated");
+    }
+
+    #[test]
+    fn commands() {
+        let presets = [Preset { name: String::from("On Air"), mode: 7, rgb: [255, 0, 0], settings: [63, 0] }];
+        let command = |addr: &str, args: Vec<Arg>| command(&message(addr, args), &presets);
+
+        assert_eq!(command("/catcaller/color", vec![Arg::Int(255), Arg::Int(300), Arg::Int(-1)]), Some(CmdData { rgb: [255, 255, 0], ..Default::default() }));
+        assert_eq!(command("/catcaller/color", vec![Arg::Float(1.0), Arg::Float(0.5), Arg::Float(0.0)]), Some(CmdData { rgb: [255, 128, 0], ..Default::default() }));
+        assert_eq!(command("/catcaller/color", vec![Arg::Int(255)]), None);
+        assert_eq!(command("/catcaller/mode", vec![Arg::Float(3.0)]), Some(CmdData { mode: 3, ..Default::default() }));
+        assert_eq!(command("/catcaller/mode", vec![Arg::Int(9)]), None);
+        assert_eq!(command("/catcaller/brightness", vec![Arg::Float(0.5)]), Some(CmdData { settings: [32, 0], ..Default::default() }));
+        assert_eq!(command("/catcaller/speed", vec![Arg::Int(100)]), Some(CmdData { settings: [0, 63], ..Default::default() }));
+        assert_eq!(command("/catcaller/preset", vec![Arg::Str(String::from("on air"))]), Some(CmdData::from(&presets[0])));
+        assert_eq!(command("/catcaller/preset", vec![Arg::Str(String::from("off air"))]), None);
+        assert_eq!(command("/catcaller/volume", vec![Arg::Float(1.0)]), None);
+    }
+
+    async fn receive(socket: &UdpSocket) -> Message {
+        let mut buf = [0; 2048];
+        let (len, _) = timeout(Duration::from_secs(2), socket.recv_from(&mut buf)).await.unwrap().unwrap();
+
+        let mut messages = Vec::new();
+        decode(&buf[.. len], &mut messages).unwrap();
+        messages.remove(0)
+    }
+
+    #[tokio::test]
+    async fn loopback() {
+        let server = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let addr = server.local_addr().unwrap();
+        let console = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let (tx, mut rx) = mpsc::channel(4);
+        let (state_tx, state_rx) = watch::channel(HeadsetState { status: BtToGui::Ready, ..Default::default() });
+        tokio::spawn(run(server, Some(console.local_addr().unwrap()), Vec::new(), tx, state_rx));
+
+        console.send_to(&encode("/catcaller/color", &[Arg::Float(1.0), Arg::Float(0.5), Arg::Float(0.0)]), addr).await.unwrap();
+
+        match timeout(Duration::from_secs(2), rx.recv()).await {
+            Ok(Some(BtCommands::SetMode(data))) => assert_eq!(data, CmdData { rgb: [255, 128, 0], ..Default::default() }),
+            other => panic!("{other:?}"),
+        }
+
+        // a color on its own leaves mode, brightness and speed as they are, and so does the feedback
+        state_tx.send_modify(|state| state.lighting = Some(CmdData { rgb: [255, 128, 0], ..Default::default() }));
+
+        assert_eq!(receive(&console).await, message("/catcaller/status", vec![Arg::Str(String::from("ready"))]));
+        assert_eq!(receive(&console).await, message("/catcaller/color", vec![Arg::Int(255), Arg::Int(128), Arg::Int(0)]));
+
+        state_tx.send_modify(|state| state.lighting = Some(CmdData { mode: 2, rgb: [255, 128, 0], settings: [63, 0] }));
+
+        let mut received = Vec::new();
+
+        for _ in 0 .. 4 {
+            received.push(receive(&console).await);
+        }
+
+        assert_eq!(received[2 ..], [
+            message("/catcaller/mode", vec![Arg::Int(2)]),
+            message("/catcaller/brightness", vec![Arg::Float(1.0)]),
+        ]);
+
+        // and no speed, the next thing is the next state
+        state_tx.send_modify(|state| state.status = BtToGui::Connected);
+        assert_eq!(receive(&console).await.args, [Arg::Str(String::from("connected"))]);
+    }
+
+    // nothing in a flood of fader moves gets lost: the mode before it, the last value and the color
+    // after it all reach the headset, whatever was in between doesn't have to
+    #[tokio::test]
+    async fn flood() {
+        let server = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let addr = server.local_addr().unwrap();
+        let console = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let (_state_tx, state_rx) = watch::channel(HeadsetState::default());
+        tokio::spawn(run(server, None, Vec::new(), tx, state_rx));
+
+        console.send_to(&encode("/catcaller/mode", &[Arg::Int(3)]), addr).await.unwrap();
+
+        for i in 0 ..= 100 {
+            console.send_to(&encode("/catcaller/brightness", &[Arg::Float(i as f32 / 100.0)]), addr).await.unwrap();
+        }
+
+        console.send_to(&encode("/catcaller/color", &[Arg::Int(0), Arg::Int(0), Arg::Int(255)]), addr).await.unwrap();
+
+        let expected = CmdData { mode: 3, rgb: [0, 0, 255], settings: [63, 0] };
+        let mut shown = CmdData::default();
+
+        while shown != expected {
+            match timeout(Duration::from_secs(2), rx.recv()).await {
+                Ok(Some(BtCommands::SetMode(data))) => shown = shown.merge(data),
+                other => panic!("{other:?}, got to {shown:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// command palette (ctrl+k by default): fuzzy search over modes, presets and the other actions
+
+use egui::{Align2, Key, Modifiers};
+
+use crate::keys::{Action, Bindings};
+
+#[derive(Default)]
+pub struct Palette {
+    open: bool,
+    query: String,
+    selected: usize,
+}
+
+impl Palette {
+    pub fn open(&mut self) {
+        *self = Self { open: true, ..Default::default() };
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // returns the action that was picked, if any
+    pub fn show(&mut self, ctx: &egui::Context, actions: &[Action], bindings: &Bindings) -> Option<Action> {
+        if !self.open {
+            return None;
+        }
+
+        let mut matches: Vec<(i32, &Action)> = actions
+            .iter()
+            .filter(|action| **action != Action::Palette)
+            .filter_map(|action| Some((fuzzy_score(&self.query, &action.label())?, action)))
+            .collect();
+
+        matches.sort_by_key(|(score, _)| -score); //stable, so ties keep their order
+
+        // handled before the text field sees them
+        let (up, down, enter) = ctx.input_mut(|input| (
+            input.consume_key(Modifiers::NONE, Key::ArrowUp),
+            input.consume_key(Modifiers::NONE, Key::ArrowDown),
+            input.consume_key(Modifiers::NONE, Key::Enter),
+        ));
+
+        self.selected = match (up, down) {
+            (true, false) => self.selected.saturating_sub(1),
+            (false, true) => self.selected + 1,
+            _ => self.selected,
+        }.min(matches.len().saturating_sub(1));
+
+        let mut picked = enter.then(|| matches.get(self.selected).map(|(_, action)| (*action).clone())).flatten();
+
+        egui::Window::new("Command palette")
+            .title_bar(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_TOP, [0.0, 10.0])
+            .show(ctx, |ui| {
+                let query = ui.text_edit_singleline(&mut self.query);
+                query.request_focus();
+
+                if query.changed() {
+                    self.selected = 0;
+                }
+
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    for (idx, (_, action)) in matches.iter().enumerate() {
+                        let label = match bindings.shortcut(action) {
+                            Some(shortcut) => format!("{}    {}", action.label(), ctx.format_shortcut(shortcut)),
+                            None => action.label(),
+                        };
+
+                        let item = ui.selectable_label(idx == self.selected, label);
+
+                        if idx == self.selected && (up || down) {
+                            item.scroll_to_me(None);
+                        }
+
+                        if item.clicked() {
+                            picked = Some((*action).clone());
+                        }
+                    }
+                });
+            });
+
+        if picked.is_some() {
+            self.open = false;
+        }
+
+        picked
+    }
+}
+
+// None if the query's characters don't appear in order. higher is better:
+// consecutive characters and characters at the start of words count extra
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last_match = None;
+
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + text[pos ..].iter().position(|&t| t == c)?;
+
+        score += 1;
+
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3; //word start
+        }
+
+        if last_match.is_some_and(|last| last + 1 == found) {
+            score += 2;
+        }
+
+        last_match = Some(found);
+        pos = found + 1;
+    }
+
+    Some(score)
+}
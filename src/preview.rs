@@ -0,0 +1,163 @@
+// headset preview: shows roughly what a mode + color + settings combination looks like before sending it.
+// drawn in a GL paint callback, or with plain egui shapes when the software renderer is in use
+
+use std::{f32::consts::TAU, sync::{Arc, Mutex}, time::Duration};
+
+use egui::{Color32, Rect, Sense, Stroke, vec2};
+
+use crate::bt::CmdData;
+use crate::graphics::{self, GlCallback};
+
+const ANIMATION_FRAME: Duration = Duration::from_millis(33); //about 30 fps
+
+#[derive(Default)]
+pub struct Preview {
+    gl: Option<Arc<Mutex<Option<PreviewGl>>>>, //None with the software renderer. GL objects get created in the first callback
+}
+
+struct PreviewGl {
+    program: u32,
+    vao: u32,
+    uniforms: [i32; 3], //size, left, right
+}
+
+impl Preview {
+    pub fn new(gl_callbacks: bool) -> Self {
+        Self { gl: gl_callbacks.then(Default::default) }
+    }
+
+    pub fn show(&self, ui: &mut egui::Ui, data: CmdData) {
+        let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width(), 80.0), Sense::hover());
+        let lights = lights(data, ui.input(|i| i.time));
+
+        match &self.gl {
+            Some(gl) => {
+                let gl = gl.clone();
+
+                ui.painter().add(egui::PaintCallback {
+                    rect,
+                    callback: Arc::new(GlCallback(Box::new(move |info, header| paint_gl(&gl, info, header, lights)))),
+                });
+            }
+
+            None => paint_shapes(ui.painter(), rect, lights),
+        }
+
+        // only the animated modes need new frames, and not more often than they can show a change
+        if matches!(data.mode, 2 ..= 5) {
+            ui.ctx().request_repaint_after(ANIMATION_FRAME);
+        }
+    }
+}
+
+// ear cup colors, 0-1. the setting ranges aren't figured out yet (see ui.rs) so this is an approximation
+fn lights(data: CmdData, time: f64) -> [[f32; 3]; 2] {
+    let color = data.rgb.map(|c| c as f32 / 255.0);
+    let brightness = 0.3 + 0.7 * data.settings[0] as f32 / 63.0;
+    let t = time as f32 * (0.5 + 2.0 * data.settings[1] as f32 / 63.0); //cycles
+
+    let level = |level: f32| color.map(|c| c * level * brightness);
+    let pulse = |t: f32| level((-t.fract() * 6.0).exp());
+    let hue = |t: f32| egui::ecolor::Hsva::new(t.fract(), 1.0, 1.0, 1.0).to_srgb().map(|c| c as f32 / 255.0 * brightness);
+
+    match data.mode {
+        2 => [level(if t.fract() < 0.5 { 1.0 } else { 0.0 }); 2], //flash
+        3 => [level(0.5 - 0.5 * (t * TAU).cos()); 2],            //breath
+        4 => [pulse(t), pulse(t + 0.5)],                          //rhythm
+        5 => [hue(t * 0.25), hue(t * 0.25 + 0.5)],                //yowu
+        6 => [[0.0; 3]; 2],                                       //lights off
+        _ => [level(1.0); 2],
+    }
+}
+
+fn paint_gl(gl: &Mutex<Option<PreviewGl>>, info: &egui::PaintCallbackInfo, header: &str, lights: [[f32; 3]; 2]) {
+    let mut gl = gl.lock().unwrap();
+
+    if gl.is_none() {
+        let vert = format!("{header}\n{}", include_str!("graphics/shader_preview.vert"));
+        let frag = format!("{header}\n{}", include_str!("graphics/shader_preview.frag"));
+
+        let program = match graphics::create_program(&vert, &frag) {
+            Ok(program) => program,
+            Err(e) => return println!("headset preview: {e}"),
+        };
+
+        let mut vao = 0;
+
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao); //core profiles won't draw without one bound
+        }
+
+        let uniforms = [c"size", c"left", c"right"].map(|name| unsafe { gl::GetUniformLocation(program, name.as_ptr()) });
+        *gl = Some(PreviewGl { program, vao, uniforms });
+    }
+
+    let Some(PreviewGl { program, vao, uniforms }) = gl.as_ref() else { return };
+    let [left, right] = lights;
+    let viewport = info.viewport_in_pixels();
+
+    unsafe {
+        gl::UseProgram(*program);
+        gl::BindVertexArray(*vao);
+        gl::Uniform2f(uniforms[0], viewport.width_px, viewport.height_px);
+        gl::Uniform3f(uniforms[1], left[0], left[1], left[2]);
+        gl::Uniform3f(uniforms[2], right[0], right[1], right[2]);
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+    }
+}
+
+// same layout as shader_preview.frag, one unit there is half the rect height here
+fn paint_shapes(painter: &egui::Painter, rect: Rect, lights: [[f32; 3]; 2]) {
+    let body = Color32::from_rgb(69, 64, 77);
+    let unit = rect.height() / 2.0;
+    let at = |x: f32, y: f32| rect.center() + vec2(x, -y) * unit;
+
+    let band: Vec<_> = (0 ..= 24)
+        .map(|i| i as f32 / 24.0 * std::f32::consts::PI)
+        .map(|a| at(a.cos() * 0.62, a.sin() * 0.62 - 0.3))
+        .collect();
+
+    painter.add(egui::Shape::line(band, Stroke::new(0.12 * unit, body)));
+
+    for (x, light) in [(-0.62, lights[0]), (0.62, lights[1])] {
+        let [r, g, b] = light.map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8);
+        let center = at(x, -0.35);
+        let cup = Rect::from_center_size(center, vec2(0.56, 0.75) * unit);
+
+        painter.rect_filled(cup.expand(0.08 * unit), 0.45 * unit, Color32::from_rgba_unmultiplied(r, g, b, 80)); //glow
+        painter.rect_filled(cup, 0.28 * unit, body);
+        painter.rect_stroke(cup.shrink(0.04 * unit), 0.26 * unit, Stroke::new(0.08 * unit, Color32::from_rgb(r, g, b)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what egui asks of the event loop after the preview was shown in a settled frame
+    fn repaint_after(mode: u8) -> Duration {
+        let ctx = egui::Context::default();
+        let preview = Preview::new(false);
+        let data = CmdData { mode, rgb: [255, 0, 0], settings: [63, 32] };
+
+        let mut repaint_after = Duration::ZERO;
+        for _ in 0 .. 3 {
+            ctx.begin_frame(Default::default());
+            egui::CentralPanel::default().show(&ctx, |ui| preview.show(ui, data));
+            repaint_after = ctx.end_frame().repaint_after;
+        }
+
+        repaint_after
+    }
+
+    #[test]
+    fn only_animated_modes_repaint() {
+        for mode in [0, 1, 6] {
+            assert_eq!(repaint_after(mode), Duration::MAX, "mode {mode}");
+        }
+
+        for mode in 2 ..= 5 {
+            assert_eq!(repaint_after(mode), ANIMATION_FRAME, "mode {mode}");
+        }
+    }
+}
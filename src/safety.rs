@@ -0,0 +1,34 @@
+// photosensitivity limits. the limiting itself happens in catcaller's Headset, so every command
+// source goes through it, this is only its [safety] section in the config file
+
+use catcaller::{FlashLimits, Level};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SafetyConfig {
+    // turns every limit off. only for setups where nobody who can see the headset is sensitive to
+    // flashing lights
+    pub allow_unsafe_flashing: bool,
+    // what the flash mode's speed gets capped to, 1-63. how fast the headset flashes at each step
+    // hasn't been measured yet, so it's the slowest one unless raised here
+    pub max_flash_speed: u8,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            allow_unsafe_flashing: false,
+            max_flash_speed: 1,
+        }
+    }
+}
+
+impl SafetyConfig {
+    pub fn limits(&self) -> FlashLimits {
+        match self.allow_unsafe_flashing {
+            true => FlashLimits::unsafe_off(),
+            false => FlashLimits::default().max_flash_speed(Level::saturating(self.max_flash_speed.max(1))),
+        }
+    }
+}
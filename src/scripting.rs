@@ -0,0 +1,697 @@
+// rhai scripts from <config dir>/scripts/*.rhai, for effects and automations that don't need a
+// rebuild. every script gets a thread of its own and is restarted when its file changes. the top
+// level runs once, after that these functions are called if the script has them:
+//   on_state(state)     any change, same map as state()
+//   on_connect()        headset ready, or already there when the script starts
+//   on_disconnect()
+//   on_battery(percent)
+//   on_event(name)      `blatand event <name>`, e.g. `make || blatand event build-failed`
+// events only start coming once the top level is done, so a top level that loops an effect
+// forever never gets any. state changes are coalesced, a handler always gets the latest.
+// scripts can't touch files or the network (no import, no eval). each run of the top level or a
+// handler may keep busy for [scripts] time_limit seconds, sleeping doesn't count, and headset
+// commands are spaced out to commands_per_second.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::TrySendError;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope};
+use serde::Deserialize;
+use tokio::sync::{mpsc, watch};
+
+use crate::bt::{BtCommands, BtToGui, CmdData, HeadsetState, MODES};
+use crate::config::{config_dir, Preset};
+
+const RELOAD_TIME: Duration = Duration::from_secs(1); //how often the scripts dir is checked
+const EVENT_QUEUE: usize = 16; //per script
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// [scripts] in the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScriptConfig {
+    pub time_limit: f32, //seconds
+    pub commands_per_second: f32,
+}
+
+impl Default for ScriptConfig {
+    fn default() -> Self {
+        Self {
+            time_limit: 1.0,
+            commands_per_second: 20.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Event {
+    State, //the state itself waits in the script's Inbox
+    Connect,
+    Disconnect,
+    Battery(u8),
+    Custom(String),
+}
+
+// everything the scripts get to use
+#[derive(Clone)]
+struct Api {
+    config: ScriptConfig,
+    presets: Vec<Preset>,
+    tx: mpsc::Sender<BtCommands>,
+    state_rx: watch::Receiver<HeadsetState>,
+}
+
+// returns where custom events go
+pub fn spawn(config: ScriptConfig, presets: Vec<Preset>, tx: mpsc::Sender<BtCommands>, state_rx: watch::Receiver<HeadsetState>) -> mpsc::Sender<String> {
+    let (events_tx, events_rx) = mpsc::channel(EVENT_QUEUE);
+    let api = Api { config, presets, tx, state_rx };
+
+    if let Some(dir) = config_dir().map(|dir| dir.join("scripts")) {
+        tokio::spawn(supervise(dir, api, events_rx));
+    }
+
+    events_tx
+}
+
+// (re)starts scripts as their files change and hands them events
+async fn supervise(dir: PathBuf, api: Api, mut custom_rx: mpsc::Receiver<String>) {
+    let mut scripts: HashMap<PathBuf, Script> = HashMap::new();
+    let mut state_rx = api.state_rx.clone();
+    let mut last_state = state_rx.borrow_and_update().clone();
+    let mut reload = tokio::time::interval(RELOAD_TIME);
+
+    loop {
+        let events = tokio::select! {
+            _ = reload.tick() => {
+                reload_scripts(&dir, &mut scripts, &api);
+                continue;
+            }
+
+            changed = state_rx.changed() => {
+                if changed.is_err() {
+                    return; //bt task is gone
+                }
+
+                let state = state_rx.borrow_and_update().clone();
+                let events = state_events(&last_state, &state);
+                last_state = state;
+                events
+            }
+
+            Some(name) = custom_rx.recv() => vec![Event::Custom(name)],
+        };
+
+        for (path, script) in &scripts {
+            for event in &events {
+                if !script.deliver(event, &last_state) {
+                    println!("script {}: queue full!", script_name(path));
+                }
+            }
+        }
+    }
+}
+
+fn state_events(old: &HeadsetState, new: &HeadsetState) -> Vec<Event> {
+    let ready = |state: &HeadsetState| matches!(state.status, BtToGui::Ready);
+    let mut events = vec![Event::State];
+
+    match (ready(old), ready(new)) {
+        (false, true) => events.push(Event::Connect),
+        (true, false) => events.push(Event::Disconnect),
+        _ => (),
+    }
+
+    if let Some(battery) = new.battery.filter(|_| new.battery != old.battery) {
+        events.push(Event::Battery(battery));
+    }
+
+    events
+}
+
+fn reload_scripts(dir: &Path, scripts: &mut HashMap<PathBuf, Script>, api: &Api) {
+    let found: HashMap<PathBuf, SystemTime> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .filter_map(|path| Some((path.clone(), path.metadata().ok()?.modified().ok()?)))
+            .collect(),
+
+        Err(_) => HashMap::new(), //no scripts dir, that's fine
+    };
+
+    // dropping a script stops it
+    scripts.retain(|path, script| found.get(path) == Some(&script.modified));
+
+    for (path, modified) in found {
+        if scripts.contains_key(&path) {
+            continue;
+        }
+
+        match std::fs::read_to_string(&path) {
+            Ok(source) => {
+                println!("script {}: started", script_name(&path));
+                scripts.insert(path.clone(), Script::start(script_name(&path), source, modified, api.clone()));
+            }
+
+            Err(e) => println!("script {}: can't read it: {e}", script_name(&path)),
+        }
+    }
+}
+
+fn script_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+// a running script, stopped when dropped
+struct Script {
+    modified: SystemTime,
+    events: std::sync::mpsc::SyncSender<Event>,
+    inbox: Arc<Inbox>,
+    stop: Arc<Stop>,
+}
+
+// shared by the supervisor and a script's thread
+#[derive(Default)]
+struct Inbox {
+    listening: AtomicBool, //the top level is done
+    state: Mutex<Option<HeadsetState>>, //for the Event::State in the queue, if there is one
+}
+
+impl Script {
+    fn start(name: String, source: String, modified: SystemTime, api: Api) -> Self {
+        let (events, events_rx) = std::sync::mpsc::sync_channel(EVENT_QUEUE);
+        let inbox = Arc::new(Inbox::default());
+        let stop = Arc::new(Stop::default());
+
+        let sandbox = Arc::new(Sandbox {
+            api,
+            stop: stop.clone(),
+            deadline: Mutex::new(Instant::now()),
+            next_command: Mutex::new(Instant::now()),
+        });
+
+        let script_inbox = inbox.clone();
+        std::thread::spawn(move || run(&name, &source, sandbox, &script_inbox, events_rx));
+
+        Self { modified, events, inbox, stop }
+    }
+
+    // false if the queue was full and the event got dropped
+    fn deliver(&self, event: &Event, state: &HeadsetState) -> bool {
+        if !self.inbox.listening.load(Ordering::Acquire) {
+            return true; //still running its top level, or done altogether
+        }
+
+        if let Event::State = event {
+            if self.inbox.state.lock().unwrap().replace(state.clone()).is_some() {
+                return true; //one's already queued, it'll get this state instead
+            }
+        }
+
+        match self.events.try_send(event.clone()) {
+            Err(TrySendError::Full(event)) => {
+                if let Event::State = event {
+                    self.inbox.state.lock().unwrap().take();
+                }
+
+                false
+            }
+
+            _ => true,
+        }
+    }
+}
+
+impl Drop for Script {
+    fn drop(&mut self) {
+        self.stop.stop();
+    }
+}
+
+#[derive(Default)]
+struct Stop {
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl Stop {
+    fn stop(&self) {
+        *self.stopped.lock().unwrap() = true;
+        self.wake.notify_all();
+    }
+
+    fn is_stopped(&self) -> bool {
+        *self.stopped.lock().unwrap()
+    }
+
+    // false if the script got stopped in the meantime
+    fn sleep(&self, duration: Duration) -> bool {
+        let stopped = self.stopped.lock().unwrap();
+        let (stopped, _) = self.wake.wait_timeout_while(stopped, duration, |stopped| !*stopped).unwrap();
+        !*stopped
+    }
+}
+
+// what a script's functions share
+struct Sandbox {
+    api: Api,
+    stop: Arc<Stop>,
+    deadline: Mutex<Instant>, //of the current run, pushed back by sleeping
+    next_command: Mutex<Instant>, //rate limit
+}
+
+impl Sandbox {
+    fn start_run(&self) {
+        *self.deadline.lock().unwrap() = Instant::now() + Duration::from_secs_f32(self.api.config.time_limit.max(0.0));
+    }
+
+    fn sleep(&self, duration: Duration) -> ScriptResult<()> {
+        let start = Instant::now();
+        let awake = self.stop.sleep(duration);
+        *self.deadline.lock().unwrap() += start.elapsed();
+
+        match awake {
+            true => Ok(()),
+            false => Err("stopped".into()),
+        }
+    }
+
+    // false if there's no headset to send it to
+    fn send(&self, command: BtCommands) -> ScriptResult<bool> {
+        let wait = {
+            let interval = Duration::from_secs_f32(1.0 / self.api.config.commands_per_second.max(0.1));
+            let mut next_command = self.next_command.lock().unwrap();
+            let now = Instant::now();
+            let wait = next_command.saturating_duration_since(now);
+            *next_command = now.max(*next_command) + interval;
+            wait
+        };
+
+        self.sleep(wait)?;
+
+        if !matches!(self.api.state_rx.borrow().status, BtToGui::Ready) {
+            return Ok(false);
+        }
+
+        match self.api.tx.blocking_send(command) {
+            Ok(_) => Ok(true),
+            Err(_) => Err("bluetooth task is gone".into()),
+        }
+    }
+
+    fn set(&self, data: CmdData) -> ScriptResult<bool> {
+        self.send(BtCommands::SetMode(data))
+    }
+}
+
+fn run(name: &str, source: &str, sandbox: Arc<Sandbox>, inbox: &Inbox, events: std::sync::mpsc::Receiver<Event>) {
+    let engine = engine(name, &sandbox);
+
+    let ast = match engine.compile(source) {
+        Ok(ast) => ast,
+        Err(e) => return println!("script {name}: {e}"),
+    };
+
+    let handlers: HashSet<String> = ast.iter_functions().map(|function| function.name.to_string()).collect();
+    let mut scope = Scope::new();
+
+    sandbox.start_run();
+
+    if let Err(e) = engine.run_ast_with_scope(&mut scope, &ast) {
+        return report(name, &sandbox, &e);
+    }
+
+    if handlers.is_empty() {
+        return;
+    }
+
+    inbox.listening.store(true, Ordering::Release);
+
+    // scripts (re)started with the headset already there get an on_connect too
+    let connected = matches!(sandbox.api.state_rx.borrow().status, BtToGui::Ready);
+    let first = connected.then_some(Event::Connect);
+
+    // ends when the script gets stopped and its sender dropped
+    for event in first.into_iter().chain(std::iter::from_fn(|| events.recv().ok())) {
+        let (handler, args): (&str, Vec<Dynamic>) = match event {
+            Event::State => match inbox.state.lock().unwrap().take() {
+                Some(state) => ("on_state", vec![state_map(&state).into()]),
+                None => continue,
+            },
+            Event::Connect => ("on_connect", vec![]),
+            Event::Disconnect => ("on_disconnect", vec![]),
+            Event::Battery(battery) => ("on_battery", vec![(battery as i64).into()]),
+            Event::Custom(name) => ("on_event", vec![name.into()]),
+        };
+
+        if !handlers.contains(handler) {
+            continue;
+        }
+
+        sandbox.start_run();
+
+        // eval_ast(false): the top level already ran
+        if let Err(e) = engine.call_fn_with_options::<Dynamic>(CallFnOptions::new().eval_ast(false), &mut scope, &ast, handler, args) {
+            report(name, &sandbox, &e);
+        }
+
+        if sandbox.stop.is_stopped() {
+            return;
+        }
+    }
+}
+
+fn report(name: &str, sandbox: &Sandbox, error: &EvalAltResult) {
+    match error {
+        _ if sandbox.stop.is_stopped() => (), //reloaded or removed, not worth mentioning
+        EvalAltResult::ErrorTerminated(reason, _) => println!("script {name}: stopped, {reason}"),
+        error => println!("script {name}: {error}"),
+    }
+}
+
+fn engine(name: &str, sandbox: &Arc<Sandbox>) -> Engine {
+    let mut engine = Engine::new();
+
+    // no files: nothing to import from, nothing to eval
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(1 << 16);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+
+    let print_name = name.to_string();
+    engine.on_print(move |text| println!("script {print_name}: {text}"));
+
+    let debug_name = name.to_string();
+    engine.on_debug(move |text, _, pos| println!("script {debug_name} {pos}: {text}"));
+
+    let s = sandbox.clone();
+    engine.on_progress(move |operations| {
+        if operations % 256 != 0 {
+            None
+        } else if s.stop.is_stopped() {
+            Some("stopped".into())
+        } else if Instant::now() > *s.deadline.lock().unwrap() {
+            Some(format!("busy for more than {}s", s.api.config.time_limit).into())
+        } else {
+            None
+        }
+    });
+
+    let s = sandbox.clone();
+    engine.register_fn("sleep", move |ms: i64| s.sleep(Duration::from_millis(ms.max(0) as u64)));
+
+    let s = sandbox.clone();
+    engine.register_fn("set_color", move |r: i64, g: i64, b: i64| {
+        s.set(CmdData { rgb: [r, g, b].map(|c| c.clamp(0, 255) as u8), ..Default::default() })
+    });
+
+    let s = sandbox.clone();
+    engine.register_fn("set_color", move |hex: &str| -> ScriptResult<bool> {
+        let digits = hex.trim_start_matches('#');
+        let value = u32::from_str_radix(digits, 16).ok().filter(|_| digits.len() == 6).ok_or_else(|| format!("\"{hex}\" isn't a color like \"#ff8800\""))?;
+        let [_, r, g, b] = value.to_be_bytes();
+        s.set(CmdData { rgb: [r, g, b], ..Default::default() })
+    });
+
+    let s = sandbox.clone();
+    engine.register_fn("set_mode", move |mode: i64| -> ScriptResult<bool> {
+        match mode {
+            1 ..= 8 => s.set(CmdData { mode: mode as u8, ..Default::default() }),
+            _ => Err(format!("there's no mode {mode}").into()),
+        }
+    });
+
+    let s = sandbox.clone();
+    engine.register_fn("set_mode", move |name: &str| -> ScriptResult<bool> {
+        match MODES.iter().position(|mode| mode.eq_ignore_ascii_case(name)) {
+            Some(idx) => s.set(CmdData { mode: idx as u8 + 1, ..Default::default() }),
+            None => Err(format!("there's no mode \"{name}\"").into()),
+        }
+    });
+
+    let s = sandbox.clone();
+    engine.register_fn("set_settings", move |brightness: i64, speed: i64| {
+        s.set(CmdData { settings: [setting(brightness), setting(speed)], ..Default::default() })
+    });
+
+    let s = sandbox.clone();
+    engine.register_fn("set_brightness", move |brightness: i64| s.set(CmdData { settings: [setting(brightness), 0], ..Default::default() }));
+
+    let s = sandbox.clone();
+    engine.register_fn("set_speed", move |speed: i64| s.set(CmdData { settings: [0, setting(speed)], ..Default::default() }));
+
+    let s = sandbox.clone();
+    engine.register_fn("set_audio_profile", move |profile: i64| -> ScriptResult<bool> {
+        match profile {
+            0 ..= 3 => s.send(BtCommands::SetAudioProfile(profile as u8)),
+            _ => Err(format!("there's no audio profile {profile}").into()),
+        }
+    });
+
+    let s = sandbox.clone();
+    engine.register_fn("apply_preset", move |name: &str| -> ScriptResult<bool> {
+        match Preset::find(&s.api.presets, name) {
+            Some(preset) => s.set(CmdData::from(preset)),
+            None => Err(format!("no preset named \"{name}\"").into()),
+        }
+    });
+
+    let s = sandbox.clone();
+    engine.register_fn("state", move || state_map(&s.api.state_rx.borrow()));
+
+    let s = sandbox.clone();
+    engine.register_fn("battery", move || optional(s.api.state_rx.borrow().battery));
+
+    let s = sandbox.clone();
+    engine.register_fn("connected", move || matches!(s.api.state_rx.borrow().status, BtToGui::Ready));
+
+    engine
+}
+
+// 0-63, 0 leaves it as it is
+fn setting(value: i64) -> u8 {
+    value.clamp(0, 63) as u8
+}
+
+// () for anything that isn't known
+fn optional(value: Option<u8>) -> Dynamic {
+    value.map_or(Dynamic::UNIT, |value| Dynamic::from_int(value as i64))
+}
+
+fn state_map(state: &HeadsetState) -> Map {
+    let lighting = state.lighting.unwrap_or_default();
+    let nonzero = |value: u8| optional((value != 0).then_some(value));
+
+    let mut map = Map::new();
+    map.insert("status".into(), state.status.name().into());
+    map.insert("connected".into(), matches!(state.status, BtToGui::Ready).into());
+    map.insert("headset".into(), state.headset.map_or(Dynamic::UNIT, |model| model.name().into()));
+    map.insert("battery".into(), optional(state.battery));
+    map.insert("audio_profile".into(), optional(state.audio_profile));
+    map.insert("mode".into(), nonzero(lighting.mode));
+    map.insert("brightness".into(), nonzero(lighting.settings[0]));
+    map.insert("speed".into(), nonzero(lighting.settings[1]));
+
+    let color = match lighting.rgb {
+        [0, 0, 0] => Dynamic::UNIT,
+        rgb => rgb.map(|c| Dynamic::from_int(c as i64)).to_vec().into(),
+    };
+
+    map.insert("color".into(), color);
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api(config: ScriptConfig) -> (Api, mpsc::Receiver<BtCommands>, watch::Sender<HeadsetState>) {
+        let (tx, rx) = mpsc::channel(64);
+        let (state_tx, state_rx) = watch::channel(HeadsetState { status: BtToGui::Ready, ..Default::default() });
+        let presets = vec![Preset { name: String::from("On Air"), mode: 7, rgb: [255, 0, 0], settings: [63, 0] }];
+
+        (Api { config, presets, tx, state_rx }, rx, state_tx)
+    }
+
+    fn start(source: &str, api: Api) -> Script {
+        Script::start(String::from("test.rhai"), source.to_string(), SystemTime::now(), api)
+    }
+
+    fn color(command: &BtCommands) -> [u8; 3] {
+        match command {
+            BtCommands::SetMode(data) => data.rgb,
+            _ => panic!("{command:?}"),
+        }
+    }
+
+    // every color a script without handlers sent before it ended
+    fn colors(source: &str, config: ScriptConfig) -> Vec<[u8; 3]> {
+        let (api, mut rx, _state_tx) = api(config);
+        let _script = start(source, api);
+
+        std::iter::from_fn(|| rx.blocking_recv()).map(|command| color(&command)).collect()
+    }
+
+    #[test]
+    fn functions() {
+        let (api, mut rx, _state_tx) = api(ScriptConfig::default());
+        let source = r##"
+            set_color(1, 2, 300);
+            set_color("#ff8800");
+            set_mode("breath");
+            set_settings(70, 5);
+            set_audio_profile(2);
+            apply_preset("on air");
+        "##;
+
+        let _script = start(source, api);
+        let sent: Vec<String> = std::iter::from_fn(|| rx.blocking_recv()).map(|command| format!("{command:?}")).collect();
+
+        assert_eq!(sent, [
+            "SetMode(CmdData { mode: 0, rgb: [1, 2, 255], settings: [0, 0] })",
+            "SetMode(CmdData { mode: 0, rgb: [255, 136, 0], settings: [0, 0] })",
+            "SetMode(CmdData { mode: 3, rgb: [0, 0, 0], settings: [0, 0] })",
+            "SetMode(CmdData { mode: 0, rgb: [0, 0, 0], settings: [63, 5] })",
+            "SetAudioProfile(2)",
+            "SetMode(CmdData { mode: 7, rgb: [255, 0, 0], settings: [63, 0] })",
+        ]);
+    }
+
+    #[test]
+    fn no_import() {
+        let source = r#"set_color(1, 1, 1); import "os" as os; set_color(2, 2, 2);"#;
+        assert_eq!(colors(source, ScriptConfig::default()), [[1, 1, 1]]);
+    }
+
+    #[test]
+    fn no_eval() {
+        // doesn't even compile
+        assert_eq!(colors(r#"set_color(1, 1, 1); eval("set_color(2, 2, 2)");"#, ScriptConfig::default()), Vec::<[u8; 3]>::new());
+    }
+
+    #[test]
+    fn time_limit() {
+        let config = ScriptConfig { time_limit: 0.2, ..Default::default() };
+        let start = Instant::now();
+
+        assert_eq!(colors("set_color(1, 1, 1); loop {} set_color(2, 2, 2);", config.clone()), [[1, 1, 1]]);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // sleeping doesn't count
+        assert_eq!(colors("sleep(300); set_color(1, 1, 1);", config), [[1, 1, 1]]);
+    }
+
+    #[test]
+    fn rate_limit() {
+        let (api, mut rx, _state_tx) = api(ScriptConfig { commands_per_second: 10.0, ..Default::default() });
+        let _script = start("for i in 0..6 { set_color(i, 0, 0); }", api);
+
+        let mut times = Vec::new();
+
+        while rx.blocking_recv().is_some() {
+            times.push(Instant::now());
+        }
+
+        assert_eq!(times.len(), 6);
+        assert!(times[5] - times[0] >= Duration::from_millis(450), "{:?}", times[5] - times[0]);
+    }
+
+    #[test]
+    fn state_uses_the_merged_lighting() {
+        let (api, mut rx, state_tx) = api(ScriptConfig::default());
+        state_tx.send_modify(|state| state.lighting = Some(CmdData { mode: 2, rgb: [255, 0, 0], settings: [0, 0] }));
+
+        let _script = start("let s = state(); if s.mode == 2 && s.color == [255, 0, 0] && s.brightness == () { set_color(1, 1, 1) }", api);
+        assert_eq!(rx.blocking_recv().map(|command| color(&command)), Some([1, 1, 1]));
+    }
+
+    #[test]
+    fn no_events_during_an_endless_top_level() {
+        let (api, mut rx, state_tx) = api(ScriptConfig::default());
+        let script = start("fn on_state(s) { set_color(9, 9, 9) } loop { set_color(1, 1, 1); sleep(1); }", api);
+
+        for battery in 0 .. 100 {
+            state_tx.send_modify(|state| state.battery = Some(battery));
+            assert!(script.deliver(&Event::State, &state_tx.borrow()));
+            assert!(script.deliver(&Event::Custom(String::from("ping")), &state_tx.borrow()));
+        }
+
+        for _ in 0 .. 20 {
+            assert_eq!(rx.blocking_recv().map(|command| color(&command)), Some([1, 1, 1]));
+        }
+    }
+
+    #[test]
+    fn state_events_are_coalesced() {
+        let (api, mut rx, state_tx) = api(ScriptConfig::default());
+        let script = start("fn on_state(s) { sleep(50); set_color(s.battery, 0, 0) }", api);
+
+        while !script.inbox.listening.load(Ordering::Acquire) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        // far more than fit in the queue while the handler is busy
+        for battery in 1 ..= 100 {
+            state_tx.send_modify(|state| state.battery = Some(battery));
+            assert!(script.deliver(&Event::State, &state_tx.borrow()));
+        }
+
+        let mut last = [0; 3];
+
+        while last != [100, 0, 0] {
+            last = color(&rx.blocking_recv().unwrap());
+        }
+
+        assert!(rx.try_recv().is_err(), "nothing after the latest state");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn hot_reload() {
+        let dir = std::env::temp_dir().join(format!("catcaller-scripts-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.rhai");
+
+        let (api, mut rx, _state_tx) = api(ScriptConfig::default());
+        let (custom_tx, custom_rx) = mpsc::channel(4);
+        tokio::spawn(supervise(dir.clone(), api, custom_rx));
+
+        let wait = async |rx: &mut mpsc::Receiver<BtCommands>| {
+            let command = tokio::time::timeout(RELOAD_TIME * 3, rx.recv()).await;
+            command.ok().flatten().map(|command| color(&command))
+        };
+
+        std::fs::write(&path, "set_color(1, 0, 0); fn on_event(name) { set_color(2, 0, 0) }").unwrap();
+        assert_eq!(wait(&mut rx).await, Some([1, 0, 0]));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        custom_tx.send(String::from("ping")).await.unwrap();
+        assert_eq!(wait(&mut rx).await, Some([2, 0, 0]));
+
+        // a change restarts it
+        std::fs::write(&path, "set_color(3, 0, 0); fn on_event(name) { set_color(4, 0, 0) }").unwrap();
+        assert_eq!(wait(&mut rx).await, Some([3, 0, 0]));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        custom_tx.send(String::from("ping")).await.unwrap();
+        assert_eq!(wait(&mut rx).await, Some([4, 0, 0]));
+
+        // and removing it stops it
+        std::fs::remove_file(&path).unwrap();
+        tokio::time::sleep(RELOAD_TIME * 2).await;
+        custom_tx.send(String::from("ping")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(rx.try_recv().is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,243 @@
+// ui themes. built in are light (the original pastel look), dark and high-contrast, more can be
+// dropped into <config dir>/themes/<name>.toml. fields that are left out come from light:
+//   dark = true
+//   background = [30, 30, 46]
+//   text = [205, 214, 244]
+//   font_size = 17.0
+//   font = "/usr/share/fonts/TTF/Inter-Regular.ttf"
+// a file named like a built in theme replaces it. theme = "auto" in [gui] picks light or dark
+// following the desktop's color scheme
+
+use std::path::PathBuf;
+
+use egui::{Color32, Context, FontData, FontDefinitions, FontFamily, FontId, TextStyle};
+use serde::Deserialize;
+
+use crate::config::config_dir;
+
+pub const AUTO: &str = "auto";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    #[serde(skip)]
+    pub name: String,
+    pub dark: bool, //egui's dark or light visuals underneath
+    pub background: [u8; 3],
+    pub text: [u8; 3],
+    pub button: [u8; 3],
+    pub button_hovered: [u8; 3],
+    pub button_text: [u8; 3],
+    pub button_text_hovered: [u8; 3],
+    pub widget: [u8; 3], //slider rails, text fields
+    pub font_size: f32,
+    pub font: Option<PathBuf>, //ttf or otf, instead of egui's font
+    pub margin: f32, //around the window contents
+    pub spacing: f32, //between sections
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: String::from("light"),
+            dark: false,
+            background: [0xB4, 0xE4, 0xFF],
+            text: [21, 40, 51],
+            button: [255, 206, 254],
+            button_hovered: [255, 153, 253],
+            button_text: [76, 0, 51],
+            button_text_hovered: [51, 0, 34],
+            widget: [201, 244, 170],
+            font_size: 15.0,
+            font: None,
+            margin: 15.0,
+            spacing: 18.0,
+        }
+    }
+}
+
+impl Theme {
+    fn builtin() -> Vec<Theme> {
+        let light = Theme::default();
+
+        let dark = Theme {
+            name: String::from("dark"),
+            dark: true,
+            background: [30, 34, 42],
+            text: [215, 222, 230],
+            button: [74, 58, 92],
+            button_hovered: [104, 78, 132],
+            button_text: [240, 226, 255],
+            button_text_hovered: [255, 255, 255],
+            widget: [62, 92, 70],
+            ..light.clone()
+        };
+
+        let high_contrast = Theme {
+            name: String::from("high-contrast"),
+            dark: true,
+            background: [0, 0, 0],
+            text: [255, 255, 255],
+            button: [255, 230, 0],
+            button_hovered: [255, 255, 255],
+            button_text: [0, 0, 0],
+            button_text_hovered: [0, 0, 0],
+            widget: [90, 90, 90],
+            font_size: 17.0,
+            ..light.clone()
+        };
+
+        vec![light, dark, high_contrast]
+    }
+
+    pub fn text_color(&self) -> Color32 {
+        rgb(self.text)
+    }
+
+    pub fn background_color(&self) -> Color32 {
+        rgb(self.background)
+    }
+
+    fn apply(&self, ctx: &Context) {
+        let mut visuals = match self.dark {
+            true => egui::Visuals::dark(),
+            false => egui::Visuals::light(),
+        };
+
+        visuals.widgets.inactive.weak_bg_fill = rgb(self.button);
+        visuals.widgets.hovered.weak_bg_fill = rgb(self.button_hovered);
+        visuals.widgets.inactive.fg_stroke.color = rgb(self.button_text);
+        visuals.widgets.hovered.fg_stroke.color = rgb(self.button_text_hovered);
+        visuals.widgets.inactive.bg_fill = rgb(self.widget);
+
+        ctx.set_visuals(visuals);
+
+        let mut fonts = FontDefinitions::default();
+
+        if let Some(path) = &self.font {
+            match std::fs::read(path) {
+                Ok(data) => {
+                    fonts.font_data.insert(self.name.clone(), FontData::from_owned(data));
+
+                    for family in [FontFamily::Proportional, FontFamily::Monospace] {
+                        fonts.families.entry(family).or_default().insert(0, self.name.clone());
+                    }
+                }
+
+                Err(e) => println!("theme {}: can't read {}: {e}", self.name, path.display()),
+            }
+        }
+
+        ctx.set_fonts(fonts);
+
+        let mut style = (*ctx.style()).clone();
+
+        style.text_styles = [
+            TextStyle::Heading, TextStyle::Body, TextStyle::Monospace, TextStyle::Button, TextStyle::Small,
+        ].into_iter().map(|text_style| (text_style, FontId::new(self.font_size, FontFamily::Proportional))).collect();
+
+        ctx.set_style(style);
+    }
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> Color32 {
+    Color32::from_rgb(r, g, b)
+}
+
+// all themes, which one is picked, and whether that still has to reach egui
+pub struct Themes {
+    themes: Vec<Theme>,
+    selected: String, //a theme name or AUTO
+    system_dark: bool,
+    changed: bool,
+}
+
+impl Default for Themes {
+    fn default() -> Self {
+        Self { themes: Theme::builtin(), selected: String::from(AUTO), system_dark: false, changed: true }
+    }
+}
+
+impl Themes {
+    pub fn load(selected: &str) -> Self {
+        Self::load_from(config_dir().map(|dir| dir.join("themes")), selected)
+    }
+
+    // the built in themes plus the files in dir
+    pub fn load_from(dir: Option<PathBuf>, selected: &str) -> Self {
+        let mut themes = Theme::builtin();
+
+        let mut paths: Vec<PathBuf> = dir
+            .and_then(|dir| std::fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+
+        paths.sort();
+
+        for path in paths {
+            let Some(name) = path.file_stem().map(|stem| stem.to_string_lossy().to_string()) else {
+                continue;
+            };
+
+            let theme = match std::fs::read_to_string(&path).map(|text| toml::from_str::<Theme>(&text)) {
+                Ok(Ok(theme)) => Theme { name, ..theme },
+                Ok(Err(e)) => {
+                    println!("error in {}: {e}", path.display());
+                    continue;
+                }
+
+                Err(e) => {
+                    println!("can't read {}: {e}", path.display());
+                    continue;
+                }
+            };
+
+            match themes.iter_mut().find(|known| known.name == theme.name) {
+                Some(known) => *known = theme,
+                None => themes.push(theme),
+            }
+        }
+
+        let mut themes = Self { themes, ..Default::default() };
+        themes.select(selected);
+        themes
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(AUTO).chain(self.themes.iter().map(|theme| theme.name.as_str()))
+    }
+
+    pub fn select(&mut self, name: &str) {
+        if name != AUTO && !self.themes.iter().any(|theme| theme.name == name) {
+            return println!("no theme named \"{name}\"");
+        }
+
+        self.selected = name.to_string();
+        self.changed = true;
+    }
+
+    pub fn set_system_dark(&mut self, dark: bool) {
+        self.changed |= self.selected == AUTO && self.system_dark != dark;
+        self.system_dark = dark;
+    }
+
+    pub fn current(&self) -> &Theme {
+        let name = match self.selected.as_str() {
+            AUTO if self.system_dark => "dark",
+            AUTO => "light",
+            name => name,
+        };
+
+        self.themes.iter().find(|theme| theme.name == name).unwrap_or(&self.themes[0])
+    }
+
+    // once per frame, before any widgets
+    pub fn apply_if_changed(&mut self, ctx: &Context) {
+        if std::mem::take(&mut self.changed) {
+            self.current().apply(ctx);
+        }
+    }
+}
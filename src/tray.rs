@@ -0,0 +1,317 @@
+// tray icon (StatusNotifierItem), so the window can be closed while the bluetooth link stays up.
+// needs a StatusNotifierWatcher on the session bus: kde, most panels and gnome's appindicator
+// extension have one. the tests run it against a stand-in watcher on a private dbus-daemon
+
+use ksni::menu::{StandardItem, SubMenu};
+use ksni::{MenuItem, ToolTip, TrayMethods};
+use tokio::sync::{mpsc, watch};
+use winit::event_loop::EventLoopProxy;
+
+use crate::bt::{BtCommands, BtToGui, CmdData, HeadsetState, MODES};
+use crate::config::Preset;
+use crate::ui::GuiEvent;
+
+const LIGHTS_OFF: u8 = 6;
+
+struct Tray {
+    state: HeadsetState,
+    presets: Vec<Preset>,
+    tx: mpsc::Sender<BtCommands>,
+    gui: Box<dyn Fn(GuiEvent) + Send>, //the event loop's proxy, outside of the tests
+}
+
+impl Tray {
+    fn ready(&self) -> bool {
+        matches!(self.state.status, BtToGui::Ready)
+    }
+
+    fn send(&self, data: CmdData) {
+        if !self.ready() {
+            return;
+        }
+
+        match self.tx.try_send(BtCommands::SetMode(data)) {
+            Ok(_) => (),
+            Err(_) => println!("queue full!"),
+        };
+    }
+
+    fn status(&self) -> String {
+        let status = match self.state.status {
+            BtToGui::Init => "Searching for BT adapter",
+            BtToGui::AdapterConnected => "Searching for headset",
+            BtToGui::Found(_) | BtToGui::Connected => "Connecting",
+            BtToGui::Ready => "Connected",
+        };
+
+        match self.state.battery {
+            Some(battery) if self.ready() => format!("{status}, battery {battery}%"),
+            _ => status.to_string(),
+        }
+    }
+}
+
+impl ksni::Tray for Tray {
+    fn id(&self) -> String {
+        String::from("catcaller")
+    }
+
+    fn title(&self) -> String {
+        String::from("Yowu CatCaller")
+    }
+
+    fn icon_name(&self) -> String {
+        String::from("audio-headphones")
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: self.title(),
+            description: self.status(),
+            ..Default::default()
+        }
+    }
+
+    // left click
+    fn activate(&mut self, _x: i32, _y: i32) {
+        (self.gui)(GuiEvent::ToggleWindow);
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let ready = self.ready();
+
+        let modes = (1 ..= 8u8)
+            .map(|mode| StandardItem {
+                label: MODES[mode as usize - 1].to_string(),
+                enabled: ready,
+                activate: Box::new(move |tray: &mut Self| tray.send(CmdData { mode, ..Default::default() })),
+                ..Default::default()
+            }.into())
+            .collect();
+
+        let presets = self.presets
+            .iter()
+            .map(|preset| {
+                let command = CmdData::from(preset);
+
+                StandardItem {
+                    label: preset.name.replace('_', "__"), //single underscores mark access keys
+                    enabled: ready,
+                    activate: Box::new(move |tray: &mut Self| tray.send(command)),
+                    ..Default::default()
+                }.into()
+            })
+            .collect();
+
+        vec![
+            StandardItem { label: self.status(), enabled: false, ..Default::default() }.into(),
+            MenuItem::Separator,
+            SubMenu { label: String::from("Mode"), enabled: ready, submenu: modes, ..Default::default() }.into(),
+            SubMenu { label: String::from("Presets"), enabled: ready && !self.presets.is_empty(), submenu: presets, ..Default::default() }.into(),
+            StandardItem {
+                label: String::from("Lights off"),
+                enabled: ready,
+                activate: Box::new(|tray: &mut Self| tray.send(CmdData { mode: LIGHTS_OFF, ..Default::default() })),
+                ..Default::default()
+            }.into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: String::from("Show/hide window"),
+                activate: Box::new(|tray: &mut Self| (tray.gui)(GuiEvent::ToggleWindow)),
+                ..Default::default()
+            }.into(),
+            StandardItem {
+                label: String::from("Quit"),
+                activate: Box::new(|tray: &mut Self| (tray.gui)(GuiEvent::Quit)),
+                ..Default::default()
+            }.into(),
+        ]
+    }
+}
+
+pub async fn serve(presets: Vec<Preset>, tx: mpsc::Sender<BtCommands>, state_rx: watch::Receiver<HeadsetState>, proxy: EventLoopProxy<GuiEvent>) {
+    let gui = proxy.clone();
+
+    if let Err(e) = run(presets, tx, state_rx, Box::new(move |event| { let _ = gui.send_event(event); })).await {
+        println!("tray: {e}");
+    }
+
+    let _ = proxy.send_event(GuiEvent::TrayGone);
+}
+
+async fn run(presets: Vec<Preset>, tx: mpsc::Sender<BtCommands>, mut state_rx: watch::Receiver<HeadsetState>, gui: Box<dyn Fn(GuiEvent) + Send>) -> Result<(), ksni::Error> {
+    let state = state_rx.borrow_and_update().clone();
+    let handle = Tray { state, presets, tx, gui }.spawn().await?;
+
+    while state_rx.changed().await.is_ok() {
+        let state = state_rx.borrow_and_update().clone();
+
+        if handle.update(|tray| tray.state = state).await.is_none() {
+            break; //tray service shut down
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tokio::time::{timeout, Duration};
+    use zbus::zvariant::{OwnedValue, Value};
+    use zbus::{dbus_interface, CacheProperties, Connection, ConnectionBuilder, Proxy, ProxyBuilder};
+
+    use super::*;
+    use crate::dbus_service::test_bus::private_bus;
+
+    // the watcher side of a panel: remembers who registered
+    struct Watcher(mpsc::UnboundedSender<String>);
+
+    #[dbus_interface(name = "org.kde.StatusNotifierWatcher")]
+    impl Watcher {
+        fn register_status_notifier_item(&self, service: String) {
+            let _ = self.0.send(service);
+        }
+
+        #[dbus_interface(property)]
+        fn is_status_notifier_host_registered(&self) -> bool {
+            true
+        }
+
+        #[dbus_interface(property)]
+        fn protocol_version(&self) -> i32 {
+            0
+        }
+    }
+
+    type Layout = (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+    // menu item ids by label, submenus included
+    fn menu_ids(layout: &Layout, ids: &mut HashMap<String, (i32, bool)>) {
+        let (id, properties, children) = layout;
+
+        if let Some(label) = properties.get("label").and_then(|label| <&str>::try_from(&**label).ok()) {
+            let enabled = properties.get("enabled").and_then(|enabled| bool::try_from(&**enabled).ok()).unwrap_or(true);
+            ids.insert(label.to_string(), (*id, enabled));
+        }
+
+        for child in children {
+            let child = match &**child {
+                Value::Value(child) => (**child).clone(),
+                child => child.clone(),
+            };
+
+            menu_ids(&Layout::try_from(OwnedValue::from(child)).unwrap(), ids);
+        }
+    }
+
+    async fn proxy<'a>(connection: &Connection, service: &str, path: &'static str, interface: &'static str) -> Proxy<'a> {
+        ProxyBuilder::new_bare(connection)
+            .destination(service.to_string()).unwrap()
+            .path(path).unwrap()
+            .interface(interface).unwrap()
+            .cache_properties(CacheProperties::No)
+            .build().await.unwrap()
+    }
+
+    // ksni always connects to the session bus, and the other session bus users (hotkeys, the color
+    // scheme, the dbus service) would follow DBUS_SESSION_BUS_ADDRESS too if it changed under them.
+    // so the private bus is only ever the session bus of a child process, running this test again
+    #[tokio::test]
+    async fn tray() {
+        let Ok(address) = std::env::var("CATCALLER_TEST_BUS") else {
+            let Some((_bus, address)) = private_bus("tray") else { return };
+
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "tray::tests::tray", "--nocapture"])
+                .env("CATCALLER_TEST_BUS", &address)
+                .env("DBUS_SESSION_BUS_ADDRESS", &address)
+                .status()
+                .unwrap();
+
+            assert!(status.success());
+            return;
+        };
+
+        let (registered_tx, mut registered) = mpsc::unbounded_channel();
+        let _watcher = ConnectionBuilder::address(address.as_str()).unwrap()
+            .name("org.kde.StatusNotifierWatcher").unwrap()
+            .serve_at("/StatusNotifierWatcher", Watcher(registered_tx)).unwrap()
+            .build().await.unwrap();
+
+        let (tx, mut rx) = mpsc::channel(8);
+        let (gui_tx, mut gui_rx) = mpsc::unbounded_channel();
+        let (state_tx, state_rx) = watch::channel(HeadsetState { status: BtToGui::AdapterConnected, ..Default::default() });
+        let presets = vec![Preset { name: String::from("on_air"), mode: 7, rgb: [255, 0, 0], settings: [63, 0] }];
+        tokio::spawn(run(presets, tx, state_rx, Box::new(move |event| { let _ = gui_tx.send(event); })));
+
+        let service = timeout(Duration::from_secs(5), registered.recv()).await.expect("the tray never registered").unwrap();
+        let host = ConnectionBuilder::address(address.as_str()).unwrap().build().await.unwrap();
+        let item = proxy(&host, &service, "/StatusNotifierItem", "org.kde.StatusNotifierItem").await;
+        let menu = proxy(&host, &service, "/MenuBar", "com.canonical.dbusmenu").await;
+
+        type ToolTip = (String, Vec<(i32, i32, Vec<u8>)>, String, String);
+        let description = || async { item.get_property::<ToolTip>("ToolTip").await.unwrap().3 };
+        let ids = || async {
+            let (_, layout): (u32, Layout) = menu.call("GetLayout", &(0i32, -1i32, Vec::<String>::new())).await.unwrap();
+            let mut ids = HashMap::new();
+            menu_ids(&layout, &mut ids);
+            ids
+        };
+        let click = |id: i32| {
+            let menu = &menu;
+            async move { menu.call_method("Event", &(id, "clicked", Value::from(0i32), 0u32)).await.unwrap() }
+        };
+
+        assert_eq!(item.get_property::<String>("Title").await.unwrap(), "Yowu CatCaller");
+        assert_eq!(item.get_property::<String>("IconName").await.unwrap(), "audio-headphones");
+        assert_eq!(description().await, "Searching for headset");
+
+        // nothing to send to before the headset is there
+        let ids_before = ids().await;
+        assert!(!ids_before["Lights off"].1);
+        click(ids_before["Lights off"].0).await;
+
+        state_tx.send(HeadsetState { status: BtToGui::Ready, battery: Some(80), ..Default::default() }).unwrap();
+
+        let start = tokio::time::Instant::now();
+        while description().await != "Connected, battery 80%" {
+            assert!(start.elapsed() < Duration::from_secs(5), "the tooltip never changed");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let ids = ids().await;
+        assert!(ids["Lights off"].1);
+        assert!(ids.contains_key("on__air"), "underscores are doubled: {ids:?}");
+
+        for label in ["Lights off", "Breath", "on__air"] {
+            click(ids[label].0).await;
+        }
+
+        let mut sent = Vec::new();
+        for _ in 0 .. 3 {
+            sent.push(format!("{:?}", timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap()));
+        }
+
+        assert_eq!(sent, [
+            "SetMode(CmdData { mode: 6, rgb: [0, 0, 0], settings: [0, 0] })",
+            "SetMode(CmdData { mode: 3, rgb: [0, 0, 0], settings: [0, 0] })",
+            "SetMode(CmdData { mode: 7, rgb: [255, 0, 0], settings: [63, 0] })",
+        ]);
+
+        assert!(rx.try_recv().is_err(), "the click while searching didn't get queued");
+
+        // left click on the icon, and the window items
+        item.call_method("Activate", &(0i32, 0i32)).await.unwrap();
+        click(ids["Show/hide window"].0).await;
+        click(ids["Quit"].0).await;
+
+        let mut events = Vec::new();
+        for _ in 0 .. 3 {
+            events.push(format!("{:?}", timeout(Duration::from_secs(5), gui_rx.recv()).await.unwrap().unwrap()));
+        }
+
+        assert_eq!(events, ["ToggleWindow", "ToggleWindow", "Quit"]);
+    }
+}
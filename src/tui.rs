@@ -0,0 +1,555 @@
+// `blatand tui`: the controls of create_ui in a terminal, for ssh sessions and tiling wms. talks to
+// the same backend as the gui (the daemon if one is running) and goes through ui::run_action, so
+// shortcuts and presets do the same thing in both.
+//   tab / shift+tab    next / previous section
+//   arrows             pick something, or change a value (shift for bigger steps)
+//   enter              send it to the headset
+//   1-8                mode, like the gui's shortcuts
+//   0-9 in color       type the selected channel's value
+//   h in color         switch between rgb and hsv
+//   q / esc            quit
+
+use std::io::Write;
+use std::time::Duration;
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{self, EnterAlternateScreen};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{Frame, Terminal};
+use tokio::sync::{mpsc, watch};
+
+use crate::bt::{BtCommands, BtToGui, CmdData, MODES};
+use crate::config::{Config, Preset};
+use crate::keys::Action;
+use crate::ui::{self, UiState};
+
+const SPINNER: [&str; 8] = ["⣾", "⣽", "⣻", "⢿", "⡿", "⣟", "⣯", "⣷"];
+const SPINNER_TIME: Duration = Duration::from_millis(100);
+const BIG_STEP: u16 = 8; //with shift held
+const MODE_COLUMNS: usize = 4;
+const AUDIO_PROFILES: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Mode,
+    Color,
+    Settings,
+    AudioProfile,
+    Presets,
+}
+
+const SECTIONS: [Section; 5] = [Section::Mode, Section::Color, Section::Settings, Section::AudioProfile, Section::Presets];
+
+pub struct Tui {
+    pub ui_state: UiState, //shared with the gui's run_action
+    focus: Section,
+    mode_cursor: usize, //0-7
+    channel: usize, //color row
+    hsv: Option<[u16; 3]>, //0-359, 0-100, 0-100 while editing in hsv
+    entry: String, //digits typed into the selected channel
+    setting: usize, //0 brightness, 1 speed
+    audio_cursor: u8,
+    audio_profile: Option<u8>, //last one sent
+    preset_cursor: usize,
+    pub spinner: usize,
+    pub status: Option<String>, //the newest message from the backend, they'd mess up the screen otherwise
+}
+
+impl Tui {
+    pub fn new(presets: Vec<Preset>) -> Self {
+        Self {
+            ui_state: UiState { presets, ..Default::default() },
+            focus: Section::Mode,
+            mode_cursor: 0,
+            channel: 0,
+            hsv: None,
+            entry: String::new(),
+            setting: 0,
+            audio_cursor: 0,
+            audio_profile: None,
+            preset_cursor: 0,
+            spinner: 0,
+            status: None,
+        }
+    }
+
+    pub fn ready(&self) -> bool {
+        matches!(self.ui_state.bt_state, BtToGui::Ready)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, tx: &mpsc::Sender<BtCommands>) {
+        if key.kind != KeyEventKind::Press {
+            return; //windows reports releases too
+        }
+
+        let step = match key.modifiers.contains(KeyModifiers::SHIFT) {
+            true => BIG_STEP,
+            false => 1,
+        };
+
+        let typing = matches!(key.code, KeyCode::Char('0' ..= '9') | KeyCode::Backspace) && self.focus == Section::Color;
+
+        if !typing {
+            self.entry.clear();
+        }
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => ui::run_action(Action::Quit, tx, &mut self.ui_state),
+            KeyCode::Char('q') | KeyCode::Esc => ui::run_action(Action::Quit, tx, &mut self.ui_state),
+            KeyCode::Tab => self.focus = SECTIONS[(self.focus_index() + 1) % SECTIONS.len()],
+            KeyCode::BackTab => self.focus = SECTIONS[(self.focus_index() + SECTIONS.len() - 1) % SECTIONS.len()],
+
+            _ if !self.ready() => (),
+
+            _ if typing => self.type_digit(key.code),
+
+            KeyCode::Char(c @ '1' ..= '8') => {
+                self.mode_cursor = c as usize - '1' as usize;
+                ui::run_action(Action::Mode(self.mode_cursor as u8 + 1), tx, &mut self.ui_state);
+            }
+
+            KeyCode::Char('h') if self.focus == Section::Color => {
+                self.hsv = match self.hsv {
+                    Some(_) => None,
+                    None => Some(rgb_to_hsv(self.ui_state.headset_color)),
+                };
+            }
+
+            KeyCode::Enter => self.apply(tx),
+            code => self.navigate(code, step),
+        }
+
+        // a preset may have changed the color under the hsv values
+        if let Some(hsv) = self.hsv {
+            if hsv_to_rgb(hsv) != self.ui_state.headset_color {
+                self.hsv = Some(rgb_to_hsv(self.ui_state.headset_color));
+            }
+        }
+    }
+
+    fn focus_index(&self) -> usize {
+        SECTIONS.iter().position(|&section| section == self.focus).unwrap_or(0)
+    }
+
+    fn apply(&mut self, tx: &mpsc::Sender<BtCommands>) {
+        let action = match self.focus {
+            Section::Mode => Action::Mode(self.mode_cursor as u8 + 1),
+            Section::Color => Action::ApplyColor,
+            Section::Presets => match self.ui_state.presets.get(self.preset_cursor) {
+                Some(preset) => Action::Preset(preset.name.clone()),
+                None => return,
+            },
+
+            Section::Settings => {
+                let mut data = CmdData::default();
+                data.settings[self.setting] = self.ui_state.headset_settings[self.setting];
+
+                return match tx.try_send(BtCommands::SetMode(data)) {
+                    Ok(_) => (),
+                    Err(_) => self.status = Some(String::from("queue full!")),
+                };
+            }
+
+            Section::AudioProfile => {
+                self.audio_profile = Some(self.audio_cursor);
+
+                return match tx.try_send(BtCommands::SetAudioProfile(self.audio_cursor)) {
+                    Ok(_) => (),
+                    Err(_) => self.status = Some(String::from("queue full!")),
+                };
+            }
+        };
+
+        ui::run_action(action, tx, &mut self.ui_state);
+    }
+
+    fn navigate(&mut self, code: KeyCode, step: u16) {
+        let delta: i32 = match code {
+            KeyCode::Left => -(step as i32),
+            KeyCode::Right => step as i32,
+            _ => 0,
+        };
+
+        match (self.focus, code) {
+            (Section::Mode, KeyCode::Left | KeyCode::Right) => self.mode_cursor = (self.mode_cursor as i32 + delta.signum()).rem_euclid(MODES.len() as i32) as usize,
+            (Section::Mode, KeyCode::Up | KeyCode::Down) => self.mode_cursor = (self.mode_cursor + MODE_COLUMNS) % MODES.len(),
+
+            (Section::Color, KeyCode::Up) => self.channel = self.channel.saturating_sub(1),
+            (Section::Color, KeyCode::Down) => self.channel = (self.channel + 1).min(2),
+            (Section::Color, KeyCode::Left | KeyCode::Right) => {
+                let value = self.channel_value(self.channel) as i32 + delta;
+                self.set_channel(self.channel, value.clamp(0, self.channel_max(self.channel) as i32) as u16);
+            }
+
+            (Section::Settings, KeyCode::Up) => self.setting = 0,
+            (Section::Settings, KeyCode::Down) => self.setting = 1,
+            (Section::Settings, KeyCode::Left | KeyCode::Right) => {
+                let setting = &mut self.ui_state.headset_settings[self.setting];
+                *setting = (*setting as i32 + delta).clamp(0, 63) as u8;
+            }
+
+            (Section::AudioProfile, KeyCode::Left | KeyCode::Right) => self.audio_cursor = (self.audio_cursor as i32 + delta.signum()).clamp(0, AUDIO_PROFILES as i32 - 1) as u8,
+
+            (Section::Presets, KeyCode::Up) => self.preset_cursor = self.preset_cursor.saturating_sub(1),
+            (Section::Presets, KeyCode::Down) => self.preset_cursor = (self.preset_cursor + 1).min(self.ui_state.presets.len().saturating_sub(1)),
+
+            _ => (),
+        }
+    }
+
+    fn type_digit(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(digit) => self.entry.push(digit),
+            _ => {
+                self.entry.pop();
+            }
+        }
+
+        let max = self.channel_max(self.channel);
+        let mut value: u16 = self.entry.parse().unwrap_or(0);
+
+        // out of range starts a new number with the digit just typed
+        if value > max {
+            self.entry = self.entry.split_off(self.entry.len() - 1);
+            value = self.entry.parse().unwrap_or(0);
+        }
+
+        self.set_channel(self.channel, value);
+    }
+
+    fn channel_names(&self) -> [&'static str; 3] {
+        match self.hsv {
+            Some(_) => ["H", "S", "V"],
+            None => ["R", "G", "B"],
+        }
+    }
+
+    fn channel_max(&self, channel: usize) -> u16 {
+        match (self.hsv, channel) {
+            (Some(_), 0) => 359,
+            (Some(_), _) => 100,
+            (None, _) => 255,
+        }
+    }
+
+    fn channel_value(&self, channel: usize) -> u16 {
+        match self.hsv {
+            Some(hsv) => hsv[channel],
+            None => self.ui_state.headset_color[channel] as u16,
+        }
+    }
+
+    fn set_channel(&mut self, channel: usize, value: u16) {
+        match &mut self.hsv {
+            Some(hsv) => {
+                hsv[channel] = value;
+                self.ui_state.headset_color = hsv_to_rgb(*hsv);
+            }
+
+            None => self.ui_state.headset_color[channel] = value as u8,
+        }
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let area = frame.area();
+
+        if !self.ready() {
+            let status = match &self.ui_state.bt_state {
+                BtToGui::Init => "Searching for BT adapter...",
+                BtToGui::AdapterConnected => "Adapter connected. Searching for headset...",
+                BtToGui::Found(_) => "Headset found. Connecting to headset...",
+                BtToGui::Connected => "Connected. Discovering services...",
+                BtToGui::Ready => unreachable!(),
+            };
+
+            let line = Line::from(vec![Span::raw(SPINNER[self.spinner % SPINNER.len()]), Span::raw(" "), Span::raw(status)]);
+            frame.render_widget(Paragraph::new(line).block(Block::bordered().title(" catcaller ")), area);
+            return;
+        }
+
+        let [header, modes, color, settings, audio, presets, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(4),
+            Constraint::Length(5),
+            Constraint::Length(4),
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ]).areas(area);
+
+        let mut title = vec![Span::styled(self.ui_state.headset_type.as_str(), Style::new().add_modifier(Modifier::BOLD))];
+
+        if self.ui_state.unsafe_flashing {
+            title.push(Span::styled("  flash limits are off", Style::new().fg(Color::Red)));
+        }
+
+        frame.render_widget(Paragraph::new(Line::from(title)), header);
+
+        if let Some(status) = &self.status {
+            frame.render_widget(Paragraph::new(status.as_str()).style(Style::new().fg(Color::Yellow)).right_aligned(), header);
+        }
+
+        self.draw_modes(frame, modes);
+        self.draw_color(frame, color);
+        self.draw_settings(frame, settings);
+        self.draw_audio_profile(frame, audio);
+        self.draw_presets(frame, presets);
+
+        let keys = match self.focus {
+            Section::Color => "←→ change  ↑↓ channel  0-9 type  h rgb/hsv  enter apply  tab next  q quit",
+            Section::Settings => "←→ change  ↑↓ setting  enter apply  tab next  q quit",
+            _ => "arrows pick  enter apply  1-8 mode  tab next  q quit",
+        };
+
+        frame.render_widget(Paragraph::new(keys).style(Style::new().add_modifier(Modifier::DIM)), help);
+    }
+
+    fn section(&self, section: Section, title: String) -> Block<'static> {
+        let block = Block::bordered().title(format!(" {title} "));
+
+        match self.focus == section {
+            true => block.border_style(Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            false => block,
+        }
+    }
+
+    // [brackets] and reversed for the keyboard cursor, • for what the headset was last sent
+    fn item(&self, section: Section, text: &str, cursor: bool, active: bool) -> Span<'static> {
+        let cursor = cursor && self.focus == section;
+        let (open, close) = if cursor { ("[", "]") } else { (" ", " ") };
+        let dot = if active { "•" } else { " " };
+        let span = Span::raw(format!("{open}{dot}{text}{close}"));
+
+        match cursor {
+            true => span.style(Style::new().add_modifier(Modifier::REVERSED)),
+            false => span,
+        }
+    }
+
+    fn draw_modes(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = MODES.chunks(MODE_COLUMNS).enumerate().map(|(row, chunk)| {
+            Line::from(chunk.iter().enumerate().map(|(column, mode)| {
+                let idx = row * MODE_COLUMNS + column;
+                self.item(Section::Mode, &format!("{mode:<11}"), idx == self.mode_cursor, idx + 1 == self.ui_state.headset_mode as usize)
+            }).collect::<Vec<_>>())
+        }).collect();
+
+        frame.render_widget(Paragraph::new(lines).block(self.section(Section::Mode, String::from("Mode"))), area);
+    }
+
+    fn draw_color(&self, frame: &mut Frame, area: Rect) {
+        let title = match self.hsv {
+            Some(_) => "Color (HSV)",
+            None => "Color (RGB)",
+        };
+
+        let block = self.section(Section::Color, String::from(title));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let [channels, swatch] = Layout::horizontal([Constraint::Min(10), Constraint::Length(10)]).spacing(2).areas(inner);
+        let rows: [Rect; 3] = Layout::vertical([Constraint::Length(1); 3]).areas(channels);
+
+        for (channel, (row, name)) in rows.into_iter().zip(self.channel_names()).enumerate() {
+            let value = self.channel_value(channel);
+            let cursor = if channel == self.channel && self.focus == Section::Color { ">" } else { " " };
+
+            frame.render_widget(bar(format!("{cursor}{name} {value:>3} "), value as f64 / self.channel_max(channel) as f64, row.width), row);
+        }
+
+        // truecolor, terminals without it get the closest color they have
+        let [r, g, b] = self.ui_state.headset_color;
+        let [hex, fill] = Layout::vertical([Constraint::Length(1), Constraint::Min(1)]).areas(swatch);
+
+        frame.render_widget(Paragraph::new(format!("#{r:02x}{g:02x}{b:02x}")), hex);
+        frame.render_widget(Block::new().style(Style::new().bg(Color::Rgb(r, g, b))), fill);
+    }
+
+    fn draw_settings(&self, frame: &mut Frame, area: Rect) {
+        let block = self.section(Section::Settings, String::from("Settings"));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let rows: [Rect; 2] = Layout::vertical([Constraint::Length(1); 2]).areas(inner);
+
+        for (setting, (row, name)) in rows.into_iter().zip(["Brightness", "Speed"]).enumerate() {
+            let value = self.ui_state.headset_settings[setting];
+            let cursor = if setting == self.setting && self.focus == Section::Settings { ">" } else { " " };
+
+            frame.render_widget(bar(format!("{cursor}{name:<10} {value:>2} "), value as f64 / 63.0, row.width), row);
+        }
+    }
+
+    fn draw_audio_profile(&self, frame: &mut Frame, area: Rect) {
+        let profiles: Vec<Span> = (0 .. AUDIO_PROFILES)
+            .map(|profile| self.item(Section::AudioProfile, &profile.to_string(), profile == self.audio_cursor, Some(profile) == self.audio_profile))
+            .collect();
+
+        frame.render_widget(Paragraph::new(Line::from(profiles)).block(self.section(Section::AudioProfile, String::from("Audio profile"))), area);
+    }
+
+    fn draw_presets(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = match self.ui_state.presets.is_empty() {
+            true => vec![Line::from("No presets yet, add some to the config file.")],
+            false => self.ui_state.presets.iter().enumerate()
+                .map(|(idx, preset)| Line::from(self.item(Section::Presets, &preset.name, idx == self.preset_cursor, false)))
+                .collect(),
+        };
+
+        // keeps the cursor on screen when there are more presets than rows
+        let visible = area.height.saturating_sub(2) as usize;
+        let scroll = (self.preset_cursor + 1).saturating_sub(visible) as u16;
+
+        frame.render_widget(Paragraph::new(lines).scroll((scroll, 0)).block(self.section(Section::Presets, String::from("Presets"))), area);
+    }
+}
+
+// label followed by a slider across the rest of the row
+fn bar(label: String, ratio: f64, width: u16) -> Paragraph<'static> {
+    let length = (width as usize).saturating_sub(label.chars().count());
+    let filled = (length as f64 * ratio).round() as usize;
+
+    Paragraph::new(Line::from(vec![
+        Span::raw(label),
+        Span::styled("━".repeat(filled), Style::new().fg(Color::Cyan)),
+        Span::styled("─".repeat(length - filled), Style::new().add_modifier(Modifier::DIM)),
+    ]))
+}
+
+// h 0-359, s and v 0-100
+fn rgb_to_hsv(rgb: [u8; 3]) -> [u16; 3] {
+    let [r, g, b] = rgb.map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+
+    let hue = match delta {
+        0.0 => 0.0,
+        d if max == r => 60.0 * ((g - b) / d).rem_euclid(6.0),
+        d if max == g => 60.0 * ((b - r) / d + 2.0),
+        d => 60.0 * ((r - g) / d + 4.0),
+    };
+
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    [hue.round() as u16 % 360, (saturation * 100.0).round() as u16, (max * 100.0).round() as u16]
+}
+
+fn hsv_to_rgb(hsv: [u16; 3]) -> [u8; 3] {
+    let h = hsv[0] as f32 / 60.0;
+    let s = hsv[1] as f32 / 100.0;
+    let v = hsv[2] as f32 / 100.0;
+
+    let c = v * s;
+    let x = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    [r, g, b].map(|channel| ((channel + v - c) * 255.0).round() as u8)
+}
+
+pub async fn run(config: Config) {
+    // before the backend starts, so what it prints from the start goes to the log
+    let (lines_tx, mut lines) = watch::channel(String::new());
+    let out = terminal_output(lines_tx);
+
+    let (tx2, mut rx2) = mpsc::channel(4);
+    let backend = crate::backend::start(&config, tx2).await;
+    let mut tui = Tui::new(config.presets.clone());
+    tui.ui_state.unsafe_flashing = config.safety.allow_unsafe_flashing;
+
+    // crossterm's event::read blocks, so it gets a thread of its own
+    let (event_tx, mut events) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if event_tx.blocking_send(event).is_err() {
+                break; //tui is gone
+            }
+        }
+    });
+
+    let mut terminal = match init(out) {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            restore();
+            return println!("can't start the tui: {e}");
+        }
+    };
+
+    let mut spinner = tokio::time::interval(SPINNER_TIME);
+
+    loop {
+        if let Err(e) = terminal.draw(|frame| tui.draw(frame)) {
+            restore();
+            return println!("can't draw: {e}");
+        }
+
+        tokio::select! {
+            Some(bt_recv) = rx2.recv() => tui.ui_state.handle_bt(bt_recv),
+            Some(event) = events.recv() => {
+                if let Event::Key(key) = event {
+                    tui.handle_key(key, &backend.tx);
+                }
+            }
+            Ok(()) = lines.changed() => tui.status = Some(lines.borrow_and_update().clone()),
+            _ = spinner.tick(), if !tui.ready() => tui.spinner += 1,
+            else => break,
+        }
+
+        if tui.ui_state.quit {
+            break;
+        }
+    }
+
+    restore();
+
+    #[cfg(unix)]
+    if let (Some(_), Some(path)) = (tui.status, crate::capture::log_path()) {
+        println!("messages from while the tui was up are in {}", path.display());
+    }
+}
+
+// stdout and stderr get captured where that's supported, see capture.rs
+fn terminal_output(lines: watch::Sender<String>) -> Box<dyn Write + Send> {
+    #[cfg(unix)]
+    match crate::capture::start(lines) {
+        Ok(terminal) => return Box::new(terminal),
+        Err(e) => println!("can't capture output, messages will mess up the screen: {e}"),
+    }
+
+    #[cfg(not(unix))]
+    drop(lines);
+
+    Box::new(std::io::stdout())
+}
+
+// like ratatui::init, on whatever the terminal ended up being
+fn init(mut out: Box<dyn Write + Send>) -> std::io::Result<Terminal<CrosstermBackend<Box<dyn Write + Send>>>> {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        hook(info);
+    }));
+
+    terminal::enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen)?;
+    Terminal::new(CrosstermBackend::new(out))
+}
+
+fn restore() {
+    #[cfg(unix)]
+    crate::capture::release();
+
+    ratatui::restore(); //raw mode and the alternate screen, on stdout which is the terminal again
+}
@@ -1,6 +1,47 @@
+use serde::Deserialize;
 use tokio::sync::mpsc;
-use egui::{Context, Color32, TextStyle, FontId};
-use crate::bt::{BtCommands, BtToGui, CmdData};
+use egui::Context;
+use crate::bt::{BtCommands, BtToGui, CmdData, MODES};
+use crate::config::Preset;
+use crate::doctor;
+use crate::keys::{Action, Bindings};
+use crate::palette::Palette;
+use crate::preview::Preview;
+use crate::theme::{self, Themes};
+
+const BRIGHTNESS_STEP: u8 = 8; //of 63
+
+// [gui] in the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GuiConfig {
+    pub zoom: f32, //on top of the display's scale factor
+    pub tray: bool, //linux only
+    pub start_minimized: bool, //to the tray, same as --minimized
+    pub theme: String, //see theme.rs
+}
+
+impl Default for GuiConfig {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            tray: true,
+            start_minimized: false,
+            theme: String::from(theme::AUTO),
+        }
+    }
+}
+
+// what wakes up the event loop besides window events
+#[derive(Debug)]
+pub enum GuiEvent {
+    Bt(BtToGui),
+    Action(Action), //global hotkeys
+    SystemDark(bool), //the desktop's color scheme
+    ToggleWindow,
+    TrayGone, //no tray to get the window back from, so don't hide it
+    Quit,
+}
 
 #[derive(Default)]
 pub struct UiState {
@@ -8,29 +49,115 @@ pub struct UiState {
     pub headset_type: String,
     pub headset_color: [u8; 3],
     pub headset_settings: [u8; 2],
+    pub headset_mode: u8, //last mode sent, 0 if none yet
+    pub hovered_mode: Option<u8>, //previewed instead of headset_mode
+    pub preview: Preview,
+    pub presets: Vec<Preset>,
+    pub bindings: Bindings,
+    pub themes: Themes,
+    pub palette: Palette,
+    pub presets_open: bool,
+    pub doctor: doctor::Page,
+    pub unsafe_flashing: bool, //allow_unsafe_flashing in [safety], shown under the headset name
+    pub quit: bool,
+}
+
+impl UiState {
+    pub fn handle_bt(&mut self, bt_recv: BtToGui) {
+        if let BtToGui::Found(headset) = &bt_recv {
+            self.headset_type = headset.name().to_string();
+        }
+
+        self.bt_state = bt_recv;
+    }
 }
 
 pub fn create_ui(ctx: &mut Context, tx: &mpsc::Sender<BtCommands>, ui_state: &mut UiState) {
-    let mut central_frame = egui::containers::Frame::default();
-    central_frame.inner_margin = egui::style::Margin { left: 15.0, right: 15.0, top: 15.0, bottom: 15.0 };
-    central_frame.fill = Color32::from_rgb(0xB4, 0xE4, 0xFF);
+    ui_state.themes.apply_if_changed(ctx);
+
+    // escape closes the topmost dialog
+    if ctx.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+        if ui_state.palette.is_open() {
+            ui_state.palette.close();
+        } else if ui_state.doctor.is_open() {
+            ui_state.doctor.close();
+        } else {
+            ui_state.presets_open = false;
+        }
+    }
+
+    let mut actions = ui_state.bindings.pressed(ctx);
+
+    let palette_actions: Vec<Action> = (1 ..= 8).map(Action::Mode)
+        .chain(ui_state.presets.iter().map(|preset| Action::Preset(preset.name.clone())))
+        .chain([Action::ApplyColor, Action::BrightnessUp, Action::BrightnessDown, Action::Presets, Action::Doctor])
+        .chain(ui_state.themes.names().map(|name| Action::Theme(name.to_string())))
+        .chain([Action::Quit])
+        .collect();
+
+    actions.extend(ui_state.palette.show(ctx, &palette_actions, &ui_state.bindings));
+
+    let mut presets_open = ui_state.presets_open;
+
+    egui::Window::new("Presets")
+        .open(&mut presets_open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            if ui_state.presets.is_empty() {
+                ui.label("No presets yet, add some to the config file.");
+            }
+
+            for preset in &ui_state.presets {
+                if ui.button(&preset.name).clicked() {
+                    actions.push(Action::Preset(preset.name.clone()));
+                }
+            }
+        });
+
+    ui_state.presets_open = presets_open;
+    ui_state.doctor.show(ctx);
+
+    for action in actions {
+        run_action(action, tx, ui_state);
+    }
+
+    let theme = ui_state.themes.current().clone();
+    let text_color = theme.text_color();
+
+    let central_frame = egui::containers::Frame {
+        inner_margin: egui::style::Margin::same(theme.margin),
+        fill: theme.background_color(),
+        ..Default::default()
+    };
 
     egui::CentralPanel::default()
     .frame(central_frame)
     .show(ctx, |ui| {
         match ui_state.bt_state {
             BtToGui::Ready => {
-                ui.colored_label(Color32::from_rgb(21, 40, 51), &ui_state.headset_type);
+                ui.colored_label(text_color, &ui_state.headset_type);
+
+                if ui_state.unsafe_flashing {
+                    ui.colored_label(egui::Color32::from_rgb(230, 60, 60), "Flash limits are off");
+                }
 
-                ui.add_space(18.0);
+                let preview = CmdData {
+                    mode: ui_state.hovered_mode.take().unwrap_or(ui_state.headset_mode),
+                    rgb: ui_state.headset_color,
+                    settings: ui_state.headset_settings,
+                };
 
-                ui.colored_label(Color32::from_rgb(21, 40, 51), "Color:");
+                ui_state.preview.show(ui, preview);
+
+                ui.add_space(theme.spacing);
+
+                ui.colored_label(text_color, "Color:");
                 ui.horizontal(|ui| {
                     ui.color_edit_button_srgb(&mut ui_state.headset_color);
 
                     if ui.button("Apply").clicked() {
-                        let mut data = CmdData::default();
-                        data.rgb = ui_state.headset_color;
+                        let data = CmdData { rgb: ui_state.headset_color, ..Default::default() };
 
                         match tx.try_send(BtCommands::SetMode(data)) {
                             Ok(_) => (),
@@ -39,26 +166,29 @@ pub fn create_ui(ctx: &mut Context, tx: &mpsc::Sender<BtCommands>, ui_state: &mu
                     }
                 });
 
-                ui.add_space(18.0);
-
-                let modes = [
-                    "Default",   "Flash",
-                    "Breath",    "Rhythm",
-                    "Yowu",      "Lights off",
-                    "Lights on", "?",
-                ];
+                ui.add_space(theme.spacing);
 
                 let chunk_size = 2;
 
-                ui.colored_label(Color32::from_rgb(21, 40, 51), "Mode:");
+                ui.colored_label(text_color, "Mode:");
 
-                for (idx, mode_chunk) in modes.chunks(chunk_size).enumerate() {
+                for (idx, mode_chunk) in MODES.chunks(chunk_size).enumerate() {
                     ui.horizontal(|ui| {
                         for (idx2, &mode) in mode_chunk.iter().enumerate() {
-                            let button = ui.add_sized([90.0, 22.0], egui::Button::new(mode));
+                            let mode_idx = (idx * chunk_size + idx2 + 1) as u8;
+                            let mut button = ui.add_sized([90.0, 22.0], egui::Button::new(mode));
+
+                            if let Some(shortcut) = ui_state.bindings.shortcut(&Action::Mode(mode_idx)) {
+                                button = button.on_hover_text(ctx.format_shortcut(shortcut));
+                            }
+
+                            if button.hovered() {
+                                ui_state.hovered_mode = Some(mode_idx);
+                            }
+
                             if button.clicked() {
-                                let mut data = CmdData::default();
-                                data.mode = (idx * chunk_size + idx2 + 1) as u8;
+                                ui_state.headset_mode = mode_idx;
+                                let data = CmdData { mode: mode_idx, ..Default::default() };
 
                                 match tx.try_send(BtCommands::SetMode(data)) {
                                     Ok(_) => (),
@@ -69,20 +199,20 @@ pub fn create_ui(ctx: &mut Context, tx: &mpsc::Sender<BtCommands>, ui_state: &mu
                     });
                 }
 
-                ui.add_space(18.0);
+                ui.add_space(theme.spacing);
 
-                ui.colored_label(Color32::from_rgb(21, 40, 51), "Settings:");
+                ui.colored_label(text_color, "Settings:");
 
                 let settings = [
                     "Brightness", "Speed", //todo: figure out ranges
                     "BPM", "Duration", //if miku is detected. need to change ranges as well?
                 ];
 
-                for x in 0 .. 2 {
+                for (x, &setting) in settings.iter().enumerate().take(2) {
                     ui.horizontal(|ui| {
                         ui.add(egui::Slider::new(&mut ui_state.headset_settings[x], 0 ..= 63)
-                            .text(settings[x])
-                            .text_color(Color32::from_rgb(21, 40, 51)));
+                            .text(setting)
+                            .text_color(text_color));
     
                         if ui.button("apply").clicked() {
                             let mut data = CmdData::default();
@@ -107,37 +237,73 @@ pub fn create_ui(ctx: &mut Context, tx: &mpsc::Sender<BtCommands>, ui_state: &mu
                         BtToGui::Ready => unreachable!(),
                     };
 
-                    ui.colored_label(Color32::from_rgb(21, 40, 51), status);
+                    ui.colored_label(text_color, status);
                     ui.spinner();
                 });
+
+                // stuck before there's even a headset, usually something in the bluetooth setup
+                if matches!(ui_state.bt_state, BtToGui::Init | BtToGui::AdapterConnected) && ui.button("Diagnose").clicked() {
+                    ui_state.doctor.open();
+                }
             }
         };
     });
 }
 
-pub fn set_egui_visuals(ctx: &mut Context) {
-    use egui::FontFamily::Proportional;
+pub fn run_action(action: Action, tx: &mpsc::Sender<BtCommands>, ui_state: &mut UiState) {
+    let ready = matches!(ui_state.bt_state, BtToGui::Ready);
+
+    let command = match action {
+        Action::Presets => {
+            ui_state.presets_open = true;
+            return;
+        }
 
-    ctx.set_pixels_per_point(2.0);
+        Action::Doctor => return ui_state.doctor.open(),
+        Action::Palette => return ui_state.palette.open(),
+        Action::Theme(name) => return ui_state.themes.select(&name),
+        Action::Quit => {
+            ui_state.quit = true;
+            return;
+        }
 
-    let mut visuals = egui::Visuals::default();
-    visuals.widgets.inactive.weak_bg_fill = Color32::from_rgb(255, 206, 254); //button
-    visuals.widgets.hovered.weak_bg_fill = Color32::from_rgb(255, 153, 253);  //button, hover
-    visuals.widgets.inactive.fg_stroke.color = Color32::from_rgb(76, 0, 51);  //button text
-    visuals.widgets.hovered.fg_stroke.color = Color32::from_rgb(51, 0, 34);   //button text, hover
-    visuals.widgets.inactive.bg_fill = Color32::from_rgb(201, 244, 170);
+        _ if !ready => return, //the rest needs a headset
 
-    ctx.set_visuals(visuals);
+        Action::Mode(mode) => {
+            ui_state.headset_mode = mode;
+            CmdData { mode, ..Default::default() }
+        }
 
-    let mut style = (*ctx.style()).clone();
+        Action::ApplyColor => CmdData { rgb: ui_state.headset_color, ..Default::default() },
+        Action::BrightnessUp | Action::BrightnessDown => {
+            let brightness = &mut ui_state.headset_settings[0];
+
+            *brightness = match action {
+                Action::BrightnessUp => brightness.saturating_add(BRIGHTNESS_STEP).min(63),
+                _ => brightness.saturating_sub(BRIGHTNESS_STEP).max(1), //0 would leave it as it is
+            };
+
+            CmdData { settings: [*brightness, 0], ..Default::default() }
+        }
+
+        Action::Preset(name) => {
+            let Some(preset) = Preset::find(&ui_state.presets, &name) else {
+                return println!("no preset named \"{name}\"");
+            };
+
+            // so the controls and the preview show what got applied
+            ui_state.headset_color = preset.rgb;
+            ui_state.headset_settings = preset.settings;
+            if preset.mode != 0 {
+                ui_state.headset_mode = preset.mode;
+            }
 
-    style.text_styles = [
-        (TextStyle::Heading, FontId::new(15.0, Proportional)),
-        (TextStyle::Body, FontId::new(15.0, Proportional)),
-        (TextStyle::Monospace, FontId::new(15.0, Proportional)),
-        (TextStyle::Button, FontId::new(15.0, Proportional)),
-        (TextStyle::Small, FontId::new(15.0, Proportional)),
-    ].into();
+            CmdData::from(preset)
+        }
+    };
 
-    ctx.set_style(style);
+    match tx.try_send(BtCommands::SetMode(command)) {
+        Ok(_) => (),
+        Err(_) => println!("queue full!"),
+    };
 }
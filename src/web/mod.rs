@@ -0,0 +1,595 @@
+// web ui for phones and tablets, served from index.html next to this file with nothing loaded from
+// elsewhere. the page talks json over a websocket on /ws:
+//   {"type":"set_mode","mode":2,"rgb":[255,0,0],"settings":[0,0]}     0 = leave as it is, like CmdData
+//   {"type":"set_audio_profile","profile":2}
+//   {"type":"apply_preset","name":"on air"}
+// and gets a hello with the modes and presets, then a state whenever something changes.
+// clients on this machine are let in right away if they asked for it by a name that can only mean
+// this machine (so not a dns rebinding page), others have to pair with the pin first (POST /pair),
+// which gets them a session cookie that lasts until catcaller restarts.
+
+use std::collections::HashSet;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch};
+use tokio::time::{timeout, Duration, Instant};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::bt::{BtCommands, BtToGui, CmdData, Coalescer, HeadsetState, MODES};
+use crate::config::Preset;
+
+const INDEX: &str = include_str!("index.html");
+const SESSION_COOKIE: &str = "catcaller_session";
+const MAX_HEAD: u64 = 8192; //request line + headers
+const MAX_BODY: usize = 64; //a pin
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const PIN_LOCKOUT: Duration = Duration::from_secs(2); //after a wrong pin, for everyone
+
+// [web] in the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebConfig {
+    pub bind: SocketAddr, //0.0.0.0:<port> to let the rest of the lan in
+    pub pin: Option<String>, //a random one is printed at startup if there's none
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([127, 0, 0, 1], 8421)),
+            pin: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    SetMode(CmdData),
+    SetAudioProfile { profile: u8 },
+    ApplyPreset { name: String },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Update<'a> {
+    Hello { modes: &'a [&'a str], presets: Vec<&'a str> },
+    State {
+        status: &'static str,
+        headset: Option<&'static str>,
+        lighting: Option<CmdData>,
+        audio_profile: Option<u8>,
+        battery: Option<u8>,
+    },
+    Error { message: String },
+}
+
+struct Shared {
+    pin: String,
+    hosts: Vec<String>, //Host headers, without the port, that only reach this machine
+    sessions: Mutex<HashSet<String>>,
+    next_attempt: Mutex<Instant>, //wrong pins push this back
+    presets: Vec<Preset>,
+    tx: mpsc::Sender<BtCommands>,
+    lighting: Coalescer, //sliders send a lot more than the headset takes
+    state_rx: watch::Receiver<HeadsetState>,
+}
+
+pub async fn serve(config: WebConfig, presets: Vec<Preset>, tx: mpsc::Sender<BtCommands>, state_rx: watch::Receiver<HeadsetState>) {
+    let listener = match TcpListener::bind(config.bind).await {
+        Ok(listener) => listener,
+        Err(e) => return println!("web: can't listen on {}: {e}", config.bind),
+    };
+
+    run(listener, config, presets, tx, state_rx).await
+}
+
+async fn run(listener: TcpListener, config: WebConfig, presets: Vec<Preset>, tx: mpsc::Sender<BtCommands>, state_rx: watch::Receiver<HeadsetState>) {
+    let pin = match config.pin {
+        Some(pin) => pin,
+        None => {
+            // printed even when only this machine can connect, for browsers on it that use another name
+            let pin = format!("{:06}", u32::from_le_bytes(random()) % 1_000_000);
+            println!("web: pairing pin for http://{} is {pin}", config.bind);
+            pin
+        }
+    };
+
+    let mut hosts = vec![String::from("localhost"), String::from("127.0.0.1"), String::from("[::1]")];
+
+    match config.bind.ip() {
+        ip if ip.is_unspecified() => (),
+        IpAddr::V4(ip) => hosts.push(ip.to_string()),
+        IpAddr::V6(ip) => hosts.push(format!("[{ip}]")),
+    }
+
+    let shared = Arc::new(Shared {
+        pin,
+        hosts,
+        sessions: Mutex::new(HashSet::new()),
+        next_attempt: Mutex::new(Instant::now()),
+        presets,
+        lighting: Coalescer::new(tx.clone()),
+        tx,
+        state_rx,
+    });
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                println!("web: {e}");
+                continue;
+            }
+        };
+
+        let shared = shared.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, peer, shared).await {
+                if e.kind() != io::ErrorKind::UnexpectedEof {
+                    println!("web: {peer}: {e}");
+                }
+            }
+        });
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>, //names lowercased
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    fn cookie(&self, name: &str) -> Option<&str> {
+        self.header("cookie")?.split(';').find_map(|cookie| {
+            let (n, value) = cookie.trim().split_once('=')?;
+            (n == name).then_some(value)
+        })
+    }
+
+    // the host it was asked for, without the port
+    fn host_name(&self) -> Option<String> {
+        let host = self.header("host")?;
+
+        let name = match host.find(']') {
+            Some(end) => &host[..= end],
+            None => host.split(':').next().unwrap_or(host),
+        };
+
+        Some(name.to_ascii_lowercase())
+    }
+
+    // browsers always send an origin with websockets and posts, so another site can't use the
+    // trust given to clients on this machine. curl and scripts don't send one
+    fn same_origin(&self) -> bool {
+        match (self.header("origin"), self.header("host")) {
+            (None, _) => true,
+            (Some(origin), Some(host)) => origin.strip_prefix("http://") == Some(host),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+async fn handle(stream: TcpStream, peer: SocketAddr, shared: Arc<Shared>) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+
+    let request = match timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(Some(request))) => request,
+        Ok(Ok(None)) => return respond(&mut stream, "400 Bad Request", &[], "").await,
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return Ok(()), //too slow, just hang up
+    };
+
+    // a page that rebinds its own name to 127.0.0.1 comes from this machine too, but with its name
+    let local = peer.ip().is_loopback() && request.host_name().is_some_and(|name| shared.hosts.contains(&name));
+    let authorized = local || request.cookie(SESSION_COOKIE).is_some_and(|token| shared.sessions.lock().unwrap().contains(token));
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => respond(&mut stream, "200 OK", &[("Content-Type", "text/html; charset=utf-8")], INDEX).await,
+        ("GET", "/session") if authorized => respond(&mut stream, "204 No Content", &[], "").await,
+        ("GET", "/session") => respond(&mut stream, "401 Unauthorized", &[], "").await,
+        (_, "/pair" | "/ws") if !request.same_origin() => respond(&mut stream, "403 Forbidden", &[], "wrong origin").await,
+        ("POST", "/pair") => pair(&mut stream, &request, &shared).await,
+        ("GET", "/ws") if !authorized => respond(&mut stream, "401 Unauthorized", &[], "pair first").await,
+        ("GET", "/ws") => websocket(stream, &request, &shared).await,
+        _ => respond(&mut stream, "404 Not Found", &[], "").await,
+    }
+}
+
+// None for anything that isn't a request we can make sense of
+async fn read_request<S: AsyncRead + Unpin>(stream: &mut BufReader<S>) -> io::Result<Option<HttpRequest>> {
+    let mut head = (&mut *stream).take(MAX_HEAD);
+    let mut line = String::new();
+
+    head.read_line(&mut line).await?;
+
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+
+    let method = method.to_string();
+    let path = target.split('?').next().unwrap_or(target).to_string();
+    let mut headers = Vec::new();
+
+    loop {
+        line.clear();
+
+        if head.read_line(&mut line).await? == 0 {
+            return Ok(None); //ran out before the end of the headers
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+
+        let Some((name, value)) = line.split_once(':') else {
+            return Ok(None);
+        };
+
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    let mut request = HttpRequest { method, path, headers, body: Vec::new() };
+    let length: usize = request.header("content-length").and_then(|length| length.parse().ok()).unwrap_or(0);
+
+    if length > MAX_BODY {
+        return Ok(None);
+    }
+
+    request.body.resize(length, 0);
+    stream.read_exact(&mut request.body).await?;
+
+    Ok(Some(request))
+}
+
+async fn respond<S: AsyncWrite + Unpin>(stream: &mut S, status: &str, headers: &[(&str, &str)], body: &str) -> io::Result<()> {
+    let mut response = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\nCache-Control: no-store\r\n", body.len());
+
+    for (name, value) in headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+
+    response.push_str("\r\n");
+    response.push_str(body);
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await
+}
+
+// the body is just the pin
+async fn pair<S: AsyncWrite + Unpin>(stream: &mut S, request: &HttpRequest, shared: &Shared) -> io::Result<()> {
+    let refused = {
+        let now = Instant::now();
+        let mut next_attempt = shared.next_attempt.lock().unwrap();
+
+        if now < *next_attempt {
+            Some(("429 Too Many Requests", "wait a moment"))
+        } else if String::from_utf8_lossy(&request.body).trim() != shared.pin {
+            *next_attempt = now + PIN_LOCKOUT;
+            Some(("403 Forbidden", "wrong pin"))
+        } else {
+            None
+        }
+    };
+
+    if let Some((status, body)) = refused {
+        return respond(stream, status, &[], body).await;
+    }
+
+    let token: String = random::<16>().iter().map(|byte| format!("{byte:02x}")).collect();
+    shared.sessions.lock().unwrap().insert(token.clone());
+
+    let cookie = format!("{SESSION_COOKIE}={token}; Path=/; HttpOnly; SameSite=Strict");
+    respond(stream, "204 No Content", &[("Set-Cookie", &cookie)], "").await
+}
+
+async fn websocket<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, request: &HttpRequest, shared: &Shared) -> io::Result<()> {
+    let upgrade = request.header("upgrade").is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+
+    let (true, Some(key)) = (upgrade, request.header("sec-websocket-key")) else {
+        return respond(&mut stream, "400 Bad Request", &[], "websockets only").await;
+    };
+
+    let response = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", derive_accept_key(key.as_bytes()));
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await?;
+
+    let mut socket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+    let mut state_rx = shared.state_rx.clone();
+
+    let hello = Update::Hello { modes: &MODES, presets: shared.presets.iter().map(|preset| preset.name.as_str()).collect() };
+    send(&mut socket, &hello).await?;
+
+    let update = state_update(&state_rx.borrow_and_update());
+    send(&mut socket, &update).await?;
+
+    loop {
+        tokio::select! {
+            message = socket.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => continue, //pings are answered by tungstenite
+                    Some(Err(e)) => return Err(io::Error::other(e)),
+                };
+
+                if let Err(message) = command(&text, shared).await {
+                    send(&mut socket, &Update::Error { message }).await?;
+                }
+            }
+
+            changed = state_rx.changed() => {
+                if changed.is_err() {
+                    return Ok(()); //backend is gone
+                }
+
+                let update = state_update(&state_rx.borrow_and_update());
+                send(&mut socket, &update).await?;
+            }
+        }
+    }
+}
+
+async fn command(text: &str, shared: &Shared) -> Result<(), String> {
+    let request: Request = serde_json::from_str(text).map_err(|e| format!("bad request: {e}"))?;
+
+    if !matches!(shared.state_rx.borrow().status, BtToGui::Ready) {
+        return Err(String::from("headset not connected"));
+    }
+
+    // a preset merged with slider moves still waiting is the same as the moves and then the preset
+    let sent = match request {
+        Request::SetMode(data) => shared.lighting.send(data),
+        Request::SetAudioProfile { profile } => shared.tx.send(BtCommands::SetAudioProfile(profile)).await.is_ok(),
+        Request::ApplyPreset { name } => match Preset::find(&shared.presets, &name) {
+            Some(preset) => shared.lighting.send(CmdData::from(preset)),
+            None => return Err(format!("no preset named \"{name}\"")),
+        },
+    };
+
+    match sent {
+        true => Ok(()),
+        false => Err(String::from("bluetooth task is gone")),
+    }
+}
+
+fn state_update(state: &HeadsetState) -> Update<'static> {
+    Update::State {
+        status: state.status.name(),
+        headset: state.headset.map(|model| model.name()),
+        lighting: state.lighting,
+        audio_profile: state.audio_profile,
+        battery: state.battery,
+    }
+}
+
+async fn send<S: AsyncRead + AsyncWrite + Unpin>(socket: &mut WebSocketStream<S>, update: &Update<'_>) -> io::Result<()> {
+    let text = serde_json::to_string(update).map_err(io::Error::other)?;
+    socket.send(Message::Text(text)).await.map_err(io::Error::other)
+}
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).expect("no random numbers from the os");
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+    use super::*;
+
+    struct Server {
+        addr: SocketAddr,
+        rx: mpsc::Receiver<BtCommands>,
+        state_tx: watch::Sender<HeadsetState>,
+    }
+
+    // on a port of its own, in front of a headset that's ready
+    async fn server() -> Server {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = WebConfig { bind: addr, pin: Some(String::from("4242")) };
+        let presets = vec![Preset { name: String::from("On Air"), mode: 7, rgb: [255, 0, 0], settings: [63, 0] }];
+
+        let (tx, rx) = mpsc::channel(8);
+        let (state_tx, state_rx) = watch::channel(HeadsetState { status: BtToGui::Ready, ..Default::default() });
+
+        tokio::spawn(run(listener, config, presets, tx, state_rx));
+        Server { addr, rx, state_tx }
+    }
+
+    // the status code and the whole response
+    async fn http(addr: SocketAddr, request: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        (response[9 .. 12].parse().unwrap(), response)
+    }
+
+    async fn get(addr: SocketAddr, path: &str, host: &str, cookie: Option<&str>) -> u16 {
+        let cookie = cookie.map(|cookie| format!("Cookie: {cookie}\r\n")).unwrap_or_default();
+        http(addr, &format!("GET {path} HTTP/1.1\r\nHost: {host}\r\n{cookie}\r\n")).await.0
+    }
+
+    async fn pair(addr: SocketAddr, host: &str, pin: &str) -> (u16, String) {
+        let request = format!("POST /pair HTTP/1.1\r\nHost: {host}\r\nOrigin: http://{host}\r\nContent-Length: {}\r\n\r\n{pin}", pin.len());
+        http(addr, &request).await
+    }
+
+    type Socket = WebSocketStream<TcpStream>;
+
+    async fn connect(addr: SocketAddr, host: &str, cookie: Option<&str>) -> Result<Socket, u16> {
+        let mut request = format!("ws://{addr}/ws").into_client_request().unwrap();
+        let headers = request.headers_mut();
+
+        headers.insert("host", HeaderValue::from_str(host).unwrap());
+        headers.insert("origin", HeaderValue::from_str(&format!("http://{host}")).unwrap());
+
+        if let Some(cookie) = cookie {
+            headers.insert("cookie", HeaderValue::from_str(cookie).unwrap());
+        }
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+
+        match tokio_tungstenite::client_async(request, stream).await {
+            Ok((socket, _)) => Ok(socket),
+            Err(tokio_tungstenite::tungstenite::Error::Http(response)) => Err(response.status().as_u16()),
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    async fn receive(socket: &mut Socket) -> serde_json::Value {
+        loop {
+            if let Message::Text(text) = socket.next().await.unwrap().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn local_names_are_let_in() {
+        let server = server().await;
+        let port = server.addr.port();
+
+        for host in [format!("localhost:{port}"), format!("127.0.0.1:{port}"), format!("[::1]:{port}"), format!("LocalHost:{port}")] {
+            assert_eq!(get(server.addr, "/session", &host, None).await, 204, "{host}");
+            assert!(connect(server.addr, &host, None).await.is_ok(), "{host}");
+        }
+    }
+
+    #[tokio::test]
+    async fn other_names_have_to_pair() {
+        let server = server().await;
+        let host = format!("evil.example:{}", server.addr.port());
+
+        // a dns rebinding page: from this machine, with a matching origin, but not by its name
+        assert_eq!(get(server.addr, "/session", &host, None).await, 401);
+        assert_eq!(connect(server.addr, &host, None).await.err(), Some(401));
+        assert_eq!(get(server.addr, "/", &host, None).await, 200);
+    }
+
+    #[tokio::test]
+    async fn other_origins_are_refused() {
+        let server = server().await;
+        let request = format!("GET /ws HTTP/1.1\r\nHost: localhost:{}\r\nOrigin: http://evil.example\r\nUpgrade: websocket\r\n\r\n", server.addr.port());
+
+        assert_eq!(http(server.addr, &request).await.0, 403);
+    }
+
+    #[tokio::test]
+    async fn pairing() {
+        let server = server().await;
+        let host = format!("catcaller.lan:{}", server.addr.port());
+
+        assert_eq!(pair(server.addr, &host, "1234").await.0, 403);
+        assert_eq!(pair(server.addr, &host, "4242").await.0, 429, "locked out after a wrong pin, even with the right one");
+
+        tokio::time::sleep(PIN_LOCKOUT).await;
+        let (status, response) = pair(server.addr, &host, "4242").await;
+        assert_eq!(status, 204);
+
+        let cookie = response.lines().find_map(|line| line.strip_prefix("Set-Cookie: ")).unwrap();
+        let cookie = cookie.split(';').next().unwrap();
+
+        assert_eq!(get(server.addr, "/session", &host, Some(cookie)).await, 204);
+        assert!(connect(server.addr, &host, Some(cookie)).await.is_ok());
+
+        let made_up = format!("{SESSION_COOKIE}=00112233445566778899aabbccddeeff");
+        assert_eq!(get(server.addr, "/session", &host, Some(&made_up)).await, 401);
+        assert_eq!(connect(server.addr, &host, Some(&made_up)).await.err(), Some(401));
+    }
+
+    #[tokio::test]
+    async fn commands() {
+        let mut server = server().await;
+        let mut socket = connect(server.addr, &format!("localhost:{}", server.addr.port()), None).await.unwrap();
+
+        let hello = receive(&mut socket).await;
+        assert_eq!(hello["type"], "hello");
+        assert_eq!(hello["presets"], serde_json::json!(["On Air"]));
+        assert_eq!(receive(&mut socket).await["status"], "ready");
+
+        let requests = [
+            r#"{"type":"set_mode","mode":2,"rgb":[0,0,255],"settings":[10,0]}"#,
+            r#"{"type":"set_audio_profile","profile":3}"#,
+            r#"{"type":"apply_preset","name":"on air"}"#,
+        ];
+
+        let mut sent = Vec::new();
+
+        // one at a time, lighting sent together could go out merged
+        for request in requests {
+            socket.send(Message::Text(request.to_string())).await.unwrap();
+            sent.push(format!("{:?}", server.rx.recv().await.unwrap()));
+        }
+
+        assert_eq!(sent, [
+            "SetMode(CmdData { mode: 2, rgb: [0, 0, 255], settings: [10, 0] })",
+            "SetAudioProfile(3)",
+            "SetMode(CmdData { mode: 7, rgb: [255, 0, 0], settings: [63, 0] })",
+        ]);
+
+        socket.send(Message::Text(String::from(r#"{"type":"apply_preset","name":"off air"}"#))).await.unwrap();
+        assert_eq!(receive(&mut socket).await["message"], "no preset named \"off air\"");
+
+        socket.send(Message::Text(String::from("{}"))).await.unwrap();
+        assert_eq!(receive(&mut socket).await["type"], "error");
+
+        server.state_tx.send_modify(|state| state.battery = Some(40));
+        assert_eq!(receive(&mut socket).await["battery"], 40);
+    }
+
+    // sliders dragged faster than the headset takes it: values in between can go, the preset and
+    // the audio profile at the end can't
+    #[tokio::test]
+    async fn flood() {
+        let mut server = server().await;
+        let mut socket = connect(server.addr, &format!("localhost:{}", server.addr.port()), None).await.unwrap();
+
+        for value in 1 ..= 100 {
+            let request = format!(r#"{{"type":"set_mode","mode":0,"rgb":[0,0,0],"settings":[0,{}]}}"#, value * 63 / 100);
+            socket.send(Message::Text(request)).await.unwrap();
+        }
+
+        socket.send(Message::Text(String::from(r#"{"type":"apply_preset","name":"on air"}"#))).await.unwrap();
+        socket.send(Message::Text(String::from(r#"{"type":"set_audio_profile","profile":2}"#))).await.unwrap();
+
+        let expected = CmdData { mode: 7, rgb: [255, 0, 0], settings: [63, 63] };
+        let (mut shown, mut audio_profile) = (CmdData::default(), None);
+
+        while shown != expected || audio_profile.is_none() {
+            match timeout(Duration::from_secs(2), server.rx.recv()).await {
+                Ok(Some(BtCommands::SetMode(data))) => shown = shown.merge(data),
+                Ok(Some(BtCommands::SetAudioProfile(profile))) => audio_profile = Some(profile),
+                other => panic!("{other:?}, got to {shown:?}"),
+            }
+        }
+
+        assert_eq!(audio_profile, Some(2));
+    }
+}
@@ -0,0 +1,102 @@
+// the gui on a gpu-less X server: Xvfb has no GL of its own worth mentioning, so this is the setup
+// the software renderer exists for. skipped where there's no Xvfb, unless CI is set
+#![cfg(target_os = "linux")]
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+// an X server of our own, gone when dropped
+struct Xvfb(Child, PathBuf);
+
+impl Drop for Xvfb {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+        let _ = std::fs::remove_dir_all(&self.1);
+    }
+}
+
+// None without an Xvfb to run. under CI that fails instead, a skipped test there would only hide
+// that it never ran
+fn xvfb(name: &str) -> Option<(Xvfb, String)> {
+    let dir = std::env::temp_dir().join(format!("catcaller-xvfb-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).ok()?;
+
+    // a display number nothing else is likely to use, one per test
+    let display = format!(":{}", 100 + std::process::id() % 400 + name.len() as u32 * 400);
+
+    let child = Command::new("Xvfb")
+        .args([display.as_str(), "-screen", "0", "800x600x24", "-nolisten", "tcp"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    let xvfb = match child {
+        Ok(child) => Xvfb(child, dir),
+        Err(e) => {
+            assert!(std::env::var_os("CI").is_none(), "no Xvfb ({e}), and CI is set");
+            println!("no Xvfb ({e}), skipping");
+            return None;
+        }
+    };
+
+    let socket = format!("/tmp/.X11-unix/X{}", &display[1 ..]);
+    let start = Instant::now();
+
+    while !Path::new(&socket).exists() {
+        assert!(start.elapsed() < Duration::from_secs(10), "Xvfb never came up");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    Some((xvfb, display))
+}
+
+// runs the gui for a few seconds with `env` on top of a clean config, returns what it printed
+fn run_gui(display: &str, home: &Path, env: &[(&str, &str)]) -> String {
+    let mut gui = Command::new(env!("CARGO_BIN_EXE_blatand"))
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("DISPLAY", display)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_RUNTIME_DIR", home)
+        .env("CATCALLER_FRAME_STATS", "1")
+        .envs(env.iter().copied())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    std::thread::sleep(Duration::from_secs(3));
+    let exited = gui.try_wait().unwrap();
+    let _ = gui.kill();
+    let _ = gui.wait();
+
+    let mut output = String::new();
+    gui.stdout.take().unwrap().read_to_string(&mut output).unwrap();
+    gui.stderr.take().unwrap().read_to_string(&mut output).unwrap();
+
+    assert_eq!(exited, None, "the gui quit early:\n{output}");
+    assert!(!output.contains("panicked"), "{output}");
+    output
+}
+
+#[test]
+fn software_renderer() {
+    let Some((xvfb, display)) = xvfb("software") else { return };
+
+    let output = run_gui(&display, &xvfb.1, &[("CATCALLER_RENDERER", "software")]);
+    assert!(!output.contains("using OpenGL"), "{output}");
+}
+
+// whatever GL Xvfb has (usually mesa's llvmpipe through GLX, sometimes nothing), the gui comes up,
+// on the GL painter that fits or on the software renderer
+#[test]
+fn gl_or_fallback() {
+    let Some((xvfb, display)) = xvfb("gl") else { return };
+
+    let output = run_gui(&display, &xvfb.1, &[("LIBGL_ALWAYS_SOFTWARE", "1")]);
+    assert!(output.contains("using OpenGL") || output.contains("falling back to the software renderer"), "{output}");
+}